@@ -0,0 +1,128 @@
+//! 在主机上比较各速率等级下压缩前后所需的数据包数量
+//!
+//! Compare the packets needed with and without compression for every rate class, on the host.
+//!
+//! ```text
+//! cargo run --example compression_bench --features std,compression --target x86_64-unknown-linux-gnu
+//! ```
+use hc14_at_rs::{
+    compress,
+    driver::frame::{FRAME_OVERHEAD, MAX_FRAME_SIZE},
+    setting::speed::Speed,
+};
+
+const SPEEDS: [Speed; 8] = [
+    Speed::S1,
+    Speed::S2,
+    Speed::S3,
+    Speed::S4,
+    Speed::S5,
+    Speed::S6,
+    Speed::S7,
+    Speed::S8,
+];
+
+/// 典型的遥测负载(Typical telemetry payloads)
+fn samples() -> Vec<(&'static str, Vec<u8>)> {
+    let mut csv = String::new();
+    for i in 0..40 {
+        csv.push_str(&format!(
+            "{},21.{},55.{},1013.{}\n",
+            1_700_000_000 + i * 60,
+            i % 10,
+            (i * 3) % 10,
+            i % 4
+        ));
+    }
+
+    let mut json = String::new();
+    for i in 0..12 {
+        json.push_str(&format!(
+            "{{\"node\":{},\"temp\":{}.{},\"hum\":{},\"bat\":{}}}\n",
+            i % 3,
+            20 + i % 4,
+            i % 10,
+            50 + i % 7,
+            3300 - i * 5
+        ));
+    }
+
+    let mut binary = Vec::new();
+    for i in 0u16..64 {
+        binary.extend_from_slice(&[0x01, 0x00]);
+        binary.extend_from_slice(&(2150 + i % 8).to_le_bytes());
+        binary.extend_from_slice(&(5500u16).to_le_bytes());
+    }
+
+    let mut noise = Vec::new();
+    let mut x: u32 = 0x1234_5678;
+    for _ in 0..480 {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        noise.push(x as u8);
+    }
+
+    vec![
+        ("csv log", csv.into_bytes()),
+        ("json", json.into_bytes()),
+        ("binary struct", binary),
+        ("random", noise),
+    ]
+}
+
+/// 原样发送所需的帧数(Frames needed uncompressed)
+fn raw_frames(data: &[u8], capacity: usize) -> usize {
+    data.len().div_ceil(capacity)
+}
+
+/// 每帧尽可能多地装入压缩后的数据，返回所需的帧数
+///
+/// Pack as much compressed input into each frame as fits, returns the frames needed.
+fn compressed_frames(data: &[u8], capacity: usize) -> usize {
+    let mut packed = [0u8; MAX_FRAME_SIZE * 2];
+    let mut frames = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        // 找到压缩后仍能放入一帧的最长输入
+        // Find the longest input that still fits one frame once compressed
+        let (mut lo, mut hi) = (1, data.len() - pos);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            let n = compress::compress(&data[pos..pos + mid], &mut packed).unwrap_or(mid);
+            if n.min(mid) <= capacity {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        pos += lo;
+        frames += 1;
+    }
+    frames
+}
+
+fn main() {
+    println!(
+        "{:<14} {:>5} {:>6} {:>6} {:>6} {:>8} {:>10}",
+        "payload", "speed", "bytes", "raw", "lzss", "saved", "airtime"
+    );
+    for (name, data) in samples() {
+        for speed in SPEEDS {
+            let capacity = speed.get_max_bytes_size() - FRAME_OVERHEAD;
+            let raw = raw_frames(&data, capacity);
+            let lzss = compressed_frames(&data, capacity);
+            let saved_ms = (raw - lzss) as u32 * speed.get_packet_delay_ms();
+            println!(
+                "{:<14} {:>5?} {:>6} {:>6} {:>6} {:>7.0}% {:>8}ms",
+                name,
+                speed,
+                data.len(),
+                raw,
+                lzss,
+                100.0 * (raw - lzss) as f32 / raw as f32,
+                saved_ms
+            );
+        }
+    }
+}
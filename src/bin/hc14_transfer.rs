@@ -0,0 +1,281 @@
+//! 通过串口上的 HC-14 收发文件
+//!
+//! Send and receive files through a serial-attached HC-14.
+//!
+//! 接收端把数据写入 `<name>.part`，完成后重命名为 `<name>`；中断后再次运行即可从 `.part`
+//! 的末尾续传。
+//!
+//! The receiver writes into `<name>.part` and renames it to `<name>` when done; run it again after an
+//! interruption and the transfer resumes from the end of the `.part` file.
+//!
+//! ```text
+//! stty -F /dev/ttyUSB0 9600 raw -echo
+//! cargo run --bin hc14_transfer --features std --target x86_64-unknown-linux-gnu -- \
+//!     receive /dev/ttyUSB0 ./inbox --speed 8
+//! cargo run --bin hc14_transfer --features std --target x86_64-unknown-linux-gnu -- \
+//!     send /dev/ttyUSB1 node.cfg --speed 8
+//! ```
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use hc14_at_rs::{
+    driver::Hc14,
+    host::{HostSerial, NoPin, StdDelay},
+    setting::speed::Speed,
+    transfer::{BlockSink, BlockSource, Progress, Transfer},
+    Error,
+};
+
+/// 从文件读取的数据来源(Data source reading from a file)
+struct FileSource {
+    file: File,
+    size: u32,
+}
+
+impl BlockSource for FileSource {
+    fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.file
+            .seek(SeekFrom::Start(u64::from(offset)))
+            .and_then(|_| self.file.read(buffer))
+            .map_err(|_| Error::Read)
+    }
+}
+
+/// 写入 `<dir>/<name>.part` 的存储(Storage writing `<dir>/<name>.part`)
+struct FileSink {
+    dir: PathBuf,
+    file: Option<File>,
+    part: PathBuf,
+    target: PathBuf,
+}
+
+impl FileSink {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            file: None,
+            part: PathBuf::new(),
+            target: PathBuf::new(),
+        }
+    }
+
+    fn open(&mut self, name: &str, size: u32) -> io::Result<u32> {
+        // 只保留文件名，忽略路径(Keep the file name only, ignore any path)
+        let name = Path::new(name)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .filter(|n| !n.is_empty())
+            .unwrap_or("received.bin");
+        self.target = self.dir.join(name);
+        self.part = self.dir.join(format!("{name}.part"));
+        let done = fs::metadata(&self.part).map(|m| m.len()).unwrap_or(0);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.part)?;
+        let resume = if done <= u64::from(size) { done } else { 0 };
+        file.set_len(resume)?;
+        self.file = Some(file);
+        Ok(resume as u32)
+    }
+}
+
+impl BlockSink for FileSink {
+    fn begin(&mut self, name: &str, size: u32) -> Result<u32, Error> {
+        self.open(name, size).map_err(|_| Error::Write)
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        let file = self.file.as_mut().ok_or(Error::Write)?;
+        file.seek(SeekFrom::Start(u64::from(offset)))
+            .and_then(|_| file.write_all(data))
+            .map_err(|_| Error::Write)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.file = None;
+        fs::rename(&self.part, &self.target).map_err(|_| Error::Write)
+    }
+}
+
+fn show(progress: Progress) {
+    eprint!(
+        "\r{}/{} bytes ({}%)",
+        progress.transferred,
+        progress.total,
+        progress.percent()
+    );
+}
+
+fn usage() -> ! {
+    eprintln!("usage: hc14_transfer send <serial-device> <file> [--speed 1-8] [--session N]");
+    eprintln!(
+        "       hc14_transfer receive <serial-device> [dir] [--speed 1-8] [--session N] [--timeout seconds]"
+    );
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next().unwrap_or_else(|| usage());
+    let mut positional = Vec::new();
+    let mut speed = Speed::default();
+    let mut session: u16 = 1;
+    let mut timeout_s: u32 = 600;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--speed" => {
+                speed = value()
+                    .parse()
+                    .ok()
+                    .and_then(Speed::new)
+                    .unwrap_or_else(|| usage())
+            }
+            "--session" => session = value().parse().unwrap_or_else(|_| usage()),
+            "--timeout" => timeout_s = value().parse().unwrap_or_else(|_| usage()),
+            _ => positional.push(arg),
+        }
+    }
+    let device = positional.first().unwrap_or_else(|| usage());
+
+    let serial =
+        HostSerial::open(device).unwrap_or_else(|e| fail(format!("cannot open {device}: {e}")));
+    let mut hc14 = Hc14::new(serial, NoPin, StdDelay)
+        .unwrap_or_else(|_| fail(format!("cannot initialise the HC-14 on {device}")));
+    hc14.set_speed(speed);
+    let mut transfer = Transfer::new(session);
+
+    match (command.as_str(), positional.len()) {
+        ("send", 2) => {
+            let path = Path::new(&positional[1]);
+            let file = File::open(path)
+                .unwrap_or_else(|e| fail(format!("cannot open {}: {e}", path.display())));
+            let size = file
+                .metadata()
+                .ok()
+                .and_then(|m| u32::try_from(m.len()).ok())
+                .unwrap_or_else(|| fail(format!("{} is too large", path.display())));
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+            let mut source = FileSource { file, size };
+            match transfer.send(&mut hc14, name, &mut source, show) {
+                Ok(()) => eprintln!("\nsent {name}"),
+                Err(e) => fail(format!("\nsend failed: {e:?}")),
+            }
+        }
+        ("receive", 1 | 2) => {
+            let dir = PathBuf::from(positional.get(1).map_or(".", String::as_str));
+            let mut sink = FileSink::new(dir);
+            match transfer.receive(
+                &mut hc14,
+                &mut sink,
+                timeout_s.saturating_mul(1_000_000),
+                show,
+            ) {
+                Ok(offer) => eprintln!("\nreceived {} ({} bytes)", offer.name, offer.size),
+                Err(e) => fail(format!("\nreceive failed: {e:?}")),
+            }
+        }
+        _ => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每个测试独立的临时目录(A temporary directory per test)
+    fn scratch(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("hc14_transfer-{}-{test}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sink_writes_into_the_part_file_and_renames_it() {
+        let dir = scratch("rename");
+        let mut sink = FileSink::new(dir.clone());
+        assert_eq!(sink.begin("node.cfg", 6).unwrap(), 0);
+        sink.write(0, b"abc").unwrap();
+        sink.write(3, b"def").unwrap();
+        assert_eq!(fs::read(dir.join("node.cfg.part")).unwrap(), b"abcdef");
+        assert!(!dir.join("node.cfg").exists());
+
+        sink.finish().unwrap();
+        assert_eq!(fs::read(dir.join("node.cfg")).unwrap(), b"abcdef");
+        assert!(!dir.join("node.cfg.part").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sink_resumes_from_the_end_of_the_part_file() {
+        let dir = scratch("resume");
+        fs::write(dir.join("node.cfg.part"), b"abc").unwrap();
+        let mut sink = FileSink::new(dir.clone());
+        assert_eq!(sink.begin("node.cfg", 6).unwrap(), 3);
+        sink.write(3, b"def").unwrap();
+        sink.finish().unwrap();
+        assert_eq!(fs::read(dir.join("node.cfg")).unwrap(), b"abcdef");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sink_restarts_when_the_part_file_is_too_large() {
+        let dir = scratch("truncate");
+        fs::write(dir.join("node.cfg.part"), b"abcdefgh").unwrap();
+        let mut sink = FileSink::new(dir.clone());
+        assert_eq!(sink.begin("node.cfg", 6).unwrap(), 0);
+        assert_eq!(fs::metadata(dir.join("node.cfg.part")).unwrap().len(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sink_ignores_the_path_of_the_offered_name() {
+        let dir = scratch("path");
+        let mut sink = FileSink::new(dir.clone());
+        sink.begin("../../etc/node.cfg", 1).unwrap();
+        sink.write(0, b"x").unwrap();
+        sink.finish().unwrap();
+        assert_eq!(fs::read(dir.join("node.cfg")).unwrap(), b"x");
+
+        sink.begin("..", 1).unwrap();
+        sink.finish().unwrap();
+        assert!(dir.join("received.bin").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn source_reads_at_the_requested_offset() {
+        let dir = scratch("source");
+        let path = dir.join("node.cfg");
+        fs::write(&path, b"0123456789").unwrap();
+        let mut source = FileSource {
+            file: File::open(&path).unwrap(),
+            size: 10,
+        };
+        let mut buffer = [0u8; 4];
+        assert_eq!(source.size(), 10);
+        assert_eq!(source.read(6, &mut buffer).unwrap(), 4);
+        assert_eq!(&buffer, b"6789");
+        assert_eq!(source.read(2, &mut buffer).unwrap(), 4);
+        assert_eq!(&buffer, b"2345");
+        assert_eq!(source.read(10, &mut buffer).unwrap(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
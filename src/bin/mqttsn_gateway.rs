@@ -0,0 +1,70 @@
+//! MQTT-SN 网关：将串口上的 HC-14 桥接到 MQTT 代理
+//!
+//! MQTT-SN gateway: bridges a serial-attached HC-14 to an MQTT broker.
+//!
+//! 每个无线客户端对应一个到代理的 MQTT 3.1.1 连接(透明网关)，见 [`hc14_at_rs::gateway`]。
+//! 只支持普通主题名称，不支持通配符订阅。
+//!
+//! Every radio client gets its own MQTT 3.1.1 connection to the broker (transparent gateway),
+//! see [`hc14_at_rs::gateway`]. Only plain topic names are supported, wildcard subscriptions are
+//! not.
+//!
+//! ```text
+//! stty -F /dev/ttyUSB0 9600 raw -echo
+//! cargo run --bin mqttsn_gateway --features std --target x86_64-unknown-linux-gnu -- \
+//!     /dev/ttyUSB0 --broker 127.0.0.1:1883 --speed 3
+//! ```
+use std::{env, process};
+
+use hc14_at_rs::{
+    driver::Hc14,
+    gateway::Gateway,
+    host::{HostSerial, NoPin, StdDelay},
+    setting::speed::Speed,
+};
+
+/// 等待无线帧的时间，之后处理代理的数据(Wait for a radio frame before serving the brokers)
+const RADIO_POLL_US: u32 = 20_000;
+
+fn usage() -> ! {
+    eprintln!("usage: mqttsn_gateway <serial-device> [--broker host:port] [--speed 1-8]");
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut device = None;
+    let mut broker_addr = String::from("127.0.0.1:1883");
+    let mut speed = Speed::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--broker" => broker_addr = args.next().unwrap_or_else(|| usage()),
+            "--speed" => {
+                speed = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .and_then(Speed::new)
+                    .unwrap_or_else(|| usage())
+            }
+            _ if device.is_none() => device = Some(arg),
+            _ => usage(),
+        }
+    }
+    let device = device.unwrap_or_else(|| usage());
+
+    let serial = HostSerial::open(&device).unwrap_or_else(|e| {
+        eprintln!("cannot open {device}: {e}");
+        process::exit(1);
+    });
+    let mut radio = Hc14::new(serial, NoPin, StdDelay).unwrap_or_else(|_| {
+        eprintln!("cannot initialise the HC-14 on {device}");
+        process::exit(1);
+    });
+    radio.set_speed(speed);
+    println!("gateway on {device} ({speed:?}), broker {broker_addr}");
+
+    let mut gateway = Gateway::new(radio, broker_addr);
+    loop {
+        gateway.poll(RADIO_POLL_US);
+    }
+}
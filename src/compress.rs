@@ -0,0 +1,193 @@
+//! 适用于短数据包的 LZSS 压缩(需要 `compression` 特性)
+//!
+//! LZSS compression suited to short packets (requires the `compression` feature).
+//!
+//! 窗口为 256 字节，正好覆盖一个最大的数据包，不需要堆内存。每 8 个记号前有一个标志字节，
+//! 位为 0 表示一个原样字节，位为 1 表示一个两字节的回溯引用(距离 1-256，长度 3-258)。
+//!
+//! The window is 256 bytes, exactly covering the largest packet, and no heap is needed.
+//! Every 8 tokens are preceded by a flag byte: a 0 bit is one literal byte,
+//! a 1 bit is a two-byte back reference (distance 1-256, length 3-258).
+//!
+//! # Example
+//! ```rust
+//! let input = b"temp=21.5;temp=21.6;temp=21.5;";
+//! let mut packed = [0u8; 64];
+//! let n = compress::compress(input, &mut packed).unwrap();
+//! let mut unpacked = [0u8; 64];
+//! let m = compress::decompress(&packed[..n], &mut unpacked).unwrap();
+//! assert_eq!(&unpacked[..m], input);
+//! ```
+use crate::Error;
+
+/// 回溯窗口大小
+///
+/// Back reference window size.
+pub const WINDOW_SIZE: usize = 256;
+
+/// 最短的回溯引用长度
+///
+/// Shortest back reference.
+pub const MIN_MATCH: usize = 3;
+
+/// 最长的回溯引用长度
+///
+/// Longest back reference.
+pub const MAX_MATCH: usize = MIN_MATCH + 255;
+
+/// 压缩 `src` 到 `dst`，返回压缩后的长度；`dst` 不足时返回 `None`
+///
+/// Compress `src` into `dst`, returns the compressed length, or `None` when `dst` is too small.
+pub fn compress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out: usize = 0;
+    let mut flag_pos: usize = 0;
+    let mut bit: u8 = 8;
+    let mut pos: usize = 0;
+
+    while pos < src.len() {
+        if bit == 8 {
+            flag_pos = out;
+            *dst.get_mut(out)? = 0;
+            out += 1;
+            bit = 0;
+        }
+
+        let (distance, length) = longest_match(src, pos);
+        if length >= MIN_MATCH {
+            dst[flag_pos] |= 1 << bit;
+            *dst.get_mut(out)? = (distance - 1) as u8;
+            *dst.get_mut(out + 1)? = (length - MIN_MATCH) as u8;
+            out += 2;
+            pos += length;
+        } else {
+            *dst.get_mut(out)? = src[pos];
+            out += 1;
+            pos += 1;
+        }
+        bit += 1;
+    }
+    Some(out)
+}
+
+/// 在窗口内查找 `pos` 处最长的匹配，返回(距离, 长度)
+///
+/// Find the longest match at `pos` within the window, returns (distance, length).
+fn longest_match(src: &[u8], pos: usize) -> (usize, usize) {
+    let start = pos.saturating_sub(WINDOW_SIZE);
+    let limit = (src.len() - pos).min(MAX_MATCH);
+    let mut best = (0, 0);
+    for candidate in start..pos {
+        let mut length: usize = 0;
+        while length < limit && src[candidate + length] == src[pos + length] {
+            length += 1;
+        }
+        if length > best.1 {
+            best = (pos - candidate, length);
+            if length == limit {
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// 解压 `src` 到 `dst`，返回解压后的长度
+///
+/// Decompress `src` into `dst`, returns the decompressed length.
+pub fn decompress(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let mut out: usize = 0;
+    let mut pos: usize = 0;
+
+    while pos < src.len() {
+        let flags = src[pos];
+        pos += 1;
+        for bit in 0..8 {
+            if pos >= src.len() {
+                break;
+            }
+            if flags & (1 << bit) == 0 {
+                *dst.get_mut(out).ok_or(Error::MessageTooLarge)? = src[pos];
+                out += 1;
+                pos += 1;
+            } else {
+                let distance = *src.get(pos).ok_or(Error::Decode)? as usize + 1;
+                let length = *src.get(pos + 1).ok_or(Error::Decode)? as usize + MIN_MATCH;
+                pos += 2;
+                if distance > out {
+                    return Err(Error::Decode);
+                }
+                if out + length > dst.len() {
+                    return Err(Error::MessageTooLarge);
+                }
+                // 引用可能与输出重叠，逐字节复制
+                // The reference may overlap the output, copy byte by byte
+                for _ in 0..length {
+                    dst[out] = dst[out - distance];
+                    out += 1;
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> usize {
+        let mut packed = [0u8; 2 * WINDOW_SIZE];
+        let n = compress(data, &mut packed).unwrap();
+        let mut unpacked = [0u8; 2 * WINDOW_SIZE];
+        let m = decompress(&packed[..n], &mut unpacked).unwrap();
+        assert_eq!(&unpacked[..m], data);
+        n
+    }
+
+    #[test]
+    fn repetitive_input_shrinks() {
+        assert_eq!(round_trip(b""), 0);
+        assert!(round_trip(b"temp=21.5;temp=21.6;temp=21.5;") < 30);
+        // 最长的引用与输出重叠(The longest reference overlaps the output)
+        assert_eq!(round_trip(&[7u8; 1 + MAX_MATCH]), 4);
+    }
+
+    #[test]
+    fn incompressible_input_round_trips() {
+        let mut data = [0u8; 250];
+        let mut x: u32 = 1;
+        for byte in data.iter_mut() {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            *byte = x as u8;
+        }
+        assert!(round_trip(&data) > data.len());
+    }
+
+    #[test]
+    fn short_buffers_are_reported() {
+        assert!(compress(b"abcdef", &mut [0u8; 4]).is_none());
+        let mut packed = [0u8; 16];
+        let n = compress(b"aaaaaaaa", &mut packed).unwrap();
+        assert!(matches!(
+            decompress(&packed[..n], &mut [0u8; 4]),
+            Err(Error::MessageTooLarge)
+        ));
+    }
+
+    #[test]
+    fn bad_references_are_rejected() {
+        let mut out = [0u8; 16];
+        // 距离超出已输出的数据(Distance beyond the output so far)
+        assert!(matches!(
+            decompress(&[0x01, 0, 0], &mut out),
+            Err(Error::Decode)
+        ));
+        // 引用缺少长度字节(Reference missing its length byte)
+        assert!(matches!(
+            decompress(&[0x02, b'a', 0], &mut out),
+            Err(Error::Decode)
+        ));
+    }
+}
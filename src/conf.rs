@@ -32,6 +32,10 @@ pub const AT_COMMAND_QUERY_POWER: [u8; 5] = *b"AT+P?";
 /// - AT setup command: Restore factory default values AT+DEFAULT
 pub const AT_COMMAND_DEFAULT: [u8; 10] = *b"AT+DEFAULT";
 
+/// AT设置指令：离开AT指令模式后进入睡眠`AT+SLEEP`
+/// - AT setup command: Sleep once AT command mode is left AT+SLEEP
+pub const AT_COMMAND_SLEEP: [u8; 8] = *b"AT+SLEEP";
+
 /// AT设置指令：设置串口波特率指令`AT+B`
 /// - AT setup command: Set serial port baud rate AT+B
 pub const AT_COMMAND_SET_BAUD: [u8; 4] = *b"AT+B";
@@ -57,6 +61,10 @@ pub const RESPONSE_OK: [u8; 4] = *b"OK\r\n";
 /// - If successfully restored to factory default settings, the following response will be returned
 pub const RESPONSE_RESET_SETTINGS: [u8; 12] = *b"OK+DEFAULT\r\n";
 
+/// 如果模块接受了睡眠指令，将返回以下信息
+/// - If the module accepted the sleep command, the following response will be returned
+pub const RESPONSE_SLEEP: [u8; 10] = *b"OK+SLEEP\r\n";
+
 /// 波特率响应前缀
 /// - Baud rate response prefix
 pub const RESPONSE_BAUD: [u8; 5] = *b"OK+B:";
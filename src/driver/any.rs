@@ -0,0 +1,435 @@
+//! 运行时记录模式的驱动，类型不随模式改变，便于放入静态变量、RTIC/Embassy 共享资源或状态机
+//!
+//! Driver tracking its mode at runtime, its type does not change with the mode, so it fits statics,
+//! RTIC/Embassy shared resources and state machines.
+//!
+//! 设置 [`IdlePolicy`] 后，定时调用 [`Hc14Any::tick`] 会在链路空闲足够久时让模块睡眠，
+//! [`Hc14Any::ensure_normal`] 需要时唤醒它。
+//!
+//! With an [`IdlePolicy`] set, calling [`Hc14Any::tick`] periodically puts the module to sleep once
+//! the link has been idle long enough, [`Hc14Any::ensure_normal`] wakes it when needed.
+//!
+//! # Example
+//! ```rust
+//! let mut radio = Hc14Any::from(Hc14::new(serial, key, delay).unwrap());
+//!
+//! // 临时进入AT配置模式，结束后总是回到正常模式
+//! // Enter AT configuration mode for a moment, always back to normal mode afterwards
+//! let parameters = radio.with_configuration(|cfg| cfg.get_parameters()).unwrap();
+//! hprintln!("{:?}", parameters);
+//!
+//! radio.ensure_normal().unwrap().send_buffer(b"hc14").unwrap();
+//!
+//! // 空闲 5 秒后睡眠，每 100 ms 检查一次(Sleep after 5 s idle, checked every 100 ms)
+//! radio.set_idle_policy(Some(IdlePolicy::new(5_000)));
+//! loop {
+//!     radio.tick(100).unwrap();
+//!     delay.delay_ms(100u32);
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{power::NoPowerPin, sleep::IdlePolicy, Configuration, Hc14, Normal, Sleep};
+use crate::Error;
+
+enum State<S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    Normal(Hc14<S, P, D, Normal, W>),
+    Configuration(Hc14<S, P, D, Configuration, W>),
+    Sleep(Hc14<S, P, D, Sleep, W>),
+}
+
+/// 处于正常模式、AT配置模式或睡眠模式的驱动
+///
+/// A driver in normal, AT configuration or sleep mode.
+pub struct Hc14Any<S, P, D, W = NoPowerPin>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    // 只在切换模式的过程中为 `None`(Only `None` in the middle of a transition)
+    state: Option<State<S, P, D, W>>,
+    idle: Option<IdlePolicy>,
+}
+
+impl<S, P, D, W> From<Hc14<S, P, D, Normal, W>> for Hc14Any<S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn from(hc14: Hc14<S, P, D, Normal, W>) -> Self {
+        Self {
+            state: Some(State::Normal(hc14)),
+            idle: None,
+        }
+    }
+}
+
+impl<S, P, D, W> From<Hc14<S, P, D, Configuration, W>> for Hc14Any<S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn from(hc14: Hc14<S, P, D, Configuration, W>) -> Self {
+        Self {
+            state: Some(State::Configuration(hc14)),
+            idle: None,
+        }
+    }
+}
+
+impl<S, P, D, W> From<Hc14<S, P, D, Sleep, W>> for Hc14Any<S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn from(hc14: Hc14<S, P, D, Sleep, W>) -> Self {
+        Self {
+            state: Some(State::Sleep(hc14)),
+            idle: None,
+        }
+    }
+}
+
+impl<S, P, D, W> Hc14Any<S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 是否处于正常模式
+    ///
+    /// Whether the driver is in normal mode.
+    pub fn is_normal(&self) -> bool {
+        matches!(self.state, Some(State::Normal(_)))
+    }
+
+    /// 是否处于AT配置模式
+    ///
+    /// Whether the driver is in AT configuration mode.
+    pub fn is_configuration(&self) -> bool {
+        matches!(self.state, Some(State::Configuration(_)))
+    }
+
+    /// 是否处于睡眠模式
+    ///
+    /// Whether the driver is in sleep mode.
+    pub fn is_sleeping(&self) -> bool {
+        matches!(self.state, Some(State::Sleep(_)))
+    }
+
+    /// 处于正常模式时返回驱动
+    ///
+    /// The driver, when in normal mode.
+    pub fn as_normal(&mut self) -> Option<&mut Hc14<S, P, D, Normal, W>> {
+        match &mut self.state {
+            Some(State::Normal(hc14)) => Some(hc14),
+            _ => None,
+        }
+    }
+
+    /// 处于AT配置模式时返回驱动
+    ///
+    /// The driver, when in AT configuration mode.
+    pub fn as_configuration(&mut self) -> Option<&mut Hc14<S, P, D, Configuration, W>> {
+        match &mut self.state {
+            Some(State::Configuration(hc14)) => Some(hc14),
+            _ => None,
+        }
+    }
+
+    /// 处于睡眠模式时返回驱动
+    ///
+    /// The driver, when in sleep mode.
+    pub fn as_sleep(&mut self) -> Option<&mut Hc14<S, P, D, Sleep, W>> {
+        match &mut self.state {
+            Some(State::Sleep(hc14)) => Some(hc14),
+            _ => None,
+        }
+    }
+
+    /// 必要时唤醒模块或切换到正常模式并返回驱动；切换失败时保持原来的模式
+    ///
+    /// Wake the module or switch to normal mode if needed and return the driver; the mode is kept
+    /// when the transition fails.
+    pub fn ensure_normal(&mut self) -> Result<&mut Hc14<S, P, D, Normal, W>, Error> {
+        if self.is_sleeping() {
+            if let Some(State::Sleep(hc14)) = self.state.take() {
+                match hc14.wake() {
+                    Ok(normal) => {
+                        self.state = Some(State::Normal(normal));
+                        if let Some(idle) = &mut self.idle {
+                            idle.activity();
+                        }
+                    }
+                    Err(e) => {
+                        self.state = Some(State::Sleep(e.hc14));
+                        return Err(e.error);
+                    }
+                }
+            }
+        }
+        if self.is_configuration() {
+            if let Some(State::Configuration(hc14)) = self.state.take() {
+                match hc14.into_normal_mode() {
+                    Ok(normal) => self.state = Some(State::Normal(normal)),
+                    Err(e) => {
+                        self.state = Some(State::Configuration(e.hc14));
+                        return Err(e.error);
+                    }
+                }
+            }
+        }
+        self.as_normal().ok_or(Error::Pin)
+    }
+
+    /// 必要时切换到AT配置模式并返回驱动；切换失败时保持原来的模式
+    ///
+    /// Switch to AT configuration mode if needed and return the driver; the mode is kept when the
+    /// transition fails.
+    pub fn ensure_configuration(&mut self) -> Result<&mut Hc14<S, P, D, Configuration, W>, Error> {
+        if self.is_sleeping() {
+            self.ensure_normal()?;
+        }
+        if self.is_normal() {
+            if let Some(State::Normal(hc14)) = self.state.take() {
+                match hc14.into_configuration_mode() {
+                    Ok(configuration) => self.state = Some(State::Configuration(configuration)),
+                    Err(e) => {
+                        self.state = Some(State::Normal(e.hc14));
+                        return Err(e.error);
+                    }
+                }
+            }
+        }
+        self.as_configuration().ok_or(Error::Pin)
+    }
+
+    /// 必要时让模块睡眠并返回驱动；切换失败时保持原来的模式
+    ///
+    /// Put the module to sleep if needed and return the driver; the mode is kept when the
+    /// transition fails.
+    pub fn ensure_sleep(&mut self) -> Result<&mut Hc14<S, P, D, Sleep, W>, Error> {
+        if self.is_configuration() {
+            self.ensure_normal()?;
+        }
+        if self.is_normal() {
+            if let Some(State::Normal(hc14)) = self.state.take() {
+                match hc14.into_sleep() {
+                    Ok(sleep) => self.state = Some(State::Sleep(sleep)),
+                    Err(e) => {
+                        self.state = Some(State::Normal(e.hc14));
+                        return Err(e.error);
+                    }
+                }
+            }
+        }
+        self.as_sleep().ok_or(Error::Pin)
+    }
+
+    /// 当前的空闲策略
+    ///
+    /// The current idle policy.
+    pub fn idle_policy(&self) -> Option<&IdlePolicy> {
+        self.idle.as_ref()
+    }
+
+    /// 设置或取消空闲策略
+    ///
+    /// Set or clear the idle policy.
+    pub fn set_idle_policy(&mut self, policy: Option<IdlePolicy>) {
+        self.idle = policy;
+    }
+
+    /// 经过 `elapsed_ms` 毫秒后调用：处于正常模式且链路空闲达到 [`IdlePolicy`] 的时间时让模块
+    /// 睡眠，返回是否刚刚进入睡眠
+    ///
+    /// Call after `elapsed_ms` milliseconds: in normal mode with the link idle for as long as the
+    /// [`IdlePolicy`] asks, the module is put to sleep; returns whether it just went to sleep.
+    pub fn tick(&mut self, elapsed_ms: u32) -> Result<bool, Error> {
+        let expired = match (&mut self.idle, &self.state) {
+            (Some(idle), Some(State::Normal(hc14))) => idle.tick(hc14.stats(), elapsed_ms),
+            _ => false,
+        };
+        if expired {
+            self.ensure_sleep()?;
+        }
+        Ok(expired)
+    }
+
+    /// 在AT配置模式下执行 `f`，之后回到正常模式；无法回到正常模式时返回错误
+    ///
+    /// Run `f` in AT configuration mode and return to normal mode afterwards; returns an error when
+    /// normal mode cannot be restored.
+    pub fn with_configuration<R>(
+        &mut self,
+        f: impl FnOnce(&mut Hc14<S, P, D, Configuration, W>) -> R,
+    ) -> Result<R, Error> {
+        let result = f(self.ensure_configuration()?);
+        self.ensure_normal()?;
+        Ok(result)
+    }
+
+    /// 释放所含资源，电源控制引脚见 [`Hc14Any::release_with_power_pin`]
+    ///
+    /// Release the contained resources, see [`Hc14Any::release_with_power_pin`] for the
+    /// power-control pin.
+    pub fn release(self) -> (S, P, D) {
+        match self.state {
+            Some(State::Normal(hc14)) => hc14.release(),
+            Some(State::Configuration(hc14)) => hc14.release(),
+            Some(State::Sleep(hc14)) => hc14.release(),
+            None => unreachable!("mode transition interrupted"),
+        }
+    }
+
+    /// 释放所含资源，包括电源控制引脚
+    ///
+    /// Release the contained resources, the power-control pin included.
+    pub fn release_with_power_pin(self) -> (S, P, D, W) {
+        match self.state {
+            Some(State::Normal(hc14)) => hc14.release_with_power_pin(),
+            Some(State::Configuration(hc14)) => hc14.release_with_power_pin(),
+            Some(State::Sleep(hc14)) => hc14.release_with_power_pin(),
+            None => unreachable!("mode transition interrupted"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        setting::parameters::Parameters,
+        sim::{SimConfig, SimDelay, SimPin, SimPowerPin, SimSerial, Simulator},
+    };
+
+    /// 可以让它失效的 Key 引脚(Key pin that can be made to fail)
+    struct Key {
+        pin: SimPin,
+        broken: Rc<Cell<bool>>,
+    }
+
+    impl OutputPin for Key {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), ()> {
+            if self.broken.get() {
+                return Err(());
+            }
+            self.pin.set_low().map_err(|_| ())
+        }
+
+        fn set_high(&mut self) -> Result<(), ()> {
+            if self.broken.get() {
+                return Err(());
+            }
+            self.pin.set_high().map_err(|_| ())
+        }
+    }
+
+    type Radio = Hc14Any<SimSerial, Key, SimDelay>;
+
+    fn radio(sim: &mut Simulator) -> (Radio, Rc<Cell<bool>>, SimPowerPin) {
+        let node = sim.add_node(Parameters::default());
+        let broken = Rc::new(Cell::new(false));
+        let key = Key {
+            pin: node.key,
+            broken: broken.clone(),
+        };
+        let hc14 = Hc14::new(node.serial, key, node.delay).unwrap();
+        (Hc14Any::from(hc14), broken, node.power)
+    }
+
+    #[test]
+    fn failed_transitions_keep_the_mode() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let (mut radio, broken, mut power) = radio(&mut sim);
+
+        // 模块断电后不应答(Without power the module does not answer)
+        power.set_low().unwrap();
+        assert!(matches!(
+            radio.ensure_configuration(),
+            Err(Error::NoResponse)
+        ));
+        assert!(radio.is_normal());
+        assert!(matches!(radio.ensure_sleep(), Err(Error::NoResponse)));
+        assert!(radio.is_normal());
+        power.set_high().unwrap();
+
+        radio.ensure_configuration().unwrap();
+        broken.set(true);
+        assert!(matches!(radio.ensure_normal(), Err(Error::Pin)));
+        assert!(radio.is_configuration());
+        assert!(matches!(radio.ensure_sleep(), Err(Error::Pin)));
+        assert!(radio.is_configuration());
+        broken.set(false);
+
+        radio.ensure_sleep().unwrap();
+        broken.set(true);
+        assert!(matches!(radio.ensure_normal(), Err(Error::Pin)));
+        assert!(radio.is_sleeping());
+        assert!(matches!(radio.ensure_configuration(), Err(Error::Pin)));
+        assert!(radio.is_sleeping());
+        broken.set(false);
+
+        radio.ensure_normal().unwrap();
+        assert!(radio.is_normal());
+    }
+
+    #[test]
+    fn with_configuration_returns_to_normal() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let (mut radio, _, _) = radio(&mut sim);
+
+        let parameters = radio.with_configuration(|cfg| cfg.get_parameters());
+        assert_eq!(parameters.unwrap(), Some(Parameters::default()));
+        assert!(radio.is_normal());
+
+        // 从睡眠开始也一样(Starting from sleep as well)
+        radio.ensure_sleep().unwrap();
+        radio.with_configuration(|_| ()).unwrap();
+        assert!(radio.is_normal());
+    }
+
+    #[test]
+    fn tick_sleeps_after_the_idle_timeout() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let (mut radio, _, _) = radio(&mut sim);
+        radio.set_idle_policy(Some(IdlePolicy::new(1_000)));
+
+        radio.ensure_normal().unwrap().send_buffer(b"hc14").unwrap();
+        // 有流量时重新计时(Traffic restarts the count)
+        assert!(!radio.tick(600).unwrap());
+        assert!(!radio.tick(600).unwrap());
+        radio.ensure_normal().unwrap().send_buffer(b"hc14").unwrap();
+        assert!(!radio.tick(600).unwrap());
+        assert!(!radio.tick(600).unwrap());
+        assert!(radio.tick(600).unwrap());
+        assert!(radio.is_sleeping());
+        assert!(!radio.tick(600).unwrap());
+
+        radio.ensure_normal().unwrap();
+        assert_eq!(radio.idle_policy().unwrap().idle_ms(), 0);
+    }
+}
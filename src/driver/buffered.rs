@@ -0,0 +1,235 @@
+//! 中断驱动的接收环形缓冲区
+//!
+//! Interrupt-driven receive ring buffer.
+//!
+//! 串口接收中断通过 [`RxProducer`] 将字节写入 `heapless` SPSC 队列，
+//! 驱动程序通过 [`RxConsumer`] 取出数据，因此应用程序忙于其他任务时也不会丢失无线数据。
+//!
+//! The UART receive interrupt pushes bytes into a `heapless` SPSC queue through [`RxProducer`],
+//! and the driver drains it through [`RxConsumer`], so no radio data is lost while the application is busy.
+//!
+//! # Example
+//! ```rust
+//! static mut RX_QUEUE: RxQueue<128> = RxQueue::new();
+//!
+//! let (tx, rx) = serial.split();
+//! let (producer, consumer) = unsafe { RX_QUEUE.split() };
+//! // 将 `producer` 和 `rx` 移交给中断(Hand `producer` and `rx` over to the interrupt)
+//!
+//! let hc14 = Hc14::new(Duplex::new(tx, consumer), key, delay).unwrap();
+//!
+//! // 在 USART1 中断中(In the USART1 interrupt)
+//! producer.on_interrupt(&mut rx);
+//! ```
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use embedded_hal::serial::{Read, Write};
+use heapless::spsc::{Consumer, Producer, Queue};
+
+/// 接收队列：SPSC 队列及其溢出计数器，可用容量为 `N - 1` 字节
+///
+/// Receive queue: an SPSC queue plus its overflow counters, usable capacity is `N - 1` bytes.
+pub struct RxQueue<const N: usize> {
+    queue: Queue<u8, N>,
+    overflows: AtomicUsize,
+    uart_errors: AtomicUsize,
+}
+
+impl<const N: usize> RxQueue<N> {
+    /// 构建一个空的接收队列，可用于 `static`
+    ///
+    /// Build an empty receive queue, usable in a `static`.
+    pub const fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+            overflows: AtomicUsize::new(0),
+            uart_errors: AtomicUsize::new(0),
+        }
+    }
+
+    /// 拆分为中断端(生产者)和驱动端(消费者)
+    ///
+    /// Split into the interrupt side (producer) and the driver side (consumer).
+    pub fn split(&mut self) -> (RxProducer<'_, N>, RxConsumer<'_, N>) {
+        let (producer, consumer) = self.queue.split();
+        (
+            RxProducer {
+                producer,
+                overflows: &self.overflows,
+                uart_errors: &self.uart_errors,
+            },
+            RxConsumer {
+                consumer,
+                overflows: &self.overflows,
+                uart_errors: &self.uart_errors,
+            },
+        )
+    }
+}
+
+impl<const N: usize> Default for RxQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 计数器只有生产者一个写入者，因此只需 load/store，无需 CAS
+///
+/// Counters have a single writer (the producer), so load/store is enough and no CAS is needed.
+fn bump(counter: &AtomicUsize) {
+    counter.store(
+        counter.load(Ordering::Relaxed).wrapping_add(1),
+        Ordering::Relaxed,
+    );
+}
+
+/// 中断端：将串口收到的字节写入队列
+///
+/// Interrupt side: pushes bytes received by the serial port into the queue.
+pub struct RxProducer<'a, const N: usize> {
+    producer: Producer<'a, u8, N>,
+    overflows: &'a AtomicUsize,
+    uart_errors: &'a AtomicUsize,
+}
+
+impl<'a, const N: usize> RxProducer<'a, N> {
+    /// 写入一个字节，队列已满时丢弃该字节并计入溢出次数
+    ///
+    /// Push one byte; when the queue is full the byte is dropped and counted as an overflow.
+    pub fn push(&mut self, byte: u8) -> bool {
+        match self.producer.enqueue(byte) {
+            Ok(()) => true,
+            Err(_) => {
+                bump(self.overflows);
+                false
+            }
+        }
+    }
+
+    /// 在串口接收中断中调用：读出串口中所有可用的字节，返回写入队列的字节数
+    ///
+    /// Call from the UART receive interrupt: drains every byte the serial port has ready,
+    /// returns the number of bytes queued.
+    pub fn on_interrupt<R: Read<u8>>(&mut self, serial: &mut R) -> usize {
+        let mut queued: usize = 0;
+        loop {
+            match serial.read() {
+                Ok(byte) => {
+                    if self.push(byte) {
+                        queued += 1;
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                // 溢出/噪声/帧错误已清除，继续读取
+                // Overrun/noise/framing errors are cleared by the read, keep going
+                Err(nb::Error::Other(_)) => bump(self.uart_errors),
+            }
+        }
+        queued
+    }
+}
+
+/// 驱动端：从队列中取出字节，实现了 `serial::Read<u8>`
+///
+/// Driver side: takes bytes out of the queue, implements `serial::Read<u8>`.
+pub struct RxConsumer<'a, const N: usize> {
+    consumer: Consumer<'a, u8, N>,
+    overflows: &'a AtomicUsize,
+    uart_errors: &'a AtomicUsize,
+}
+
+impl<'a, const N: usize> RxConsumer<'a, N> {
+    /// 队列中等待读取的字节数
+    ///
+    /// Number of bytes waiting in the queue.
+    pub fn len(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// 队列是否为空
+    ///
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        !self.consumer.ready()
+    }
+
+    /// 因队列已满而丢弃的字节数
+    ///
+    /// Number of bytes dropped because the queue was full.
+    pub fn overflows(&self) -> usize {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    /// 串口报告的接收错误次数(溢出、噪声、帧错误等)
+    ///
+    /// Number of receive errors reported by the UART (overrun, noise, framing, ...).
+    pub fn uart_errors(&self) -> usize {
+        self.uart_errors.load(Ordering::Relaxed)
+    }
+}
+
+impl<'a, const N: usize> Read<u8> for RxConsumer<'a, N> {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.consumer.dequeue().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+/// 由独立的发送端和接收端组成的串口，例如串口发送半部与 [`RxConsumer`]
+///
+/// A serial port made of separate transmit and receive halves, e.g. a serial TX half and an [`RxConsumer`].
+#[derive(Debug)]
+pub struct Duplex<TX, RX> {
+    tx: TX,
+    rx: RX,
+}
+
+impl<TX, RX> Duplex<TX, RX>
+where
+    TX: Write<u8>,
+    RX: Read<u8>,
+{
+    /// 由发送端和接收端构建串口
+    ///
+    /// Build a serial port from a transmit half and a receive half.
+    pub fn new(tx: TX, rx: RX) -> Self {
+        Self { tx, rx }
+    }
+
+    /// 释放发送端和接收端
+    ///
+    /// Release the transmit and receive halves.
+    pub fn release(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+}
+
+impl<TX, RX> Read<u8> for Duplex<TX, RX>
+where
+    RX: Read<u8>,
+{
+    type Error = RX::Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.rx.read()
+    }
+}
+
+impl<TX, RX> Write<u8> for Duplex<TX, RX>
+where
+    TX: Write<u8>,
+{
+    type Error = TX::Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.tx.write(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.tx.flush()
+    }
+}
@@ -7,45 +7,116 @@ use nb::block;
 
 use crate::{
     conf::{
-        AT_COMMAND_DEFAULT, AT_COMMAND_QUERY_ALL, AT_COMMAND_QUERY_MODE, RESPONSE_OK,
-        RESPONSE_RESET_SETTINGS,
+        AT_COMMAND_DEFAULT, AT_COMMAND_QUERY_ALL, AT_COMMAND_QUERY_MODE, RESPONSE_RESET_SETTINGS,
     },
     setting::{
-        baudrate::BaudRate, channel::Channel, parameters::Parameters, power::TransmissionPower,
-        speed::Speed,
+        band::BandPlan, baudrate::BaudRate, channel::Channel, parameters::Parameters,
+        power::TransmissionPower, speed::Speed, GenerateAtCommand,
     },
     Error,
 };
 
-use core::{marker::PhantomData, result::Result::*};
+use core::result::Result::*;
 
-use super::{Configuration, Hc14, Normal};
+use super::{Configuration, Hc14, Normal, TransitionError};
+
+/// 频段规划(Band plan)
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 当前的频段规划，AT配置模式的设置接口按它检查信道和功率
+    ///
+    /// The current band plan, the setting APIs of AT configuration mode check channels and power
+    /// against it.
+    pub fn band_plan(&self) -> &BandPlan {
+        &self.band_plan
+    }
+
+    /// 更换频段规划，默认为 [`BandPlan::DEFAULT`]
+    ///
+    /// Replace the band plan, [`BandPlan::DEFAULT`] by default.
+    pub fn set_band_plan(&mut self, plan: BandPlan) {
+        self.band_plan = plan;
+    }
+}
 
 /// 配置模式(Configuration Mode)
-impl<S, P, D> Hc14<S, P, D, Configuration>
+impl<S, P, D, W> Hc14<S, P, D, Configuration, W>
 where
     S: Read<u8> + Write<u8>,
     P: OutputPin,
     D: DelayUs<u32>,
+    W: OutputPin,
 {
-    /// ! **"AT配置模式"** 切换到 "**正常模式**"。
-    pub fn into_normal_mode(mut self) -> Result<Hc14<S, P, D, Normal>, ()> {
-        let at_off = self.key_pin.set_high();
-        self.delay.delay_us(100_000_u32); // delay 0.1s
-
-        match at_off {
-            Ok(_) => Ok(Hc14 {
-                serial: self.serial,
-                key_pin: self.key_pin,
-                delay: self.delay,
-                mode: PhantomData::<Normal>,
-            }),
-            Err(_) => Err(()),
+    /// ! **"AT配置模式"** 切换到 "**正常模式**"，失败时交还配置模式的驱动。
+    /// - **"AT Configuration Mode"** Switch to "**Normal Mode**", handing back the configuration-mode driver on failure.
+    #[allow(clippy::result_large_err)] // 错误中带着整个驱动(The error carries the whole driver)
+    pub fn into_normal_mode(mut self) -> Result<Hc14<S, P, D, Normal, W>, TransitionError<Self>> {
+        if self.key_pin.set_high().is_err() {
+            return Err(TransitionError {
+                hc14: self,
+                error: Error::Pin,
+            });
+        }
+        let timing = self.mode_timing;
+        self.delay.delay_us(timing.settle_us);
+        let mut hc14: Hc14<S, P, D, Normal, W> = self.into_mode();
+        if !timing.verify_exit {
+            return Ok(hc14);
         }
+        for _ in 0..=timing.retries {
+            // 模块离开AT配置模式后探测字节会发到空中，经过正常模式的发送路径
+            // Once the module left AT mode the probe goes out over the air, so use the normal send path
+            let answered = hc14
+                .send_buffer(&AT_COMMAND_QUERY_MODE)
+                .map_err(|e| match e {
+                    nb::Error::Other(nb::Error::Other(error)) => error,
+                    _ => Error::Write,
+                })
+                .and_then(|_| hc14.await_ok());
+            match answered {
+                Ok(true) => hc14.delay.delay_us(timing.settle_us),
+                Ok(false) => {
+                    // 以 0x00 结束探测字节，让对端的帧解码器丢弃它们
+                    // End the probe with 0x00 so frame decoders on the other end drop it
+                    return match hc14.send_byte(0) {
+                        Ok(_) => Ok(hc14),
+                        Err(_) => Err(TransitionError {
+                            hc14: hc14.into_mode(),
+                            error: Error::Write,
+                        }),
+                    };
+                }
+                Err(error) => {
+                    return Err(TransitionError {
+                        hc14: hc14.into_mode(),
+                        error,
+                    })
+                }
+            }
+        }
+        Err(TransitionError {
+            hc14: hc14.into_mode(),
+            error: Error::StillInAtMode,
+        })
+    }
+
+    /// 释放所含资源，电源控制引脚见 [`Hc14::release_with_power_pin`]
+    /// - Release of included resources, see [`Hc14::release_with_power_pin`] for the power-control pin
+    pub fn release(self) -> (S, P, D) {
+        (self.serial, self.key_pin, self.delay)
     }
 
     /// ! 使用 "AT" 指令检查，当前是否为: **AT配置模式**
     /// - Use the "AT" command to check if you are currently in: **AT configuration mode**.
+    ///
+    /// 在 [`ModeTiming::response_timeout_us`](super::handshake::ModeTiming::response_timeout_us) 内没有收到 `OK` 时返回 `false`。
+    ///
+    /// Returns `false` when no `OK` arrives within [`ModeTiming::response_timeout_us`](super::handshake::ModeTiming::response_timeout_us).
     /// # Example
     /// ```rust
     /// let hc14 = hc14::Hc14::new(serial, set, delay).unwrap();
@@ -53,18 +124,7 @@ where
     /// assert!(hc14_configure.is_at_mode());
     /// ```
     pub fn is_at_mode(&mut self) -> bool {
-        for ch in &AT_COMMAND_QUERY_MODE {
-            let _ = block!(self.serial.write(*ch));
-        }
-        let mut n: usize = 0;
-        let mut buffer: [u8; 4] = [0u8; 4];
-        while n < 4 {
-            if let Ok(ch) = block!(self.serial.read()) {
-                buffer[n] = ch;
-                n += 1;
-            }
-        }
-        buffer == RESPONSE_OK
+        self.at_probe()
     }
 
     /// **[Configuration]**: 将串行端口读取到的指令信息，返回至整个缓冲区
@@ -137,15 +197,22 @@ where
     /// Or the information you want to obtain by querying the command
     /// ```rust
     ///  hc14_configure.wirte_command(&AT_COMMAND_QUERY_VERSION, &mut buffer);
+    /// ```
     ///
+    /// 原始指令不经过频段规划的检查，请使用 [`Hc14::set_channel`]、[`Hc14::set_power`] 或
+    /// [`Hc14::apply_parameters`]。
+    ///
+    /// Raw commands are not checked against the band plan, use [`Hc14::set_channel`],
+    /// [`Hc14::set_power`] or [`Hc14::apply_parameters`] instead.
     pub fn wirte_command<'a>(&mut self, command: &[u8], buffer: &'a mut [u8]) -> &'a [u8] {
         self.send_buffer(command).unwrap();
         self.read_buffer(buffer)
     }
 
-    /// 将 HC-14 重置为默认设置。
+    /// 将 HC-14 重置为默认设置。出厂设置不经过频段规划的检查。
     ///
-    /// Reset the HC-14 to its default settings.
+    /// Reset the HC-14 to its default settings. The factory settings are not checked against the
+    /// band plan.
     pub fn reset_settings(&mut self) -> bool {
         self.send_buffer(&AT_COMMAND_DEFAULT).unwrap();
 
@@ -196,6 +263,7 @@ where
         let channel: Channel = Channel::try_from(param_slices[1]).ok()?;
         let speed: Speed = Speed::try_from(param_slices[2]).ok()?;
         let power: TransmissionPower = TransmissionPower::try_from(param_slices[3]).ok()?;
+        self.set_speed(speed);
 
         Some(Parameters {
             baud,
@@ -205,11 +273,88 @@ where
         })
     }
 
+    /// 依次写入 `parameters` 中的信道、速率、功率和波特率，并确认模块应答的值；新的波特率在回到正常模式后生效。
+    /// 频段规划不允许的信道或功率在写入任何设置之前被拒绝。某一项设置失败时返回错误，之前的设置已经生效，
+    /// 之后的设置没有写入；可以重试或用 [`Hc14::get_parameters`] 读回模块当前的参数。
+    ///
+    /// Write the channel, speed, power and baud rate of `parameters` in turn and check the values the
+    /// module answers with; the new baud rate applies once back in normal mode. A channel or power
+    /// level the band plan does not allow is refused before any setting is written. When one setting
+    /// fails the error is returned with the earlier settings already in effect and the later ones
+    /// not written; retry, or read the module's current parameters back with
+    /// [`Hc14::get_parameters`].
+    /// ```rust
+    /// let mut hc14_configure = hc14.into_configuration_mode().unwrap();
+    /// let profile = Parameters {
+    ///     channel: Channel::from(12),
+    ///     speed: Speed::S8,
+    ///     ..Parameters::default()
+    /// };
+    /// hc14_configure.apply_parameters(&profile).unwrap();
+    /// ```
+    pub fn apply_parameters(&mut self, parameters: &Parameters) -> Result<(), Error> {
+        self.band_plan.check(parameters)?;
+        self.set_channel(parameters.channel)?;
+        let mut line = [0u8; 16];
+        let answer = self.at_command(parameters.speed.make_command(), &mut line);
+        if Speed::try_from(answer.ok_or(Error::NoResponse)?) != Ok(parameters.speed) {
+            return Err(Error::CommandRejected);
+        }
+        self.set_speed(parameters.speed);
+        self.set_power(parameters.power)?;
+        let answer = self.at_command(parameters.baud.make_command(), &mut line);
+        if BaudRate::try_from(answer.ok_or(Error::NoResponse)?) != Ok(parameters.baud) {
+            return Err(Error::CommandRejected);
+        }
+        Ok(())
+    }
+
+    /// 在频段规划允许时设置无线信道，并确认模块应答的值
+    ///
+    /// Set the wireless channel when the band plan allows it and check the value the module answers
+    /// with.
+    /// ```rust
+    /// let mut hc14_configure = hc14.into_configuration_mode().unwrap();
+    /// hc14_configure.set_band_plan(BandPlan::CN433);
+    /// hc14_configure.set_channel(Channel::from(28)).unwrap();
+    /// assert!(hc14_configure.set_channel(Channel::from(1)).is_err());
+    /// ```
+    pub fn set_channel(&mut self, channel: Channel) -> Result<(), Error> {
+        if !self.band_plan.allows_channel(&channel) {
+            return Err(Error::InvalidChannel);
+        }
+        let mut command = [0u8; 7];
+        let mut line = [0u8; 16];
+        let answer = self.at_command(channel.make_command_buf(&mut command), &mut line);
+        if Channel::try_from(answer.ok_or(Error::NoResponse)?) != Ok(channel) {
+            return Err(Error::CommandRejected);
+        }
+        Ok(())
+    }
+
+    /// 在频段规划允许时设置发射功率，并确认模块应答的值
+    ///
+    /// Set the transmit power when the band plan allows it and check the value the module answers
+    /// with.
+    pub fn set_power(&mut self, power: TransmissionPower) -> Result<(), Error> {
+        if !self.band_plan.allows_power(&power) {
+            return Err(Error::InvalidPower);
+        }
+        let mut line = [0u8; 16];
+        let answer = self.at_command(power.make_command(), &mut line);
+        if TransmissionPower::try_from(answer.ok_or(Error::NoResponse)?) != Ok(power) {
+            return Err(Error::CommandRejected);
+        }
+        Ok(())
+    }
+
     /// 设置无线信道, 信道范围从1-50。
-    /// 该设置方法有两个，都是可用的。
+    /// 该设置方法有两个，都是可用的。范围之外或频段规划不允许的信道不会发送给模块，返回
+    /// [`Error::InvalidChannel`]。
     ///
     /// Setting the wireless channel, channel range from 1-50;
-    /// There are two methods for this setting, both of which are available
+    /// There are two methods for this setting, both of which are available. Channels out of range or
+    /// not allowed by the band plan are not sent to the module, [`Error::InvalidChannel`] is returned.
     /// ```rust
     /// let hc14 = hc14::Hc14::new(serial, set, delay).unwrap();
     /// // 进入配置模式(Entering Configuration Mode)
@@ -217,14 +362,21 @@ where
     /// // 创建缓冲区(Creating a Buffer)
     /// let mut buffer = [0u8; 16];
     /// // 执行信道设置指令(Execute channel setting commands)
-    /// hc14_configure.wirte_set_channel(2, &mut buffer);
+    /// hc14_configure.wirte_set_channel(2, &mut buffer).unwrap();
     /// ```
     ///
     pub fn wirte_set_channel<'a>(
         &mut self,
         mut channel_number: i32,
         buffer: &'a mut [u8],
-    ) -> &'a [u8] {
+    ) -> Result<&'a [u8], Error> {
+        if !(1..=50).contains(&channel_number)
+            || !self
+                .band_plan
+                .allows_channel(&Channel::from(channel_number as u8))
+        {
+            return Err(Error::InvalidChannel);
+        }
         let mut channel_command: [u8; 7] = [65, 84, 43, 67, 48, 48, 48];
 
         let mut buf: [u8; 2] = [0u8; 2];
@@ -244,6 +396,6 @@ where
                 channel_command[5 + index] = ascii_value;
             }
         }
-        self.wirte_command(&channel_command, buffer)
+        Ok(self.wirte_command(&channel_command, buffer))
     }
 }
@@ -0,0 +1,363 @@
+//! 法规占空比限制
+//!
+//! Regulatory duty-cycle limit.
+//!
+//! 部分地区要求 433 MHz 发射机在任意一段时间内的发射时间不超过一定比例。限制器把滑动窗口分成
+//! [`BUCKETS`] 段，每次发送按 [`Speed::get_airtime_us`] 累计空中时间：模块每次都发出完整的
+//! 数据包(包括前导码和同步字)，因此不满的数据包也按整包计算。发送会超出限制时，按照
+//! [`DutyAction`] 等待到窗口中腾出足够的时间，或者返回 [`Error::DutyCycle`]。
+//!
+//! Some regions only allow a 433 MHz transmitter on air for a fixed share of any period of time.
+//! The limiter splits its sliding window into [`BUCKETS`] slices and accounts the air time of every
+//! transmission with [`Speed::get_airtime_us`]: the module always sends whole packets (preamble and
+//! sync word included), so a partial packet counts as a full one. When a send would exceed the
+//! limit it either waits per [`DutyAction`] until the window has room, or returns
+//! [`Error::DutyCycle`].
+//!
+//! 限制器的时钟由驱动自己的等待推进，其余时间需要调用 [`Hc14::tick_duty_cycle`] 告知；不调用
+//! 时限制器只会偏保守。
+//!
+//! The limiter's clock advances with the driver's own waits, other time has to be reported through
+//! [`Hc14::tick_duty_cycle`]; without it the limiter merely errs on the safe side.
+//!
+//! # Example
+//! ```rust
+//! let mut hc14 = Hc14::new(serial, key, delay).unwrap();
+//! // 一小时内最多发射 1%(At most 1% on air within an hour)
+//! hc14.set_duty_cycle(Some(DutyCycle::new(10, 3_600_000, DutyAction::Reject)));
+//!
+//! match hc14.send_frame(b"report") {
+//!     Err(Error::DutyCycle) => hprintln!("{} us left", hc14.duty_cycle().unwrap().available_us()),
+//!     result => result.unwrap(),
+//! }
+//! // 在定时器中断中(In a timer interrupt)
+//! hc14.tick_duty_cycle(1_000);
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{pacing::Pacing, Hc14, Normal};
+use crate::{setting::speed::Speed, Error};
+
+/// 滑动窗口的分段数
+///
+/// Slices of the sliding window.
+pub const BUCKETS: usize = 20;
+
+/// 发送会超出占空比时的处理方式
+///
+/// What to do when a send would exceed the duty cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DutyAction {
+    /// 等待窗口中腾出时间(Wait until the window has room)
+    Delay,
+    /// 返回 [`Error::DutyCycle`](Return [`Error::DutyCycle`])
+    Reject,
+}
+
+/// 占空比限制器：在 `window_ms` 毫秒的滑动窗口内，发射时间不超过千分之 `permille`
+///
+/// Duty-cycle limiter: time on air stays within `permille` per thousand over a sliding window of
+/// `window_ms` milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DutyCycle {
+    permille: u16,
+    window_ms: u32,
+    action: DutyAction,
+    buckets: [u32; BUCKETS],
+    current: usize,
+    elapsed_us: u32,
+}
+
+impl DutyCycle {
+    /// 构建限制器，`permille` 超过 1000 时按 1000 计
+    ///
+    /// Build a limiter, `permille` above 1000 counts as 1000.
+    pub const fn new(permille: u16, window_ms: u32, action: DutyAction) -> Self {
+        Self {
+            permille: if permille > 1000 { 1000 } else { permille },
+            window_ms,
+            action,
+            buckets: [0; BUCKETS],
+            current: 0,
+            elapsed_us: 0,
+        }
+    }
+
+    /// 允许的发射比例(千分之)
+    ///
+    /// Allowed share on air, per thousand.
+    pub fn permille(&self) -> u16 {
+        self.permille
+    }
+
+    /// 滑动窗口的长度(毫秒)
+    ///
+    /// Length of the sliding window in milliseconds.
+    pub fn window_ms(&self) -> u32 {
+        self.window_ms
+    }
+
+    /// 超出限制时的处理方式
+    ///
+    /// What happens when the limit would be exceeded.
+    pub fn action(&self) -> DutyAction {
+        self.action
+    }
+
+    /// 一个窗口内允许的发射时间(微秒)
+    ///
+    /// Time on air allowed within one window, in microseconds.
+    pub fn budget_us(&self) -> u32 {
+        (u64::from(self.window_ms) * u64::from(self.permille)).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// 当前窗口内已用的发射时间(微秒)
+    ///
+    /// Time on air used within the current window, in microseconds.
+    pub fn used_us(&self) -> u32 {
+        self.buckets
+            .iter()
+            .fold(0u32, |sum, b| sum.saturating_add(*b))
+    }
+
+    /// 当前窗口内剩余的发射时间(微秒)
+    ///
+    /// Time on air left within the current window, in microseconds.
+    pub fn available_us(&self) -> u32 {
+        self.budget_us().saturating_sub(self.used_us())
+    }
+
+    /// 以 `speed` 一次发送 `bytes` 字节的空中时间(微秒)，按整包计算，见 [`Speed::get_airtime_us`]
+    ///
+    /// Air time of `bytes` bytes sent in one go at `speed`, in microseconds, counted in whole
+    /// packets, see [`Speed::get_airtime_us`].
+    pub fn airtime_us(speed: &Speed, bytes: usize) -> u32 {
+        speed.get_airtime_us(bytes)
+    }
+
+    /// 时间过去了 `us` 微秒
+    ///
+    /// `us` microseconds have passed.
+    pub fn advance_us(&mut self, us: u32) {
+        let slice = self.slice_us();
+        let mut elapsed = u64::from(self.elapsed_us) + u64::from(us);
+        for _ in 0..BUCKETS {
+            if elapsed < u64::from(slice) {
+                break;
+            }
+            elapsed -= u64::from(slice);
+            self.current = (self.current + 1) % BUCKETS;
+            self.buckets[self.current] = 0;
+        }
+        // 超过一整个窗口时所有分段都已清空(Past a whole window every slice is clear anyway)
+        self.elapsed_us = (elapsed % u64::from(slice)) as u32;
+    }
+
+    /// 记录 `us` 微秒的发射时间
+    ///
+    /// Record `us` microseconds on air.
+    pub fn record(&mut self, us: u32) {
+        self.buckets[self.current] = self.buckets[self.current].saturating_add(us);
+    }
+
+    /// 再发射 `us` 微秒之前需要等待的时间(微秒)；超过整个窗口的预算时返回 `None`
+    ///
+    /// Time to wait before another `us` microseconds on air, in microseconds; `None` when it exceeds
+    /// the budget of a whole window.
+    pub fn wait_us(&self, us: u32) -> Option<u32> {
+        let budget = self.budget_us();
+        if us > budget {
+            return None;
+        }
+        let need = (u64::from(self.used_us()) + u64::from(us)).saturating_sub(u64::from(budget));
+        if need == 0 {
+            return Some(0);
+        }
+        let slice = self.slice_us();
+        let mut freed: u64 = 0;
+        // 从最旧的分段开始，依次过期(Slices expire in turn, oldest first)
+        for k in 0..BUCKETS {
+            freed += u64::from(self.buckets[(self.current + 1 + k) % BUCKETS]);
+            if freed >= need {
+                let wait = (k as u64 + 1) * u64::from(slice) - u64::from(self.elapsed_us);
+                return Some(wait.min(u64::from(u32::MAX)) as u32);
+            }
+        }
+        None
+    }
+
+    /// 为 `us` 微秒的发射腾出时间并记录下来：按 [`DutyAction`] 等待或返回 [`Error::DutyCycle`]；
+    /// 返回等待的时间(微秒)
+    ///
+    /// Make room for `us` microseconds on air and record them: wait or return
+    /// [`Error::DutyCycle`] per [`DutyAction`]; returns the time waited, in microseconds.
+    pub(crate) fn admit(&mut self, us: u32, delay: &mut impl DelayUs<u32>) -> Result<u32, Error> {
+        let waited = match (self.wait_us(us), self.action) {
+            (Some(0), _) => 0,
+            (Some(wait), DutyAction::Delay) => {
+                delay.delay_us(wait);
+                self.advance_us(wait);
+                wait
+            }
+            _ => return Err(Error::DutyCycle),
+        };
+        self.record(us);
+        Ok(waited)
+    }
+
+    fn slice_us(&self) -> u32 {
+        (u64::from(self.window_ms) * 1000 / BUCKETS as u64).clamp(1, u64::from(u32::MAX)) as u32
+    }
+}
+
+/// 占空比限制(Duty-cycle limit)
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// **[Normal]**: 当前的占空比限制器
+    /// - The current duty-cycle limiter.
+    pub fn duty_cycle(&self) -> Option<&DutyCycle> {
+        self.duty_cycle.as_ref()
+    }
+
+    /// **[Normal]**: 设置或取消占空比限制
+    /// - Set or clear the duty-cycle limit.
+    pub fn set_duty_cycle(&mut self, limiter: Option<DutyCycle>) {
+        self.duty_cycle = limiter;
+    }
+
+    /// **[Normal]**: 告知限制器过去了 `elapsed_ms` 毫秒，例如在定时器中断中调用
+    /// - Tell the limiter `elapsed_ms` milliseconds have passed, e.g. from a timer interrupt.
+    pub fn tick_duty_cycle(&mut self, elapsed_ms: u32) {
+        if let Some(limiter) = &mut self.duty_cycle {
+            limiter.advance_us(elapsed_ms.saturating_mul(1000));
+        }
+    }
+
+    /// 为即将作为一次发送写入的 `bytes` 字节腾出并记录发射时间，每次发送只调用一次
+    ///
+    /// Make room on air for, and record, `bytes` bytes about to be written as one transmission;
+    /// called once per transmission.
+    pub(crate) fn admit(&mut self, bytes: usize) -> Result<(), Error> {
+        admit(
+            &mut self.duty_cycle,
+            &self.speed,
+            &mut self.pacing,
+            &mut self.delay,
+            bytes,
+        )
+    }
+}
+
+/// 完整驱动与拆分的发送端共用的 [`Hc14::admit`]：为一次发送申请发射时间，等待的时间同时推进发送节奏
+///
+/// [`Hc14::admit`] shared by the full driver and the split transmit half: admit one transmission,
+/// the time waited advancing the pacing too.
+pub(crate) fn admit(
+    duty_cycle: &mut Option<DutyCycle>,
+    speed: &Speed,
+    pacing: &mut Pacing,
+    delay: &mut impl DelayUs<u32>,
+    bytes: usize,
+) -> Result<(), Error> {
+    if let Some(limiter) = duty_cycle {
+        let waited = limiter.admit(DutyCycle::airtime_us(speed, bytes), delay)?;
+        pacing.advance_us(waited);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 累计等待时间的延迟(Delay adding up the time waited)
+    #[derive(Default)]
+    struct Waited(u32);
+
+    impl DelayUs<u32> for Waited {
+        fn delay_us(&mut self, us: u32) {
+            self.0 += us;
+        }
+    }
+
+    #[test]
+    fn airtime_counts_whole_packets() {
+        assert_eq!(DutyCycle::airtime_us(&Speed::S8, 0), 0);
+        assert_eq!(DutyCycle::airtime_us(&Speed::S8, 1), 700_000);
+        assert_eq!(DutyCycle::airtime_us(&Speed::S8, 250), 700_000);
+        assert_eq!(DutyCycle::airtime_us(&Speed::S8, 251), 1_000_000);
+        assert_eq!(DutyCycle::airtime_us(&Speed::S3, 161), 7_200_000);
+    }
+
+    #[test]
+    fn budget_follows_the_window() {
+        let limiter = DutyCycle::new(10, 3_600_000, DutyAction::Reject);
+        assert_eq!(limiter.budget_us(), 36_000_000);
+        assert_eq!(
+            DutyCycle::new(2000, 1_000, DutyAction::Reject).permille(),
+            1000
+        );
+    }
+
+    #[test]
+    fn wait_until_the_oldest_slices_expire() {
+        // 预算 2 s，每段 1 s(A 2 s budget, 1 s per slice)
+        let mut limiter = DutyCycle::new(100, 20_000, DutyAction::Delay);
+        limiter.record(1_500_000);
+        assert_eq!(limiter.wait_us(500_000), Some(0));
+        assert_eq!(limiter.wait_us(1_000_000), Some(20_000_000));
+        limiter.advance_us(300_000);
+        assert_eq!(limiter.wait_us(1_000_000), Some(19_700_000));
+        assert_eq!(limiter.wait_us(2_000_001), None);
+
+        limiter.advance_us(19_700_000);
+        assert_eq!(limiter.used_us(), 0);
+        assert_eq!(limiter.available_us(), 2_000_000);
+    }
+
+    #[test]
+    fn admit_waits_or_rejects() {
+        let mut delay = Waited::default();
+        let mut limiter = DutyCycle::new(100, 20_000, DutyAction::Reject);
+        assert_eq!(limiter.admit(1_500_000, &mut delay).unwrap(), 0);
+        assert!(matches!(
+            limiter.admit(1_000_000, &mut delay),
+            Err(Error::DutyCycle)
+        ));
+        assert_eq!(limiter.used_us(), 1_500_000);
+
+        let mut limiter = DutyCycle::new(100, 20_000, DutyAction::Delay);
+        limiter.admit(1_500_000, &mut delay).unwrap();
+        assert_eq!(limiter.admit(1_000_000, &mut delay).unwrap(), 20_000_000);
+        assert_eq!((delay.0, limiter.used_us()), (20_000_000, 1_000_000));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn driver_charges_once_per_transmission() {
+        use crate::{
+            setting::parameters::Parameters,
+            sim::{SimConfig, Simulator},
+        };
+
+        let mut sim = Simulator::new(SimConfig::default());
+        let node = sim.add_node(Parameters::default());
+        let mut hc14 = Hc14::new(node.serial, node.key, node.delay).unwrap();
+        hc14.set_duty_cycle(Some(DutyCycle::new(100, 30_000, DutyAction::Reject)));
+
+        hc14.send_frame(b"hello").unwrap();
+        assert_eq!(hc14.duty_cycle().unwrap().used_us(), 2_600_000);
+        assert!(matches!(hc14.send_frame(b"again"), Err(Error::DutyCycle)));
+        hc14.tick_duty_cycle(30_000);
+        hc14.send_frame(b"again").unwrap();
+    }
+}
@@ -0,0 +1,407 @@
+//! 单数据包帧：COBS 编码，`0x00` 结束，首字节为帧标志
+//!
+//! Single-packet frames: COBS encoded, ended by `0x00`, with a flag byte first.
+//!
+//! 每一帧都必须能放入当前速率等级的单个数据包，这样一帧要么完整到达，要么整体丢失。
+//! 启用 `compression` 特性后，可以逐帧选择压缩，帧标志记录该帧是否被压缩。
+//!
+//! Every frame must fit a single packet of the current rate class, so a frame either arrives whole
+//! or is lost whole. With the `compression` feature each frame may be compressed, and the frame flag
+//! records whether it was.
+//!
+//! # Example
+//! ```rust
+//! hc14.send_frame(b"hello").unwrap();
+//!
+//! let mut buffer = [0u8; 256];
+//! let payload = hc14.receive_frame(&mut buffer).unwrap();
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{receive, stats, Hc14, Normal};
+use crate::Error;
+
+/// 最大的帧长度：最大数据包(250 字节)加上 COBS 开销
+///
+/// Largest frame: the largest packet (250 bytes) plus COBS overhead.
+pub const MAX_FRAME_SIZE: usize = 256;
+
+/// 每帧的开销：COBS 首字节、帧标志和结束符
+///
+/// Per-frame overhead: the COBS code byte, the frame flag and the delimiter.
+pub const FRAME_OVERHEAD: usize = 3;
+
+/// 帧标志：负载已压缩
+///
+/// Frame flag: the payload is compressed.
+pub const FLAG_COMPRESSED: u8 = 0x01;
+
+/// 帧标志：负载是 [`messages`](super::messages) 发送的类型化消息
+///
+/// Frame flag: the payload is a typed message sent by [`messages`](super::messages).
+pub const FLAG_MESSAGE: u8 = 0x02;
+
+/// 等待帧时两次轮询串口之间的间隔(微秒)
+const POLL_US: u32 = 1_000;
+
+/// COBS 编码，返回编码后的长度(不含结束符)
+///
+/// COBS encode, returns the encoded length (without the delimiter).
+pub(crate) fn cobs_encode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut code_pos: usize = 0;
+    let mut out: usize = 1;
+    let mut code: u8 = 1;
+    *dst.get_mut(code_pos)? = 0;
+    for &byte in src {
+        if byte != 0 {
+            *dst.get_mut(out)? = byte;
+            out += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            dst[code_pos] = code;
+            code_pos = out;
+            *dst.get_mut(out)? = 0;
+            out += 1;
+            code = 1;
+        }
+    }
+    dst[code_pos] = code;
+    Some(out)
+}
+
+/// COBS 解码(输入不含结束符)，返回解码后的长度
+///
+/// COBS decode (input without the delimiter), returns the decoded length.
+pub(crate) fn cobs_decode(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let mut pos: usize = 0;
+    let mut out: usize = 0;
+    while pos < src.len() {
+        let code = src[pos] as usize;
+        if code == 0 {
+            return Err(Error::Decode);
+        }
+        pos += 1;
+        for _ in 1..code {
+            let byte = *src.get(pos).ok_or(Error::Decode)?;
+            if byte == 0 {
+                return Err(Error::Decode);
+            }
+            *dst.get_mut(out).ok_or(Error::MessageTooLarge)? = byte;
+            out += 1;
+            pos += 1;
+        }
+        if code < 0xFF && pos < src.len() {
+            *dst.get_mut(out).ok_or(Error::MessageTooLarge)? = 0;
+            out += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// 单数据包帧(Single-packet frames)
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// **[Normal]**: 当前速率等级下单帧可携带的最大负载
+    /// - Largest payload a single frame can carry at the current rate class
+    pub fn max_frame_payload(&self) -> usize {
+        self.speed.get_max_bytes_size() - FRAME_OVERHEAD
+    }
+
+    /// **[Normal]**: 以一帧发送 `payload`，超出单个数据包时返回 [`Error::MessageTooLarge`]
+    /// - Send `payload` as one frame, returns [`Error::MessageTooLarge`] when it exceeds a single packet
+    pub fn send_frame(&mut self, payload: &[u8]) -> Result<(), Error> {
+        self.write_frame(0, payload)
+    }
+
+    /// **[Normal]**: 压缩后以一帧发送 `payload`，压缩无益时原样发送
+    /// - Send `payload` as one compressed frame, or uncompressed when compression does not help
+    #[cfg(feature = "compression")]
+    pub fn send_compressed_frame(&mut self, payload: &[u8]) -> Result<(), Error> {
+        let mut packed = [0u8; MAX_FRAME_SIZE];
+        match crate::compress::compress(payload, &mut packed) {
+            Some(n) if n < payload.len() => self.write_frame(FLAG_COMPRESSED, &packed[..n]),
+            _ => self.write_frame(0, payload),
+        }
+    }
+
+    /// **[Normal]**: 接收一帧并返回其负载(必要时解压)，空帧会被跳过
+    /// - Receive one frame and return its payload (decompressed if needed), empty frames are skipped
+    pub fn receive_frame<'a>(&mut self, buffer: &'a mut [u8]) -> Result<&'a [u8], Error> {
+        let (_, len) = self.receive_flagged_frame(buffer)?;
+        Ok(&buffer[..len])
+    }
+
+    /// 与 [`receive_frame`](Self::receive_frame) 相同，返回帧标志和负载长度
+    ///
+    /// Like [`receive_frame`](Self::receive_frame), returns the frame flag and the payload length.
+    pub(crate) fn receive_flagged_frame(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(u8, usize), Error> {
+        let mut encoded = [0u8; MAX_FRAME_SIZE];
+        let outcome = loop {
+            let result = receive::read_until(&mut self.serial, 0, &mut encoded);
+            let outcome = self.stats.on_read(result)?;
+            if outcome.is_truncated() {
+                stats::add(&mut self.stats.frame_errors, 1);
+                return Err(Error::MessageTooLarge);
+            }
+            if outcome.len > 1 {
+                break outcome;
+            }
+        };
+        let result = decode_frame(&encoded[..outcome.len - 1], buffer);
+        self.stats.on_frame(result)
+    }
+
+    /// **[Normal]**: 与 [`receive_frame`](Self::receive_frame) 相同，但 `timeout_us` 微秒内没有收到完整的帧时返回
+    /// [`Error::Timeout`]；过长的帧会被跳过
+    /// - Like [`receive_frame`](Self::receive_frame), but returns [`Error::Timeout`] when no whole frame
+    ///   arrives within `timeout_us` microseconds; oversized frames are skipped
+    pub fn receive_frame_timeout<'a>(
+        &mut self,
+        buffer: &'a mut [u8],
+        timeout_us: u32,
+    ) -> Result<&'a [u8], Error> {
+        let (len, _) = self.receive_frame_timed(buffer, timeout_us)?;
+        Ok(&buffer[..len])
+    }
+
+    /// 与 [`receive_frame_timeout`](Self::receive_frame_timeout) 相同，返回负载长度和等待的时间(微秒)
+    ///
+    /// Like [`receive_frame_timeout`](Self::receive_frame_timeout), returns the payload length and
+    /// the time waited in microseconds.
+    pub(crate) fn receive_frame_timed(
+        &mut self,
+        buffer: &mut [u8],
+        timeout_us: u32,
+    ) -> Result<(usize, u32), Error> {
+        let mut encoded = [0u8; MAX_FRAME_SIZE];
+        let mut len: usize = 0;
+        let mut overflow = false;
+        let mut waited: u32 = 0;
+        loop {
+            let read = self.serial.read();
+            if read.is_ok() {
+                stats::add(&mut self.stats.bytes_received, 1);
+            }
+            match read {
+                Ok(0) => {
+                    if overflow {
+                        stats::add(&mut self.stats.frame_errors, 1);
+                    } else if len > 0 {
+                        let result = decode_frame(&encoded[..len], buffer);
+                        let (_, n) = self.stats.on_frame(result)?;
+                        return Ok((n, waited));
+                    }
+                    len = 0;
+                    overflow = false;
+                }
+                Ok(byte) => match encoded.get_mut(len) {
+                    Some(slot) => {
+                        *slot = byte;
+                        len += 1;
+                    }
+                    None => overflow = true,
+                },
+                Err(nb::Error::WouldBlock) => {
+                    if waited >= timeout_us {
+                        return Err(Error::Timeout);
+                    }
+                    self.wait_us(POLL_US);
+                    waited = waited.saturating_add(POLL_US);
+                }
+                Err(nb::Error::Other(_)) => {
+                    stats::add(&mut self.stats.overruns, 1);
+                    return Err(Error::Read);
+                }
+            }
+        }
+    }
+
+    /// 以给定的帧标志发送一帧
+    ///
+    /// Send one frame with the given frame flag.
+    pub(crate) fn write_frame(&mut self, flags: u8, body: &[u8]) -> Result<(), Error> {
+        if body.len() > self.max_frame_payload() {
+            return Err(Error::MessageTooLarge);
+        }
+        let mut raw = [0u8; MAX_FRAME_SIZE];
+        raw[0] = flags;
+        raw[1..=body.len()].copy_from_slice(body);
+        let mut encoded = [0u8; MAX_FRAME_SIZE];
+        let n = cobs_encode(&raw[..=body.len()], &mut encoded).ok_or(Error::MessageTooLarge)?;
+        self.admit(n + 1)?;
+        for ch in encoded[..n].iter().chain(&[0]) {
+            self.write_byte(*ch)?;
+        }
+        stats::add(&mut self.stats.frames_sent, 1);
+        Ok(())
+    }
+}
+
+/// 解码一帧(不含结束符)，返回帧标志和负载长度
+///
+/// Decode one frame (without the delimiter), returns the frame flag and the payload length.
+fn decode_frame(encoded: &[u8], buffer: &mut [u8]) -> Result<(u8, usize), Error> {
+    let mut raw = [0u8; MAX_FRAME_SIZE];
+    let n = cobs_decode(encoded, &mut raw)?;
+    if n == 0 {
+        return Err(Error::Decode);
+    }
+    let len = decode_payload(raw[0], &raw[1..n], buffer)?;
+    Ok((raw[0], len))
+}
+
+/// 按照帧标志将帧体还原为负载
+///
+/// Restore the payload from a frame body according to the frame flag.
+pub(crate) fn decode_payload(flags: u8, body: &[u8], buffer: &mut [u8]) -> Result<usize, Error> {
+    if flags & FLAG_COMPRESSED != 0 {
+        #[cfg(feature = "compression")]
+        return crate::compress::decompress(body, buffer);
+        #[cfg(not(feature = "compression"))]
+        return Err(Error::Decode);
+    }
+    buffer
+        .get_mut(..body.len())
+        .ok_or(Error::MessageTooLarge)?
+        .copy_from_slice(body);
+    Ok(body.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        let mut encoded = [0u8; MAX_FRAME_SIZE + 8];
+        let n = cobs_encode(data, &mut encoded).unwrap();
+        assert!(!encoded[..n].contains(&0));
+        assert!(n <= data.len() + 1 + data.len() / 254);
+        let mut decoded = [0u8; MAX_FRAME_SIZE];
+        let m = cobs_decode(&encoded[..n], &mut decoded).unwrap();
+        assert_eq!(&decoded[..m], data);
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        round_trip(b"");
+        round_trip(b"\0");
+        round_trip(b"\0\0");
+        round_trip(b"ab\0cd\0");
+        round_trip(&[0x11; 253]);
+        round_trip(&[0x11; 254]);
+        round_trip(&[0x11; 255]);
+        let mut mixed = [0u8; MAX_FRAME_SIZE];
+        for (i, byte) in mixed.iter_mut().enumerate() {
+            *byte = (i % 7) as u8;
+        }
+        round_trip(&mixed);
+    }
+
+    #[test]
+    fn cobs_rejects_bad_input() {
+        let mut decoded = [0u8; 8];
+        assert!(matches!(
+            cobs_decode(&[3, 1, 0], &mut decoded),
+            Err(Error::Decode)
+        ));
+        assert!(matches!(
+            cobs_decode(&[4, 1, 2], &mut decoded),
+            Err(Error::Decode)
+        ));
+        assert!(matches!(
+            cobs_decode(&[3, 1, 2], &mut decoded[..1]),
+            Err(Error::MessageTooLarge)
+        ));
+        assert!(cobs_encode(b"abc", &mut [0u8; 3]).is_none());
+    }
+
+    #[test]
+    fn frame_carries_its_flag() {
+        let mut encoded = [0u8; 16];
+        let n = cobs_encode(b"\0hi", &mut encoded).unwrap();
+        let mut payload = [0u8; 16];
+        let (flags, len) = decode_frame(&encoded[..n], &mut payload).unwrap();
+        assert_eq!((flags, &payload[..len]), (0, &b"hi"[..]));
+        let n = cobs_encode(b"\x02hi", &mut encoded).unwrap();
+        let (flags, len) = decode_frame(&encoded[..n], &mut payload).unwrap();
+        assert_eq!((flags, &payload[..len]), (FLAG_MESSAGE, &b"hi"[..]));
+        assert!(matches!(
+            decode_frame(&[], &mut payload),
+            Err(Error::Decode)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frames_over_the_simulator() {
+        use crate::{
+            setting::{parameters::Parameters, speed::Speed},
+            sim::{SimConfig, Simulator},
+        };
+
+        let mut sim = Simulator::new(SimConfig::default());
+        let parameters = Parameters {
+            speed: Speed::S8,
+            ..Parameters::default()
+        };
+        let [a, b] = [sim.add_node(parameters), sim.add_node(parameters)];
+        let mut hc14_a = Hc14::new(a.serial, a.key, a.delay).unwrap();
+        let mut hc14_b = Hc14::new(b.serial, b.key, b.delay).unwrap();
+        hc14_a.set_speed(Speed::S8);
+        hc14_b.set_speed(Speed::S8);
+
+        let payload = [0u8; 247];
+        assert_eq!(hc14_a.max_frame_payload(), payload.len());
+        hc14_a.send_frame(&payload).unwrap();
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        assert_eq!(hc14_b.receive_frame(&mut buffer).unwrap(), &payload[..]);
+        assert!(matches!(
+            hc14_a.send_frame(&[0u8; 248]),
+            Err(Error::MessageTooLarge)
+        ));
+        assert_eq!(hc14_a.stats().frames_sent, 1);
+        assert_eq!(hc14_b.stats().frames_received, 1);
+
+        // 没有数据时超时(Times out without data)
+        assert!(matches!(
+            hc14_b.receive_frame_timeout(&mut buffer, 10_000),
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[cfg(all(feature = "std", feature = "compression"))]
+    #[test]
+    fn compressed_frames_over_the_simulator() {
+        use crate::{
+            setting::parameters::Parameters,
+            sim::{SimConfig, Simulator},
+        };
+
+        let mut sim = Simulator::new(SimConfig::default());
+        let [a, b] = [
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+        ];
+        let mut hc14_a = Hc14::new(a.serial, a.key, a.delay).unwrap();
+        let mut hc14_b = Hc14::new(b.serial, b.key, b.delay).unwrap();
+
+        // 超过单帧的负载压缩后可以放入(A payload beyond one frame fits once compressed)
+        let payload = [b'x'; 120];
+        hc14_a.send_compressed_frame(&payload).unwrap();
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        assert_eq!(hc14_b.receive_frame(&mut buffer).unwrap(), &payload[..]);
+    }
+}
@@ -0,0 +1,198 @@
+//! 模式切换时以 `AT`/`OK` 握手确认模块的状态
+//!
+//! Confirming the module's state with an `AT`/`OK` handshake on mode transitions.
+//!
+//! 进入AT配置模式时，拉低 Key 引脚并等待 [`ModeTiming::settle_us`] 后发送 `AT`，直到收到 `OK`
+//! 或用完重试次数，此时 Key 引脚恢复高电平并返回 [`Error::NoResponse`]。离开时拉高 Key 引脚并
+//! 等待 [`ModeTiming::settle_us`]。打开 [`ModeTiming::verify_exit`] 后还会再发送一次 `AT` 确认
+//! 模块不再应答：这两个字节(外加一个 `0x00`，使对端的帧解码器丢弃它们)经过正常模式的发送路径
+//! 发到空中，计入统计、发送节奏和占空比；确认期间收到的数据会被读走。只使用原始字节流的对端
+//! 会收到这些字节，因此默认关闭。
+//!
+//! 无法在不发送的情况下确认：HC-14 没有指示模式的引脚，正常模式下也不会对串口上的任何内容
+//! 作出应答，而是把它们原样发到空中。“离开了AT配置模式”只能从“发出的 `AT` 没有得到 `OK`”
+//! 推断出来，所以确认必然要发送字节。默认情况下依靠 Key 引脚和 [`ModeTiming::settle_us`]；
+//! 如果 Key 引脚没有生效，下一次进入AT配置模式或发送的数据被模块当作AT指令时才会发现。
+//!
+//! Entering AT configuration mode pulls the key pin low, waits [`ModeTiming::settle_us`] and sends
+//! `AT` until `OK` comes back or the retries run out, in which case the key pin goes high again
+//! and [`Error::NoResponse`] is returned. Leaving pulls the key pin high and waits
+//! [`ModeTiming::settle_us`]. With [`ModeTiming::verify_exit`] on it also sends `AT` once more to
+//! confirm the module no longer answers: those two bytes (plus a `0x00` so that frame decoders on
+//! the other end discard them) go out over the air through the normal-mode send path, counted in
+//! the statistics, pacing and duty cycle, and data received during the check is consumed. Peers
+//! reading the raw byte stream would see these bytes, so the check is off by default.
+//!
+//! There is no way to confirm the exit without transmitting: the HC-14 has no pin reporting its
+//! mode, and in normal mode it answers nothing on the serial port but forwards every byte over
+//! the air. Having left AT configuration mode can only be inferred from an `AT` that went out and
+//! got no `OK`, so any confirmation transmits. By default the driver relies on the key pin and
+//! [`ModeTiming::settle_us`]; a key pin that did not take effect only shows on the next entry into
+//! AT configuration mode, or when data sent afterwards is taken as AT commands.
+//!
+//! # Example
+//! ```rust
+//! let mut hc14 = Hc14::new(serial, key, delay).unwrap();
+//! hc14.set_mode_timing(ModeTiming {
+//!     settle_us: 150_000,
+//!     retries: 5,
+//!     ..ModeTiming::default()
+//! });
+//! match hc14.into_configuration_mode() {
+//!     Ok(configure) => { /* ... */ }
+//!     Err(e) => hprintln!("module silent: {:?}", e.error),
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use nb::block;
+
+use super::Hc14;
+use crate::{
+    conf::{AT_COMMAND_QUERY_MODE, RESPONSE_OK},
+    Error,
+};
+
+/// 等待应答时两次轮询串口之间的间隔(微秒)
+const POLL_US: u32 = 1_000;
+
+/// 模式切换的时间和重试设置
+///
+/// Timing and retries of mode transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeTiming {
+    /// 切换 Key 引脚后等待模块就绪的时间(微秒，默认 100 ms)
+    /// (Wait after switching the key pin until the module is ready, in microseconds, 100 ms by default)
+    pub settle_us: u32,
+    /// `AT` 没有应答时的重试次数(默认 3)(Retries when `AT` is not answered, 3 by default)
+    pub retries: u8,
+    /// 每次等待 `OK` 的时间(微秒，默认 200 ms)(Wait for `OK` per attempt, in microseconds, 200 ms by default)
+    pub response_timeout_us: u32,
+    /// 离开AT配置模式时是否发送 `AT` 确认(默认否：确认只能通过发到空中的字节完成，见
+    /// [模块文档](self))
+    /// (Whether leaving AT configuration mode is confirmed by sending `AT`, no by default: the
+    /// check can only be made with bytes that go out over the air, see the
+    /// [module documentation](self))
+    pub verify_exit: bool,
+    /// 断开电源后保持的时间(微秒，默认 100 ms)(Time kept powered off, in microseconds, 100 ms by default)
+    pub power_off_us: u32,
+    /// 接通电源后等待模块启动的时间(微秒，默认 250 ms)
+    /// (Wait after powering on until the module has booted, in microseconds, 250 ms by default)
+    pub boot_us: u32,
+}
+
+impl Default for ModeTiming {
+    fn default() -> Self {
+        Self {
+            settle_us: 100_000,
+            retries: 3,
+            response_timeout_us: 200_000,
+            verify_exit: false,
+            power_off_us: 100_000,
+            boot_us: 250_000,
+        }
+    }
+}
+
+/// 模式切换设置(Mode transition settings)
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 当前的模式切换设置
+    ///
+    /// The current mode transition settings.
+    pub fn mode_timing(&self) -> ModeTiming {
+        self.mode_timing
+    }
+
+    /// 修改模式切换设置
+    ///
+    /// Change the mode transition settings.
+    pub fn set_mode_timing(&mut self, timing: ModeTiming) {
+        self.mode_timing = timing;
+    }
+
+    /// 丢弃串口中已收到的数据
+    ///
+    /// Discard whatever the serial port has already received.
+    pub(crate) fn discard_input(&mut self) {
+        while self.serial.read().is_ok() {}
+    }
+
+    /// 发送 `AT` 并在 [`ModeTiming::response_timeout_us`] 内等待 `OK`
+    ///
+    /// Send `AT` and wait up to [`ModeTiming::response_timeout_us`] for `OK`.
+    pub(crate) fn at_probe(&mut self) -> bool {
+        for ch in &AT_COMMAND_QUERY_MODE {
+            if block!(self.serial.write(*ch)).is_err() {
+                return false;
+            }
+        }
+        self.await_ok().unwrap_or(false)
+    }
+
+    /// 在 [`ModeTiming::response_timeout_us`] 内等待 `OK`，串口出错时返回 [`Error::Read`]
+    ///
+    /// Wait up to [`ModeTiming::response_timeout_us`] for `OK`, returning [`Error::Read`] on a
+    /// serial error.
+    pub(crate) fn await_ok(&mut self) -> Result<bool, Error> {
+        let mut tail = [0u8; RESPONSE_OK.len()];
+        let mut waited: u32 = 0;
+        while waited < self.mode_timing.response_timeout_us {
+            match self.serial.read() {
+                Ok(ch) => {
+                    tail.copy_within(1.., 0);
+                    tail[RESPONSE_OK.len() - 1] = ch;
+                    if tail == RESPONSE_OK {
+                        return Ok(true);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => {
+                    self.delay.delay_us(POLL_US);
+                    waited += POLL_US;
+                }
+                Err(nb::Error::Other(_)) => return Err(Error::Read),
+            }
+        }
+        Ok(false)
+    }
+
+    /// 发送一条AT指令并在 [`ModeTiming::response_timeout_us`] 内读取一行应答
+    ///
+    /// Send one AT command and read one answer line within [`ModeTiming::response_timeout_us`].
+    pub(crate) fn at_command<'a>(
+        &mut self,
+        command: &[u8],
+        buffer: &'a mut [u8],
+    ) -> Option<&'a [u8]> {
+        for ch in command {
+            block!(self.serial.write(*ch)).ok()?;
+        }
+        let mut count = 0;
+        let mut waited: u32 = 0;
+        while waited < self.mode_timing.response_timeout_us && count < buffer.len() {
+            match self.serial.read() {
+                Ok(ch) => {
+                    buffer[count] = ch;
+                    count += 1;
+                    if ch == b'\n' {
+                        return Some(&buffer[..count]);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => {
+                    self.delay.delay_us(POLL_US);
+                    waited += POLL_US;
+                }
+                Err(nb::Error::Other(_)) => return None,
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,179 @@
+//! 正常模式的 `core::fmt::Write` 与 `embedded_io` 实现
+//!
+//! `core::fmt::Write` and `embedded_io` implementations for normal mode.
+//!
+//! # Example
+//! ```rust
+//! use core::fmt::Write;
+//!
+//! let mut hc14 = Hc14::new(serial, key, delay).unwrap();
+//! write!(hc14, "t={} v={:.2}\n", 42, 3.3).unwrap();
+//! ```
+use core::fmt;
+
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use embedded_io::ErrorKind;
+use nb::block;
+
+use super::{
+    split::{Hc14Rx, Hc14Tx},
+    Hc14, Normal,
+};
+use crate::Error;
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Read
+            | Error::Write
+            | Error::ModbusException(_)
+            | Error::Rejected(_)
+            | Error::Pin
+            | Error::Flash => ErrorKind::Other,
+            Error::InvalidBaudRate
+            | Error::InvalidChannel
+            | Error::InvalidPower
+            | Error::MessageTooLarge => ErrorKind::InvalidInput,
+            Error::Utf8 | Error::Decode | Error::Crc | Error::Verification => {
+                ErrorKind::InvalidData
+            }
+            Error::Timeout | Error::NoResponse => ErrorKind::TimedOut,
+            Error::StillInAtMode | Error::CommandRejected | Error::DutyCycle => ErrorKind::Other,
+            Error::NoPowerPin => ErrorKind::Unsupported,
+        }
+    }
+}
+
+/// 先阻塞读取一个字节，然后不阻塞地读取剩余可用的字节
+///
+/// Block for the first byte, then read whatever else is available without blocking.
+fn read_some<R: Read<u8>>(serial: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    buf[0] = block!(serial.read()).map_err(|_| Error::Read)?;
+    let mut count: usize = 1;
+    while count < buf.len() {
+        match serial.read() {
+            Ok(ch) => {
+                buf[count] = ch;
+                count += 1;
+            }
+            Err(nb::Error::WouldBlock) => break,
+            Err(nb::Error::Other(_)) => return Err(Error::Read),
+        }
+    }
+    Ok(count)
+}
+
+impl<S, P, D, W> fmt::Write for Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.transmit(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl<S, P, D, W> embedded_io::ErrorType for Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    type Error = Error;
+}
+
+impl<S, P, D, W> embedded_io::Read for Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let result = read_some(&mut self.serial, buf);
+        self.stats.on_bytes(result)
+    }
+}
+
+impl<S, P, D, W> embedded_io::Write for Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.transmit(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        block!(self.serial.flush()).map_err(|_| Error::Write)
+    }
+}
+
+impl<TX, P, D, W> fmt::Write for Hc14Tx<TX, P, D, W>
+where
+    TX: Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.send_string(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl<TX, P, D, W> embedded_io::ErrorType for Hc14Tx<TX, P, D, W>
+where
+    TX: Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    type Error = Error;
+}
+
+impl<TX, P, D, W> embedded_io::Write for Hc14Tx<TX, P, D, W>
+where
+    TX: Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.send_buffer(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Hc14Tx::flush(self)
+    }
+}
+
+impl<RX> embedded_io::ErrorType for Hc14Rx<RX>
+where
+    RX: Read<u8>,
+{
+    type Error = Error;
+}
+
+impl<RX> embedded_io::Read for Hc14Rx<RX>
+where
+    RX: Read<u8>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let result = read_some(&mut self.rx, buf);
+        self.stats.on_bytes(result)
+    }
+}
@@ -0,0 +1,105 @@
+//! 通过无线发送类型化消息(需要 `messages` 特性)
+//!
+//! Typed messages over the radio (requires the `messages` feature).
+//!
+//! 消息使用 postcard 序列化，作为 [`frame`](super::frame) 的单数据包帧的负载发送，并带有帧标志
+//! [`FLAG_MESSAGE`]，因此可以和 [`Hc14::send_frame`] 发送的帧在同一信道上混用；没有该标志的帧在接收时返回
+//! [`Error::Decode`]。
+//! 序列化后的消息必须能放入当前速率等级的单帧([`Hc14::max_frame_payload`])，否则在发送前报错。
+//!
+//! Messages are serialized with postcard and sent as the payload of a single-packet
+//! [`frame`](super::frame) carrying the [`FLAG_MESSAGE`] frame flag, so they can share a channel
+//! with frames sent by [`Hc14::send_frame`]; a frame without that flag is reported as
+//! [`Error::Decode`] on receipt. A serialized message
+//! must fit a single frame of the current rate class ([`Hc14::max_frame_payload`]), otherwise
+//! sending fails before anything is written.
+//!
+//! # Example
+//! ```rust
+//! #[derive(Serialize, Deserialize)]
+//! struct Reading {
+//!     node: u8,
+//!     temperature: i16,
+//! }
+//!
+//! hc14.send_message(&Reading { node: 1, temperature: 215 }).unwrap();
+//! let reading: Reading = hc14.receive_message().unwrap();
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    frame::{FLAG_MESSAGE, MAX_FRAME_SIZE},
+    Hc14, Normal,
+};
+use crate::Error;
+
+/// 类型化消息(Typed messages)
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// **[Normal]**: 序列化并以一帧发送一条消息，消息无法放入当前速率等级的单帧时返回 [`Error::MessageTooLarge`]
+    /// - Serialize and send one message as a frame, returns [`Error::MessageTooLarge`] when it cannot fit a single frame of the current rate class
+    pub fn send_message<T: Serialize>(&mut self, message: &T) -> Result<(), Error> {
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let payload =
+            postcard::to_slice(message, &mut buffer).map_err(|_| Error::MessageTooLarge)?;
+        self.write_frame(FLAG_MESSAGE, payload)
+    }
+
+    /// **[Normal]**: 接收一帧并反序列化为一条消息，空帧会被跳过，不是消息的帧返回 [`Error::Decode`]
+    /// - Receive one frame and deserialize it as a message, empty frames are skipped and a frame that is not a message returns [`Error::Decode`]
+    pub fn receive_message<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let (flags, len) = self.receive_flagged_frame(&mut buffer)?;
+        if flags & FLAG_MESSAGE == 0 {
+            return Err(Error::Decode);
+        }
+        postcard::from_bytes(&buffer[..len]).map_err(|_| Error::Decode)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        driver::Hc14,
+        setting::parameters::Parameters,
+        sim::{SimConfig, Simulator},
+        Error,
+    };
+
+    #[test]
+    fn messages_share_the_channel_with_frames() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let [a, b] = [
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+        ];
+        let mut hc14_a = Hc14::new(a.serial, a.key, a.delay).unwrap();
+        let mut hc14_b = Hc14::new(b.serial, b.key, b.delay).unwrap();
+
+        hc14_a.send_message(&(1u8, -215i16)).unwrap();
+        hc14_a.send_frame(b"raw").unwrap();
+        hc14_a.send_frame(b"raw").unwrap();
+        assert_eq!(hc14_b.receive_message::<(u8, i16)>().unwrap(), (1, -215));
+        let mut buffer = [0u8; 16];
+        assert_eq!(hc14_b.receive_frame(&mut buffer).unwrap(), b"raw");
+        // 不是消息的帧不会被误解析(A frame that is not a message is not misparsed)
+        assert!(matches!(
+            hc14_b.receive_message::<(u8, i16)>(),
+            Err(Error::Decode)
+        ));
+        assert!(matches!(
+            hc14_a.send_message(&[u32::MAX; 32]),
+            Err(Error::MessageTooLarge)
+        ));
+    }
+}
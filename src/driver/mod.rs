@@ -7,12 +7,67 @@ use embedded_hal::{
 };
 pub use nb::*;
 
+use crate::setting::{band::BandPlan, speed::Speed};
+use duty::DutyCycle;
+use handshake::ModeTiming;
+use pacing::Pacing;
+use power::NoPowerPin;
+use sleep::SleepMethod;
+use stats::LinkStats;
+
 /// AT配置模式(AT Configuration Mode)
 pub mod configure;
 
 /// 正常模式(Normal Mode)
 pub mod normal;
 
+/// 中断接收缓冲区(Interrupt-driven receive buffer)
+pub mod buffered;
+
+/// 拆分的发送端与接收端(Split transmit and receive halves)
+pub mod split;
+
+/// 数据包发送节奏(Packet pacing)
+pub mod pacing;
+
+/// `core::fmt::Write` 与 `embedded_io` 实现(`core::fmt::Write` and `embedded_io` implementations)
+pub mod io;
+
+/// 分隔符、定长与按行接收(Delimiter, fixed-length and line receive)
+pub mod receive;
+
+/// 单数据包帧(Single-packet frames)
+pub mod frame;
+
+/// 类型化消息(Typed messages)
+#[cfg(feature = "messages")]
+pub mod messages;
+
+/// Modbus RTU 传输(Modbus RTU transport)
+pub mod modbus;
+
+/// 模式切换握手(Mode transition handshake)
+pub mod handshake;
+
+/// 运行时模式的驱动(Driver with a runtime mode)
+pub mod any;
+
+/// 链路统计(Link statistics)
+pub mod stats;
+
+/// 电源控制引脚(Power-control pin)
+pub mod power;
+
+/// 睡眠模式与空闲策略(Sleep mode and idle policy)
+pub mod sleep;
+
+/// 占空比限制(Duty-cycle limit)
+pub mod duty;
+
+/// smoltcp 网络接口(smoltcp network interface)
+#[cfg(feature = "smoltcp")]
+pub mod net;
+
 /// 正常模式标记(Normal Mode Flags)
 #[derive(Debug)]
 pub struct Normal;
@@ -21,16 +76,102 @@ pub struct Normal;
 #[derive(Debug)]
 pub struct Configuration;
 
+/// 睡眠模式标记(Sleep Mode Flags)
+#[derive(Debug)]
+pub struct Sleep;
+
+/// 模式切换失败：交还原来状态的驱动以及错误，Key 引脚的电平未知，可以再次尝试切换
+///
+/// A failed mode transition: hands back the driver in its original state together with the error;
+/// the level of the key pin is unknown, the transition may be tried again.
+///
+/// # Example
+/// ```rust
+/// let hc14 = match hc14.into_configuration_mode() {
+///     Ok(mut configure) => {
+///         configure.reset_settings();
+///         configure.into_normal_mode().map_err(|e| e.error)?
+///     }
+///     // 引脚故障，驱动仍可在正常模式下使用(Pin fault, the driver still works in normal mode)
+///     Err(e) => e.into_inner(),
+/// };
+/// ```
+pub struct TransitionError<T> {
+    /// 原来状态的驱动(The driver in its original state)
+    pub hc14: T,
+    /// 失败的原因(Why the transition failed)
+    pub error: crate::Error,
+}
+
+impl<T> TransitionError<T> {
+    /// 取回原来状态的驱动
+    ///
+    /// Take back the driver in its original state.
+    pub fn into_inner(self) -> T {
+        self.hc14
+    }
+}
+
+// 串口等资源未必实现 `Debug`，只打印错误，以便调用 `unwrap`
+// Serial ports and friends may not implement `Debug`, print the error only so `unwrap` works
+impl<T> core::fmt::Debug for TransitionError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Hc14 资源：串行端口、输出引脚和延迟。Hc14 Resources: serial ports, output pins, and delays.
 #[derive(Debug, Clone, Copy)]
-pub struct Hc14<S, P, D, M>
+pub struct Hc14<S, P, D, M, W = NoPowerPin>
 where
     S: Read<u8> + Write<u8>,
     P: OutputPin,
     D: DelayUs<u32>,
+    W: OutputPin,
 {
     serial: S,
     key_pin: P,
     delay: D,
+    speed: Speed,
+    pacing: Pacing,
+    stats: LinkStats,
+    mode_timing: ModeTiming,
+    power_pin: W,
+    /// 是否由 [`Hc14::with_power_pin`] 构建(Whether built by [`Hc14::with_power_pin`])
+    has_power_pin: bool,
+    sleep_method: SleepMethod,
+    duty_cycle: Option<DutyCycle>,
+    band_plan: BandPlan,
     pub(crate) mode: PhantomData<M>,
 }
+
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 以另一种模式标记交出全部资源和设置
+    ///
+    /// Hand over every resource and setting under another mode marker.
+    pub(crate) fn into_mode<N>(self) -> Hc14<S, P, D, N, W> {
+        Hc14 {
+            serial: self.serial,
+            key_pin: self.key_pin,
+            delay: self.delay,
+            speed: self.speed,
+            pacing: self.pacing,
+            stats: self.stats,
+            mode_timing: self.mode_timing,
+            power_pin: self.power_pin,
+            has_power_pin: self.has_power_pin,
+            sleep_method: self.sleep_method,
+            duty_cycle: self.duty_cycle,
+            band_plan: self.band_plan,
+            mode: PhantomData::<N>,
+        }
+    }
+}
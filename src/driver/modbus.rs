@@ -0,0 +1,659 @@
+//! 基于 HC-14 的 Modbus RTU 传输(主站与从站)
+//!
+//! Modbus RTU transport over HC-14 (master and slave).
+//!
+//! 有线 Modbus RTU 以 3.5 个字符的静默间隔分隔帧，但 HC-14 会把数据打包后在空中发送，
+//! 同一帧的两个数据包之间会有数百毫秒的间隔。因此这里按功能码推算帧长，无法推算时以 CRC
+//! 判断帧结束，只有静默超过一个数据包的发送时间才丢弃不完整的帧。响应超时由请求和响应
+//! 在当前 [`Speed`](crate::setting::speed::Speed) 下的空中时间得出。
+//!
+//! Wired Modbus RTU separates frames with a 3.5 character silent interval, but HC-14 packs the data
+//! into packets on air, and two packets of one frame can be hundreds of milliseconds apart. Frame
+//! length is therefore derived from the function code, falling back to the CRC to find the end of a
+//! frame, and an incomplete frame is only dropped after a silence longer than one packet's airtime.
+//! Response timeouts follow from the airtime of the request and the response at the current
+//! [`Speed`](crate::setting::speed::Speed).
+//!
+//! # Example
+//! ```rust
+//! // 主站(Master)
+//! let mut registers = [0u16; 4];
+//! hc14.modbus().read_holding_registers(1, 0x0000, &mut registers).unwrap();
+//!
+//! // 从站(Slave)
+//! let mut buffer = [0u8; MAX_ADU_SIZE];
+//! let mut modbus = hc14.modbus();
+//! if let Ok(request) = modbus.receive_request(1, &mut buffer, 1_000_000) {
+//!     let (unit, function) = (request.unit, request.function());
+//!     modbus.respond_exception(unit, function, exception::ILLEGAL_FUNCTION).unwrap();
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{stats, Hc14, Normal};
+use crate::Error;
+
+/// 最大的 RTU 帧长度：地址、PDU(最多 253 字节)和 CRC
+///
+/// Largest RTU frame: address, PDU (up to 253 bytes) and CRC.
+pub const MAX_ADU_SIZE: usize = 256;
+
+/// 广播地址，从站不响应广播请求
+///
+/// Broadcast address, slaves do not answer broadcast requests.
+pub const BROADCAST: u8 = 0;
+
+/// Modbus 异常码
+///
+/// Modbus exception codes.
+pub mod exception {
+    /// 非法功能(Illegal function)
+    pub const ILLEGAL_FUNCTION: u8 = 0x01;
+    /// 非法数据地址(Illegal data address)
+    pub const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+    /// 非法数据值(Illegal data value)
+    pub const ILLEGAL_DATA_VALUE: u8 = 0x03;
+    /// 从站设备故障(Server device failure)
+    pub const SERVER_DEVICE_FAILURE: u8 = 0x04;
+}
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// 接收等待的时间参数
+///
+/// Timing parameters for receive waits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtuTiming {
+    /// 无数据时两次轮询串口之间的等待(微秒)
+    ///
+    /// Wait between two serial polls while no data is ready, in microseconds.
+    pub poll_us: u32,
+    /// 帧长未知时，CRC 正确且静默这么久即认为帧结束(微秒)
+    ///
+    /// When the frame length is unknown, a valid CRC followed by this much silence ends the frame,
+    /// in microseconds.
+    pub idle_us: u32,
+    /// 从站处理请求的时间余量，计入响应超时(微秒)
+    ///
+    /// Slave processing margin added to the response timeout, in microseconds.
+    pub turnaround_us: u32,
+}
+
+impl Default for RtuTiming {
+    fn default() -> Self {
+        Self {
+            poll_us: 500,
+            idle_us: 5_000,
+            turnaround_us: 200_000,
+        }
+    }
+}
+
+/// 从站收到的请求
+///
+/// A request received by a slave.
+#[derive(Debug)]
+pub struct Request<'a> {
+    /// 请求的目标地址：本站地址或 [`BROADCAST`]
+    ///
+    /// Address the request was sent to: the slave's own address or [`BROADCAST`].
+    pub unit: u8,
+    /// 请求的 PDU(功能码和数据)
+    ///
+    /// PDU of the request (function code and data).
+    pub pdu: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    /// 功能码
+    ///
+    /// Function code.
+    pub fn function(&self) -> u8 {
+        self.pdu[0]
+    }
+
+    /// 功能码之后的数据
+    ///
+    /// Data after the function code.
+    pub fn data(&self) -> &'a [u8] {
+        &self.pdu[1..]
+    }
+}
+
+/// 借用正常模式驱动的 Modbus RTU 传输
+///
+/// Modbus RTU transport borrowing the normal-mode driver.
+pub struct Modbus<'a, S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    hc14: &'a mut Hc14<S, P, D, Normal, W>,
+    timing: RtuTiming,
+}
+
+/// Modbus RTU
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// **[Normal]**: 以默认时间参数使用 Modbus RTU 传输
+    /// - Use the Modbus RTU transport with the default timing
+    pub fn modbus(&mut self) -> Modbus<'_, S, P, D, W> {
+        Modbus {
+            hc14: self,
+            timing: RtuTiming::default(),
+        }
+    }
+}
+
+impl<'a, S, P, D, W> Modbus<'a, S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 使用自定义的时间参数
+    ///
+    /// Use custom timing parameters.
+    pub fn with_timing(mut self, timing: RtuTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// 在当前速率等级下 `len` 字节的空中时间(微秒)
+    ///
+    /// Airtime of `len` bytes at the current rate class, in microseconds.
+    pub fn airtime_us(&self, len: usize) -> u32 {
+        self.hc14.speed().get_airtime_us(len.max(1))
+    }
+
+    /// 请求长 `request_len`、响应长 `response_len` 字节时的响应超时(微秒)
+    ///
+    /// Response timeout for a `request_len`-byte request and a `response_len`-byte response, in
+    /// microseconds.
+    pub fn response_timeout_us(&self, request_len: usize, response_len: usize) -> u32 {
+        self.airtime_us(request_len) + self.airtime_us(response_len) + self.timing.turnaround_us
+    }
+
+    // ---------------------------------------------------------------------------------------------
+    // 主站(Master)
+
+    /// 向 `unit` 发送请求 PDU 并等待响应，返回响应 PDU；广播请求不等待响应，返回空切片。
+    /// CRC 错误的帧被跳过直至超时，从站的异常响应返回 [`Error::ModbusException`]
+    ///
+    /// Send a request PDU to `unit` and wait for the answer, returns the response PDU; broadcast
+    /// requests do not wait and return an empty slice. Frames with a bad CRC are skipped until the
+    /// timeout, an exception response from the slave returns [`Error::ModbusException`].
+    pub fn request<'b>(
+        &mut self,
+        unit: u8,
+        pdu: &[u8],
+        buffer: &'b mut [u8; MAX_ADU_SIZE],
+    ) -> Result<&'b [u8], Error> {
+        self.request_expecting(unit, pdu, MAX_ADU_SIZE, buffer)
+    }
+
+    /// 读保持寄存器(功能码 0x03)
+    ///
+    /// Read holding registers (function 0x03).
+    pub fn read_holding_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), Error> {
+        self.read_registers(READ_HOLDING_REGISTERS, unit, address, values)
+    }
+
+    /// 读输入寄存器(功能码 0x04)
+    ///
+    /// Read input registers (function 0x04).
+    pub fn read_input_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), Error> {
+        self.read_registers(READ_INPUT_REGISTERS, unit, address, values)
+    }
+
+    /// 写单个寄存器(功能码 0x06)
+    ///
+    /// Write a single register (function 0x06).
+    pub fn write_single_register(
+        &mut self,
+        unit: u8,
+        address: u16,
+        value: u16,
+    ) -> Result<(), Error> {
+        let mut pdu = [WRITE_SINGLE_REGISTER, 0, 0, 0, 0];
+        pdu[1..3].copy_from_slice(&address.to_be_bytes());
+        pdu[3..5].copy_from_slice(&value.to_be_bytes());
+        let mut buffer = [0u8; MAX_ADU_SIZE];
+        self.request_expecting(unit, &pdu, 8, &mut buffer)?;
+        Ok(())
+    }
+
+    /// 写多个寄存器(功能码 0x10)，最多 123 个
+    ///
+    /// Write multiple registers (function 0x10), at most 123.
+    pub fn write_multiple_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &[u16],
+    ) -> Result<(), Error> {
+        if values.is_empty() || values.len() > 123 {
+            return Err(Error::MessageTooLarge);
+        }
+        let mut pdu = [0u8; MAX_ADU_SIZE - 3];
+        pdu[0] = WRITE_MULTIPLE_REGISTERS;
+        pdu[1..3].copy_from_slice(&address.to_be_bytes());
+        pdu[3..5].copy_from_slice(&(values.len() as u16).to_be_bytes());
+        pdu[5] = (values.len() * 2) as u8;
+        for (i, value) in values.iter().enumerate() {
+            pdu[6 + i * 2..8 + i * 2].copy_from_slice(&value.to_be_bytes());
+        }
+        let mut buffer = [0u8; MAX_ADU_SIZE];
+        self.request_expecting(unit, &pdu[..6 + values.len() * 2], 8, &mut buffer)?;
+        Ok(())
+    }
+
+    fn read_registers(
+        &mut self,
+        function: u8,
+        unit: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), Error> {
+        if values.is_empty() || values.len() > 125 {
+            return Err(Error::MessageTooLarge);
+        }
+        let mut pdu = [function, 0, 0, 0, 0];
+        pdu[1..3].copy_from_slice(&address.to_be_bytes());
+        pdu[3..5].copy_from_slice(&(values.len() as u16).to_be_bytes());
+        let mut buffer = [0u8; MAX_ADU_SIZE];
+        let response = self.request_expecting(unit, &pdu, 5 + values.len() * 2, &mut buffer)?;
+        if response.len() != 2 + values.len() * 2 || usize::from(response[1]) != values.len() * 2 {
+            return Err(Error::Decode);
+        }
+        for (value, bytes) in values.iter_mut().zip(response[2..].chunks(2)) {
+            *value = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(())
+    }
+
+    fn request_expecting<'b>(
+        &mut self,
+        unit: u8,
+        pdu: &[u8],
+        response_len: usize,
+        buffer: &'b mut [u8; MAX_ADU_SIZE],
+    ) -> Result<&'b [u8], Error> {
+        if pdu.is_empty() {
+            return Err(Error::Decode);
+        }
+        // 丢弃之前残留的数据(Discard anything left over from before)
+        while self.hc14.serial.read().is_ok() {}
+        self.send_adu(unit, pdu)?;
+        if unit == BROADCAST {
+            self.hc14.wait_us(self.timing.turnaround_us);
+            return Ok(&[]);
+        }
+
+        let mut timeout = self.response_timeout_us(pdu.len() + 3, response_len);
+        let mut elapsed: u32 = 0;
+        loop {
+            let (received, waited) = self.receive_adu(buffer, false, timeout);
+            timeout = timeout.saturating_sub(waited);
+            elapsed = elapsed.saturating_add(waited);
+            let len = match received {
+                Ok(len) => len,
+                // 与从站相同，损坏的帧(已计入 CRC 错误)不结束等待
+                // As on the slave, a corrupted frame (counted as a CRC error) does not end the wait
+                Err(Error::Crc) if timeout > 0 => continue,
+                Err(Error::Timeout | Error::Crc) => {
+                    stats::add(&mut self.hc14.stats.timeouts, 1);
+                    return Err(Error::Timeout);
+                }
+                Err(e) => return Err(e),
+            };
+            // 共享信道上其他从站的响应(Answers from other slaves on the shared channel)
+            if buffer[0] != unit {
+                continue;
+            }
+            self.hc14.stats.record_round_trip(u16::from(unit), elapsed);
+            let function = buffer[1];
+            if function == pdu[0] | 0x80 {
+                return Err(Error::ModbusException(buffer[2]));
+            }
+            if function != pdu[0] {
+                return Err(Error::Decode);
+            }
+            return Ok(&buffer[1..len - 2]);
+        }
+    }
+
+    // ---------------------------------------------------------------------------------------------
+    // 从站(Slave)
+
+    /// 等待发给 `unit` 或广播的请求，CRC 错误和发给其他从站的帧会被忽略；
+    /// `timeout_us` 内没有请求时返回 [`Error::Timeout`]
+    ///
+    /// Wait for a request to `unit` or to everyone, frames with a bad CRC or for other slaves are
+    /// ignored; returns [`Error::Timeout`] when no request arrives within `timeout_us`.
+    pub fn receive_request<'b>(
+        &mut self,
+        unit: u8,
+        buffer: &'b mut [u8; MAX_ADU_SIZE],
+        timeout_us: u32,
+    ) -> Result<Request<'b>, Error> {
+        let mut timeout = timeout_us;
+        loop {
+            let (received, waited) = self.receive_adu(buffer, true, timeout);
+            timeout = timeout.saturating_sub(waited);
+            let len = match received {
+                Ok(len) => len,
+                // 损坏的帧同样消耗了等待时间(A corrupted frame used up waiting time as well)
+                Err(Error::Crc) if timeout > 0 => continue,
+                Err(Error::Crc) => return Err(Error::Timeout),
+                Err(e) => return Err(e),
+            };
+            if buffer[0] == unit || buffer[0] == BROADCAST {
+                return Ok(Request {
+                    unit: buffer[0],
+                    pdu: &buffer[1..len - 2],
+                });
+            }
+        }
+    }
+
+    /// 以响应 PDU 应答请求；`unit` 为 [`Request::unit`]，广播请求不应答
+    ///
+    /// Answer a request with a response PDU; `unit` is [`Request::unit`], broadcast requests are not
+    /// answered.
+    pub fn respond(&mut self, unit: u8, pdu: &[u8]) -> Result<(), Error> {
+        if unit == BROADCAST {
+            return Ok(());
+        }
+        self.send_adu(unit, pdu)
+    }
+
+    /// 以异常码应答请求
+    ///
+    /// Answer a request with an exception code.
+    pub fn respond_exception(&mut self, unit: u8, function: u8, code: u8) -> Result<(), Error> {
+        self.respond(unit, &[function | 0x80, code])
+    }
+
+    // ---------------------------------------------------------------------------------------------
+
+    fn send_adu(&mut self, unit: u8, pdu: &[u8]) -> Result<(), Error> {
+        if pdu.len() > MAX_ADU_SIZE - 3 {
+            return Err(Error::MessageTooLarge);
+        }
+        let mut adu = [0u8; MAX_ADU_SIZE];
+        adu[0] = unit;
+        adu[1..=pdu.len()].copy_from_slice(pdu);
+        let crc = crc16(&adu[..=pdu.len()]);
+        adu[pdu.len() + 1..pdu.len() + 3].copy_from_slice(&crc.to_le_bytes());
+        self.hc14.transmit(&adu[..pdu.len() + 3])?;
+        stats::add(&mut self.hc14.stats.frames_sent, 1);
+        Ok(())
+    }
+
+    /// 接收一个完整的帧，返回帧长或错误，以及无论成功与否都已等待的时间(微秒)
+    ///
+    /// Receive one whole frame, returns its length or the error, together with the time waited in
+    /// microseconds either way.
+    fn receive_adu(
+        &mut self,
+        buffer: &mut [u8; MAX_ADU_SIZE],
+        request: bool,
+        timeout_us: u32,
+    ) -> (Result<usize, Error>, u32) {
+        let frame_gap_us = self.hc14.speed().get_packet_delay_ms() * 1000 + self.timing.idle_us;
+        let mut len: usize = 0;
+        let mut waited: u32 = 0;
+        let mut idle: u32 = 0;
+        loop {
+            match self.hc14.serial.read() {
+                Ok(byte) => {
+                    stats::add(&mut self.hc14.stats.bytes_received, 1);
+                    idle = 0;
+                    if len == MAX_ADU_SIZE {
+                        // 不可能是合法的帧，等待静默后重新开始
+                        // Cannot be a valid frame, start over after the silence
+                        continue;
+                    }
+                    buffer[len] = byte;
+                    len += 1;
+                    if expected_len(&buffer[..len], request) == Some(len) {
+                        let result = finish(buffer, len);
+                        return (self.hc14.stats.on_frame(result), waited);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => {
+                    if len == 0 && waited >= timeout_us {
+                        return (Err(Error::Timeout), waited);
+                    }
+                    if len > 0 {
+                        let known = expected_len(&buffer[..len], request).is_some();
+                        if !known && idle >= self.timing.idle_us && finish(buffer, len).is_ok() {
+                            stats::add(&mut self.hc14.stats.frames_received, 1);
+                            return (Ok(len), waited);
+                        }
+                        if idle >= frame_gap_us {
+                            // 后续数据包没有到达，丢弃不完整的帧
+                            // The following packet never came, drop the incomplete frame
+                            stats::add(&mut self.hc14.stats.frame_errors, 1);
+                            len = 0;
+                        }
+                    }
+                    self.hc14.wait_us(self.timing.poll_us);
+                    waited = waited.saturating_add(self.timing.poll_us);
+                    idle = idle.saturating_add(self.timing.poll_us);
+                }
+                Err(nb::Error::Other(_)) => {
+                    stats::add(&mut self.hc14.stats.overruns, 1);
+                    return (Err(Error::Read), waited);
+                }
+            }
+        }
+    }
+}
+
+/// 检查帧的 CRC，返回帧长
+///
+/// Check the CRC of a frame, returns its length.
+fn finish(buffer: &[u8], len: usize) -> Result<usize, Error> {
+    if len < 4 {
+        return Err(Error::Crc);
+    }
+    let crc = u16::from_le_bytes([buffer[len - 2], buffer[len - 1]]);
+    if crc16(&buffer[..len - 2]) != crc {
+        return Err(Error::Crc);
+    }
+    Ok(len)
+}
+
+/// 由功能码推算帧长，未知的功能码或数据不足时返回 `None`
+///
+/// Frame length derived from the function code, `None` for unknown functions or too little data.
+fn expected_len(adu: &[u8], request: bool) -> Option<usize> {
+    let function = *adu.get(1)?;
+    if request {
+        match function {
+            0x01..=0x06 => Some(8),
+            0x0F | 0x10 => adu.get(6).map(|&count| 9 + usize::from(count)),
+            _ => None,
+        }
+    } else {
+        match function {
+            f if f & 0x80 != 0 => Some(5),
+            0x01..=0x04 => adu.get(2).map(|&count| 5 + usize::from(count)),
+            0x05 | 0x06 | 0x0F | 0x10 => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// Modbus CRC-16(多项式 0xA001，初值 0xFFFF)
+///
+/// Modbus CRC-16 (polynomial 0xA001, initial value 0xFFFF).
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_matches_reference_values() {
+        assert_eq!(crc16(b"123456789"), 0x4B37);
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xCDC5);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn finish_checks_the_crc() {
+        let adu = [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD];
+        assert_eq!(finish(&adu, adu.len()).unwrap(), 8);
+        let mut corrupted = adu;
+        corrupted[3] ^= 0x01;
+        assert!(matches!(finish(&corrupted, 8), Err(Error::Crc)));
+        assert!(matches!(finish(&adu, 3), Err(Error::Crc)));
+    }
+
+    #[test]
+    fn length_from_the_function_code() {
+        assert_eq!(expected_len(&[1, 0x03], true), Some(8));
+        assert_eq!(expected_len(&[1, 0x10, 0, 0, 0, 2], true), None);
+        assert_eq!(expected_len(&[1, 0x10, 0, 0, 0, 2, 4], true), Some(13));
+        assert_eq!(expected_len(&[1, 0x03, 4], false), Some(9));
+        assert_eq!(expected_len(&[1, 0x83], false), Some(5));
+        assert_eq!(expected_len(&[1, 0x06], false), Some(8));
+        assert_eq!(expected_len(&[1, 0x2B], true), None);
+        assert_eq!(expected_len(&[1], true), None);
+    }
+
+    /// 按脚本读出字节的串口，`None` 表示暂无数据，写入被丢弃
+    /// (Serial port reading bytes per a script, `None` meaning nothing ready, writes are discarded)
+    struct Script(heapless::Deque<Option<u8>, 64>);
+
+    impl Read<u8> for Script {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            self.0.pop_front().flatten().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    impl Write<u8> for Script {
+        type Error = ();
+
+        fn write(&mut self, _word: u8) -> nb::Result<(), ()> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    struct Pin;
+
+    impl OutputPin for Pin {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    struct NoDelay;
+
+    impl DelayUs<u32> for NoDelay {
+        fn delay_us(&mut self, _us: u32) {}
+    }
+
+    /// 寄存器 0 和 1 的值为 1 和 2 的响应(Response with 1 and 2 in registers 0 and 1)
+    const RESPONSE: [u8; 9] = [0x01, 0x03, 0x04, 0x00, 0x01, 0x00, 0x02, 0x2A, 0x32];
+
+    fn master(frames: &[&[u8]]) -> Hc14<Script, Pin, NoDelay, Normal> {
+        // 首个 `None` 结束发送请求前的清空(The first `None` ends the drain before the request)
+        let mut script = heapless::Deque::new();
+        script.push_back(None).unwrap();
+        for frame in frames {
+            for &byte in frame.iter() {
+                script.push_back(Some(byte)).unwrap();
+            }
+            for _ in 0..20 {
+                script.push_back(None).unwrap();
+            }
+        }
+        Hc14::new(Script(script), Pin, NoDelay).unwrap()
+    }
+
+    #[test]
+    fn master_skips_corrupted_responses() {
+        assert_eq!(crc16(&RESPONSE[..7]).to_le_bytes(), RESPONSE[7..]);
+        let mut corrupted = RESPONSE;
+        corrupted[4] ^= 0x01;
+        let mut hc14 = master(&[&corrupted, &RESPONSE]);
+
+        let mut values = [0u16; 2];
+        hc14.modbus()
+            .read_holding_registers(1, 0, &mut values)
+            .unwrap();
+        assert_eq!(values, [1, 2]);
+        let stats = hc14.stats();
+        assert_eq!((stats.crc_errors, stats.frames_received), (1, 1));
+        assert_eq!(stats.timeouts, 0);
+    }
+
+    #[test]
+    fn master_times_out_on_corrupted_responses_only() {
+        let mut corrupted = RESPONSE;
+        corrupted[8] ^= 0x01;
+        let mut hc14 = master(&[&corrupted]);
+
+        let mut values = [0u16; 2];
+        assert!(matches!(
+            hc14.modbus().read_holding_registers(1, 0, &mut values),
+            Err(Error::Timeout)
+        ));
+        let stats = hc14.stats();
+        assert_eq!((stats.crc_errors, stats.timeouts), (1, 1));
+    }
+}
@@ -0,0 +1,757 @@
+//! smoltcp 网络接口：在无线链路上运行 IPv4/UDP
+//!
+//! smoltcp network interface: IPv4/UDP over the radio link.
+//!
+//! [`RadioDevice`] 为正常模式驱动实现了 `smoltcp::phy::Device`。每个 IP 数据包被拆分为若干
+//! 单数据包帧(见 [`frame`](super::frame))，每个分片带有 2 字节的分片头；接收端按顺序重组，
+//! 任何分片丢失都会导致整个 IP 数据包被丢弃，由上层协议负责重传。
+//! 可选的类 6LoWPAN 头部压缩将 28 字节的 IPv4/UDP 头部压缩到最少 10 字节。
+//!
+//! [`RadioDevice`] implements `smoltcp::phy::Device` for the normal-mode driver. Each IP packet is
+//! split into single-packet frames (see [`frame`](super::frame)), each carrying a 2-byte fragment
+//! header; the receiver reassembles them in order, and a lost fragment drops the whole IP packet,
+//! leaving retransmission to the protocols on top. Optional 6LoWPAN-style header compression shrinks
+//! the 28-byte IPv4/UDP header down to as little as 10 bytes.
+//!
+//! # Example
+//! ```rust
+//! let mut device: RadioDevice<_, _, _, _, 512> = RadioDevice::new(hc14, &parameters);
+//! device.set_header_compression(true);
+//! device.set_address_prefix(Some([10, 0, 0]));
+//!
+//! let config = Config::new(HardwareAddress::Ip);
+//! let mut iface = Interface::new(config, &mut device, Instant::from_millis(0));
+//! iface.update_ip_addrs(|addrs| {
+//!     addrs.push(IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24)).unwrap();
+//! });
+//! loop {
+//!     iface.poll(Instant::from_millis(now_ms()), &mut device, &mut sockets);
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use smoltcp::{
+    phy::{self, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+use super::{
+    frame::{self, MAX_FRAME_SIZE},
+    stats, Hc14, Normal,
+};
+use crate::setting::parameters::Parameters;
+
+/// 分片头长度：标签和序号
+///
+/// Fragment header length: tag and index.
+pub const FRAGMENT_HEADER_LEN: usize = 2;
+
+/// 单个 IP 数据包最多的分片数
+///
+/// Largest number of fragments of a single IP packet.
+pub const MAX_FRAGMENTS: usize = 64;
+
+/// 分片序号：最后一个分片
+const FRAG_LAST: u8 = 0x80;
+/// 分片序号：IP 头部已压缩(仅首个分片有效)
+const FRAG_COMPRESSED: u8 = 0x40;
+/// 分片序号掩码
+const FRAG_INDEX: u8 = 0x3F;
+
+/// 压缩头部：源地址只携带最后一个字节
+const HC_SRC_PREFIX: u8 = 0x01;
+/// 压缩头部：目的地址只携带最后一个字节
+const HC_DST_PREFIX: u8 = 0x02;
+/// 压缩头部：UDP 头部已压缩
+const HC_UDP: u8 = 0x04;
+/// 压缩头部：设置了 DF 位
+const HC_DONT_FRAG: u8 = 0x08;
+/// 压缩头部：携带 IP 标识
+const HC_IDENT: u8 = 0x10;
+
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const PROTOCOL_UDP: u8 = 17;
+/// 压缩头部的最大长度
+const MAX_COMPRESSED_LEN: usize = 18;
+
+/// 在无线链路上实现 `smoltcp::phy::Device` 的适配器，`N` 为收发缓冲区大小(字节)
+///
+/// Adapter implementing `smoltcp::phy::Device` over the radio link, `N` is the size of the
+/// receive and transmit buffers in bytes.
+pub struct RadioDevice<S, P, D, W, const N: usize>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    hc14: Hc14<S, P, D, Normal, W>,
+    mtu: usize,
+    header_compression: bool,
+    prefix: Option<[u8; 3]>,
+    tx: [u8; N],
+    tx_tag: u8,
+    frame: [u8; MAX_FRAME_SIZE],
+    frame_len: usize,
+    frame_overflow: bool,
+    rx: [u8; N],
+    rx_len: usize,
+    rx_tag: u8,
+    rx_next: Option<u8>,
+    rx_compressed: bool,
+    rx_ready: bool,
+}
+
+impl<S, P, D, W, const N: usize> RadioDevice<S, P, D, W, N>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 由正常模式驱动和模块当前的参数构建网络设备
+    ///
+    /// Build a network device from the normal-mode driver and the module's current parameters.
+    pub fn new(hc14: Hc14<S, P, D, Normal, W>, parameters: &Parameters) -> Self {
+        let mut device = Self {
+            hc14,
+            mtu: 0,
+            header_compression: false,
+            prefix: None,
+            tx: [0; N],
+            tx_tag: 0,
+            frame: [0; MAX_FRAME_SIZE],
+            frame_len: 0,
+            frame_overflow: false,
+            rx: [0; N],
+            rx_len: 0,
+            rx_tag: 0,
+            rx_next: None,
+            rx_compressed: false,
+            rx_ready: false,
+        };
+        device.set_parameters(parameters);
+        device
+    }
+
+    /// 释放正常模式驱动
+    ///
+    /// Release the normal-mode driver.
+    pub fn release(self) -> Hc14<S, P, D, Normal, W> {
+        self.hc14
+    }
+
+    /// 模块参数改变后调用，重新计算 MTU 和由速率等级得出的发送节奏
+    ///
+    /// Call after the module's parameters changed, recomputes the MTU and pacing derived from the
+    /// rate class.
+    pub fn set_parameters(&mut self, parameters: &Parameters) {
+        self.hc14.set_speed(parameters.speed);
+        let fragment = self.hc14.max_frame_payload() - FRAGMENT_HEADER_LEN;
+        self.mtu = N.min(fragment * MAX_FRAGMENTS);
+    }
+
+    /// 当前的 MTU：缓冲区大小与最多分片所能承载的字节数中的较小值
+    ///
+    /// Current MTU: the smaller of the buffer size and what the largest number of fragments can carry.
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// 发送时是否压缩 IPv4/UDP 头部，接收端总能解压
+    ///
+    /// Whether to compress IPv4/UDP headers when sending, the receiver always decompresses.
+    pub fn set_header_compression(&mut self, enabled: bool) {
+        self.header_compression = enabled;
+    }
+
+    /// 网络前缀(例如 `10.0.0.0/24` 的 `[10, 0, 0]`)，该前缀内的地址只传输最后一个字节，
+    /// 所有节点必须使用相同的前缀
+    ///
+    /// Network prefix (e.g. `[10, 0, 0]` for `10.0.0.0/24`), addresses inside it are sent as their
+    /// last byte only; every node must use the same prefix.
+    pub fn set_address_prefix(&mut self, prefix: Option<[u8; 3]>) {
+        self.prefix = prefix;
+    }
+
+    /// 读出串口中所有可用的字节，返回是否有完整的 IP 数据包
+    ///
+    /// Drain every byte the serial port has ready, returns whether a whole IP packet is available.
+    fn poll_serial(&mut self) -> bool {
+        while !self.rx_ready {
+            let read = self.hc14.serial.read();
+            if read.is_ok() {
+                stats::add(&mut self.hc14.stats.bytes_received, 1);
+            }
+            match read {
+                Ok(0) => {
+                    if self.frame_overflow {
+                        stats::add(&mut self.hc14.stats.frame_errors, 1);
+                    } else if self.frame_len > 0 {
+                        self.on_frame();
+                    }
+                    self.frame_len = 0;
+                    self.frame_overflow = false;
+                }
+                Ok(byte) => match self.frame.get_mut(self.frame_len) {
+                    Some(slot) => {
+                        *slot = byte;
+                        self.frame_len += 1;
+                    }
+                    None => self.frame_overflow = true,
+                },
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    stats::add(&mut self.hc14.stats.overruns, 1);
+                    self.frame_overflow = true;
+                }
+            }
+        }
+        self.rx_ready
+    }
+
+    /// 处理一个完整的帧(不含结束符)
+    ///
+    /// Handle one whole frame (without the delimiter).
+    fn on_frame(&mut self) {
+        let mut raw = [0u8; MAX_FRAME_SIZE];
+        let mut body = [0u8; MAX_FRAME_SIZE];
+        let decoded = frame::cobs_decode(&self.frame[..self.frame_len], &mut raw)
+            .and_then(|n| frame::decode_payload(raw[0], &raw[1..n.max(1)], &mut body));
+        let len = match self.hc14.stats.on_frame(decoded) {
+            Ok(len) if len >= FRAGMENT_HEADER_LEN => len,
+            _ => return,
+        };
+        let (tag, info) = (body[0], body[1]);
+        let index = info & FRAG_INDEX;
+        if index == 0 {
+            self.rx_tag = tag;
+            self.rx_len = 0;
+        } else if self.rx_next != Some(index) || self.rx_tag != tag {
+            // 中间的分片丢失，丢弃整个数据包
+            // A fragment in between was lost, drop the whole packet
+            self.rx_next = None;
+            return;
+        }
+        let data = &body[FRAGMENT_HEADER_LEN..len];
+        match self.rx.get_mut(self.rx_len..self.rx_len + data.len()) {
+            Some(slot) => slot.copy_from_slice(data),
+            None => {
+                self.rx_next = None;
+                return;
+            }
+        }
+        self.rx_len += data.len();
+        if index == 0 {
+            self.rx_compressed = info & FRAG_COMPRESSED != 0;
+        }
+        if info & FRAG_LAST != 0 {
+            self.rx_next = None;
+            self.rx_ready = !self.rx_compressed || self.expand_header();
+        } else {
+            self.rx_next = Some(index + 1);
+        }
+    }
+
+    /// 还原已重组数据包的压缩头部，失败时丢弃该数据包
+    ///
+    /// Restore the compressed header of the reassembled packet, drops the packet on failure.
+    fn expand_header(&mut self) -> bool {
+        match decompress_header(&mut self.rx, self.rx_len, self.prefix) {
+            Some(len) => {
+                self.rx_len = len;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<S, P, D, W, const N: usize> phy::Device for RadioDevice<S, P, D, W, N>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    type RxToken<'a>
+        = RadioRxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = RadioTxToken<'a, S, P, D, W, N>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        if !self.poll_serial() {
+            return None;
+        }
+        Some((
+            RadioRxToken {
+                packet: &self.rx[..self.rx_len],
+                ready: &mut self.rx_ready,
+            },
+            RadioTxToken {
+                hc14: &mut self.hc14,
+                buffer: &mut self.tx,
+                tag: &mut self.tx_tag,
+                header_compression: self.header_compression,
+                prefix: self.prefix,
+            },
+        ))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(RadioTxToken {
+            hc14: &mut self.hc14,
+            buffer: &mut self.tx,
+            tag: &mut self.tx_tag,
+            header_compression: self.header_compression,
+            prefix: self.prefix,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ip;
+        caps.max_transmission_unit = self.mtu;
+        caps.max_burst_size = Some(1);
+        caps
+    }
+}
+
+/// 接收令牌：持有一个已重组的 IP 数据包
+///
+/// Receive token: holds one reassembled IP packet.
+pub struct RadioRxToken<'a> {
+    packet: &'a [u8],
+    ready: &'a mut bool,
+}
+
+impl<'a> phy::RxToken for RadioRxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let result = f(self.packet);
+        *self.ready = false;
+        result
+    }
+}
+
+/// 发送令牌：将 IP 数据包分片后通过无线发送
+///
+/// Transmit token: fragments an IP packet and sends it over the radio.
+pub struct RadioTxToken<'a, S, P, D, W, const N: usize>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    hc14: &'a mut Hc14<S, P, D, Normal, W>,
+    buffer: &'a mut [u8; N],
+    tag: &'a mut u8,
+    header_compression: bool,
+    prefix: Option<[u8; 3]>,
+}
+
+impl<'a, S, P, D, W, const N: usize> phy::TxToken for RadioTxToken<'a, S, P, D, W, N>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let result = f(&mut self.buffer[..len]);
+        let mut start: usize = 0;
+        let mut flags: u8 = 0;
+        if self.header_compression {
+            let mut header = [0u8; MAX_COMPRESSED_LEN];
+            if let Some((n, consumed)) =
+                compress_header(&self.buffer[..len], self.prefix, &mut header)
+            {
+                // 压缩头部紧挨负载放置，从而无需复制负载
+                // Place the compressed header right before the payload so the payload is not copied
+                start = consumed - n;
+                self.buffer[start..consumed].copy_from_slice(&header[..n]);
+                flags = FRAG_COMPRESSED;
+            }
+        }
+        // 无线链路不可靠，发送失败与空中丢包同等对待
+        // The radio link is unreliable, a failed send is treated like a packet lost on air
+        let _ = send_fragments(self.hc14, *self.tag, flags, &self.buffer[start..len]);
+        *self.tag = self.tag.wrapping_add(1);
+        result
+    }
+}
+
+/// 将数据包拆分为帧发送
+///
+/// Send a packet split into frames.
+fn send_fragments<S, P, D, W>(
+    hc14: &mut Hc14<S, P, D, Normal, W>,
+    tag: u8,
+    flags: u8,
+    packet: &[u8],
+) -> Result<(), crate::Error>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    let size = hc14.max_frame_payload() - FRAGMENT_HEADER_LEN;
+    let count = packet.len().div_ceil(size);
+    let mut body = [0u8; MAX_FRAME_SIZE];
+    for (index, chunk) in packet.chunks(size).enumerate() {
+        let mut info = index as u8 & FRAG_INDEX;
+        if index == 0 {
+            info |= flags;
+        }
+        if index + 1 == count {
+            info |= FRAG_LAST;
+        }
+        body[0] = tag;
+        body[1] = info;
+        body[FRAGMENT_HEADER_LEN..FRAGMENT_HEADER_LEN + chunk.len()].copy_from_slice(chunk);
+        hc14.send_frame(&body[..FRAGMENT_HEADER_LEN + chunk.len()])?;
+    }
+    Ok(())
+}
+
+/// 压缩 IPv4(以及 UDP)头部，返回压缩后的长度和被替换的原始头部长度；
+/// 带选项、分片或非零 TOS 的数据包不压缩
+///
+/// Compress the IPv4 (and UDP) header, returns the compressed length and the length of the original
+/// header it replaces; packets with options, fragmentation or a non-zero TOS are left alone.
+fn compress_header(
+    packet: &[u8],
+    prefix: Option<[u8; 3]>,
+    out: &mut [u8; MAX_COMPRESSED_LEN],
+) -> Option<(usize, usize)> {
+    if packet.len() < IPV4_HEADER_LEN || packet[0] != 0x45 || packet[1] != 0 {
+        return None;
+    }
+    if usize::from(u16::from_be_bytes([packet[2], packet[3]])) != packet.len() {
+        return None;
+    }
+    // 只允许 DF 位，不允许 MF 和分片偏移
+    // Only DF may be set, no MF and no fragment offset
+    if packet[6] & !0x40 != 0 || packet[7] != 0 {
+        return None;
+    }
+    let udp = packet[9] == PROTOCOL_UDP
+        && packet.len() >= IPV4_HEADER_LEN + UDP_HEADER_LEN
+        && usize::from(u16::from_be_bytes([packet[24], packet[25]]))
+            == packet.len() - IPV4_HEADER_LEN;
+
+    let mut hc: u8 = 0;
+    let mut n: usize = 1;
+    out[n] = packet[8];
+    n += 1;
+    if packet[6] & 0x40 != 0 {
+        hc |= HC_DONT_FRAG;
+    }
+    if packet[4..6] != [0, 0] {
+        hc |= HC_IDENT;
+        out[n..n + 2].copy_from_slice(&packet[4..6]);
+        n += 2;
+    }
+    if udp {
+        hc |= HC_UDP;
+    } else {
+        out[n] = packet[9];
+        n += 1;
+    }
+    for (at, flag) in [(12, HC_SRC_PREFIX), (16, HC_DST_PREFIX)] {
+        let address = &packet[at..at + 4];
+        if prefix == Some([address[0], address[1], address[2]]) {
+            hc |= flag;
+            out[n] = address[3];
+            n += 1;
+        } else {
+            out[n..n + 4].copy_from_slice(address);
+            n += 4;
+        }
+    }
+    let mut consumed = IPV4_HEADER_LEN;
+    if udp {
+        // 端口和校验和；UDP 长度由数据包长度得出
+        // Ports and checksum; the UDP length follows from the packet length
+        out[n..n + 4].copy_from_slice(&packet[20..24]);
+        out[n + 4..n + 6].copy_from_slice(&packet[26..28]);
+        n += 6;
+        consumed += UDP_HEADER_LEN;
+    }
+    out[0] = hc;
+    Some((n, consumed))
+}
+
+/// 就地还原 `packet[..len]` 的压缩头部，返回完整数据包的长度
+///
+/// Restore the compressed header of `packet[..len]` in place, returns the length of the full packet.
+fn decompress_header(packet: &mut [u8], len: usize, prefix: Option<[u8; 3]>) -> Option<usize> {
+    let src = packet.get(..len)?;
+    let hc = *src.first()?;
+    let mut pos: usize = 1;
+    let mut header = [0u8; IPV4_HEADER_LEN + UDP_HEADER_LEN];
+    header[0] = 0x45;
+    header[8] = field(src, &mut pos, 1)?[0];
+    if hc & HC_IDENT != 0 {
+        header[4..6].copy_from_slice(field(src, &mut pos, 2)?);
+    }
+    if hc & HC_DONT_FRAG != 0 {
+        header[6] = 0x40;
+    }
+    header[9] = if hc & HC_UDP != 0 {
+        PROTOCOL_UDP
+    } else {
+        field(src, &mut pos, 1)?[0]
+    };
+    for (at, flag) in [(12, HC_SRC_PREFIX), (16, HC_DST_PREFIX)] {
+        if hc & flag != 0 {
+            header[at..at + 3].copy_from_slice(&prefix?);
+            header[at + 3] = field(src, &mut pos, 1)?[0];
+        } else {
+            header[at..at + 4].copy_from_slice(field(src, &mut pos, 4)?);
+        }
+    }
+    let mut header_len = IPV4_HEADER_LEN;
+    if hc & HC_UDP != 0 {
+        header[20..24].copy_from_slice(field(src, &mut pos, 4)?);
+        header[26..28].copy_from_slice(field(src, &mut pos, 2)?);
+        header_len += UDP_HEADER_LEN;
+    }
+
+    let total = len - pos + header_len;
+    if total > packet.len() || total > usize::from(u16::MAX) {
+        return None;
+    }
+    header[2..4].copy_from_slice(&(total as u16).to_be_bytes());
+    if hc & HC_UDP != 0 {
+        header[24..26].copy_from_slice(&((total - IPV4_HEADER_LEN) as u16).to_be_bytes());
+    }
+    let checksum = ipv4_checksum(&header[..IPV4_HEADER_LEN]);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    packet.copy_within(pos..len, header_len);
+    packet[..header_len].copy_from_slice(&header[..header_len]);
+    Some(total)
+}
+
+/// 从压缩头部中读取 `count` 字节
+///
+/// Read `count` bytes from a compressed header.
+fn field<'a>(src: &'a [u8], pos: &mut usize, count: usize) -> Option<&'a [u8]> {
+    let bytes = src.get(*pos..*pos + count)?;
+    *pos += count;
+    Some(bytes)
+}
+
+/// IPv4 头部校验和
+///
+/// IPv4 header checksum.
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 带正确校验和的 IPv4/UDP 数据包(An IPv4/UDP packet with a valid checksum)
+    fn udp_packet(ident: u16, payload: &[u8], out: &mut [u8]) -> usize {
+        let total = IPV4_HEADER_LEN + UDP_HEADER_LEN + payload.len();
+        let packet = &mut out[..total];
+        packet.fill(0);
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&(total as u16).to_be_bytes());
+        packet[4..6].copy_from_slice(&ident.to_be_bytes());
+        packet[6] = 0x40;
+        packet[8] = 64;
+        packet[9] = PROTOCOL_UDP;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        let checksum = ipv4_checksum(&packet[..IPV4_HEADER_LEN]);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        packet[20..22].copy_from_slice(&5683u16.to_be_bytes());
+        packet[22..24].copy_from_slice(&5684u16.to_be_bytes());
+        packet[24..26].copy_from_slice(&((UDP_HEADER_LEN + payload.len()) as u16).to_be_bytes());
+        packet[26..28].copy_from_slice(&[0xAB, 0xCD]);
+        packet[28..].copy_from_slice(payload);
+        total
+    }
+
+    /// 压缩后再就地解压，返回压缩头部长度和还原的数据包
+    /// (Compress, then decompress in place, returns the compressed length and the restored packet)
+    fn round_trip(
+        packet: &[u8],
+        prefix: Option<[u8; 3]>,
+        out: &mut [u8; 128],
+    ) -> Option<(usize, usize)> {
+        let mut header = [0u8; MAX_COMPRESSED_LEN];
+        let (n, consumed) = compress_header(packet, prefix, &mut header)?;
+        out[..n].copy_from_slice(&header[..n]);
+        out[n..n + packet.len() - consumed].copy_from_slice(&packet[consumed..]);
+        let len = decompress_header(out, n + packet.len() - consumed, prefix)?;
+        Some((n, len))
+    }
+
+    #[test]
+    fn udp_header_round_trip() {
+        let mut packet = [0u8; 64];
+        let len = udp_packet(0x1234, b"hello", &mut packet);
+        let mut out = [0u8; 128];
+
+        // 前缀内的地址只携带最后一个字节(Addresses inside the prefix carry their last byte only)
+        let (n, restored) = round_trip(&packet[..len], Some([10, 0, 0]), &mut out).unwrap();
+        assert_eq!(n, 12);
+        assert_eq!(&out[..restored], &packet[..len]);
+
+        let (n, restored) = round_trip(&packet[..len], None, &mut out).unwrap();
+        assert_eq!(n, 18);
+        assert_eq!(&out[..restored], &packet[..len]);
+
+        // 没有 IP 标识时压缩到最少的 10 字节(Down to 10 bytes without an IP identification)
+        let len = udp_packet(0, b"hello", &mut packet);
+        let (n, restored) = round_trip(&packet[..len], Some([10, 0, 0]), &mut out).unwrap();
+        assert_eq!(n, 10);
+        assert_eq!(&out[..restored], &packet[..len]);
+    }
+
+    #[test]
+    fn non_udp_header_round_trip() {
+        let mut packet = [0u8; 64];
+        let len = udp_packet(7, b"ping", &mut packet);
+        packet[9] = 1;
+        packet[10..12].fill(0);
+        let checksum = ipv4_checksum(&packet[..IPV4_HEADER_LEN]);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        let mut out = [0u8; 128];
+
+        let (n, restored) = round_trip(&packet[..len], Some([10, 0, 0]), &mut out).unwrap();
+        assert_eq!(n, 7);
+        assert_eq!(&out[..restored], &packet[..len]);
+    }
+
+    #[test]
+    fn unsupported_headers_are_left_alone() {
+        let mut packet = [0u8; 64];
+        let len = udp_packet(1, b"hello", &mut packet);
+        let mut header = [0u8; MAX_COMPRESSED_LEN];
+
+        let mut options = packet;
+        options[0] = 0x46;
+        assert!(compress_header(&options[..len], None, &mut header).is_none());
+        let mut fragmented = packet;
+        fragmented[6] = 0x20;
+        assert!(compress_header(&fragmented[..len], None, &mut header).is_none());
+        assert!(compress_header(&packet[..len - 1], None, &mut header).is_none());
+
+        // 压缩时使用了前缀，解压端没有前缀时丢弃(Dropped when the receiver lacks the sender's prefix)
+        let (n, _) = compress_header(&packet[..len], Some([10, 0, 0]), &mut header).unwrap();
+        let mut out = [0u8; 128];
+        out[..n].copy_from_slice(&header[..n]);
+        assert!(decompress_header(&mut out, n, None).is_none());
+        assert!(decompress_header(&mut out, 1, Some([10, 0, 0])).is_none());
+    }
+
+    #[cfg(feature = "std")]
+    mod sim {
+        use super::*;
+        use crate::{
+            driver::power::NoPowerPin,
+            sim::{SimConfig, SimDelay, SimPin, SimSerial, Simulator},
+        };
+        use phy::{Device, RxToken, TxToken};
+
+        type SimDevice = RadioDevice<SimSerial, SimPin, SimDelay, NoPowerPin, 512>;
+
+        fn pair(sim: &mut Simulator) -> (SimDevice, SimDevice) {
+            let parameters = Parameters::default();
+            let [a, b] = [sim.add_node(parameters), sim.add_node(parameters)];
+            let hc14_a = Hc14::new(a.serial, a.key, a.delay).unwrap();
+            let hc14_b = Hc14::new(b.serial, b.key, b.delay).unwrap();
+            (
+                RadioDevice::new(hc14_a, &parameters),
+                RadioDevice::new(hc14_b, &parameters),
+            )
+        }
+
+        fn receive(device: &mut SimDevice) -> Option<Vec<u8>> {
+            let (rx, _) = device.receive(Instant::from_millis(0))?;
+            Some(rx.consume(|packet| packet.to_vec()))
+        }
+
+        fn send(device: &mut SimDevice, packet: &[u8]) {
+            let tx = device.transmit(Instant::from_millis(0)).unwrap();
+            tx.consume(packet.len(), |buffer| buffer.copy_from_slice(packet));
+        }
+
+        #[test]
+        fn fragmented_packets_are_reassembled() {
+            let mut sim = Simulator::new(SimConfig::default());
+            let (mut device_a, mut device_b) = pair(&mut sim);
+            device_a.set_header_compression(true);
+            device_a.set_address_prefix(Some([10, 0, 0]));
+            device_b.set_address_prefix(Some([10, 0, 0]));
+            assert_eq!(device_a.mtu(), 512);
+
+            let mut payload = [0u8; 300];
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            let mut packet = [0u8; 512];
+            let len = udp_packet(42, &payload, &mut packet);
+            send(&mut device_a, &packet[..len]);
+            assert!(device_a.hc14.stats.frames_sent > 1);
+            sim.advance_us(30_000_000);
+
+            assert_eq!(receive(&mut device_b).unwrap(), &packet[..len]);
+            assert!(receive(&mut device_b).is_none());
+        }
+
+        #[test]
+        fn lost_and_out_of_order_fragments_drop_the_packet() {
+            let mut sim = Simulator::new(SimConfig::default());
+            let (device_a, mut device_b) = pair(&mut sim);
+            let mut hc14_a = device_a.release();
+
+            // 中间的分片丢失(A fragment in between is lost)
+            hc14_a.send_frame(&[1, 0, b'a']).unwrap();
+            hc14_a.send_frame(&[1, 2 | FRAG_LAST, b'c']).unwrap();
+            // 分片乱序到达(Fragments arrive out of order)
+            hc14_a.send_frame(&[2, 1, b'b']).unwrap();
+            hc14_a.send_frame(&[2, 0, b'a']).unwrap();
+            hc14_a.send_frame(&[2, 2 | FRAG_LAST, b'c']).unwrap();
+            // 另一个数据包的分片混入(A fragment of another packet interleaves)
+            hc14_a.send_frame(&[3, 0, b'a']).unwrap();
+            hc14_a.send_frame(&[4, 1 | FRAG_LAST, b'b']).unwrap();
+            // 完整的数据包(A whole packet)
+            hc14_a.send_frame(&[5, 0, b'o']).unwrap();
+            hc14_a.send_frame(&[5, 1, b'k']).unwrap();
+            hc14_a.send_frame(&[5, 2 | FRAG_LAST, b'!']).unwrap();
+            sim.advance_us(60_000_000);
+
+            assert_eq!(receive(&mut device_b).unwrap(), b"ok!");
+            assert!(receive(&mut device_b).is_none());
+        }
+    }
+}
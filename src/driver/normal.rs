@@ -18,31 +18,62 @@ where
                 serial,
                 key_pin,
                 delay,
+                speed: Speed::default(),
+                pacing: Pacing::default(),
+                stats: LinkStats::default(),
+                mode_timing: ModeTiming::default(),
+                power_pin: NoPowerPin,
+                has_power_pin: false,
+                sleep_method: SleepMethod::default(),
+                duty_cycle: None,
+                band_plan: BandPlan::DEFAULT,
                 mode: PhantomData::<Normal>,
             }),
             Err(_) => Err(nb::Error::Other(())),
         }
     }
+}
 
-    /// ! **"正常模式"** 切换到: "**AT配置模式**"
-    /// - **"Normal Mode "** Switch to: "**AT Configuration Mode**"
-    pub fn into_configuration_mode(mut self) -> Result<Hc14<S, P, D, Configuration>, ()> {
-        let at_on = self.key_pin.set_low();
-        self.delay.delay_us(100_000_u32); // delay 0.1s
-
-        match at_on {
-            Ok(_) => Ok(Hc14 {
-                serial: self.serial,
-                key_pin: self.key_pin,
-                delay: self.delay,
-                mode: PhantomData::<Configuration>,
-            }),
-            Err(_) => Err(nb::Error::Other(())),
+/// Normal mode
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// ! **"正常模式"** 切换到: "**AT配置模式**"，失败时交还正常模式的驱动
+    /// - **"Normal Mode "** Switch to: "**AT Configuration Mode**", handing back the normal-mode driver on failure
+    #[allow(clippy::result_large_err)] // 错误中带着整个驱动(The error carries the whole driver)
+    pub fn into_configuration_mode(
+        mut self,
+    ) -> core::result::Result<Hc14<S, P, D, Configuration, W>, TransitionError<Self>> {
+        if self.key_pin.set_low().is_err() {
+            return Err(TransitionError {
+                hc14: self,
+                error: crate::Error::Pin,
+            });
         }
+        let timing = self.mode_timing;
+        self.delay.delay_us(timing.settle_us);
+        // 正常模式下收到的数据会干扰应答(Data received in normal mode would garble the answer)
+        self.discard_input();
+        for _ in 0..=timing.retries {
+            if self.at_probe() {
+                return Ok(self.into_mode());
+            }
+        }
+        // 模块没有应答，回到正常模式(The module never answered, back to normal mode)
+        let error = match self.key_pin.set_high() {
+            Ok(_) => crate::Error::NoResponse,
+            Err(_) => crate::Error::Pin,
+        };
+        self.delay.delay_us(timing.settle_us);
+        Err(TransitionError { hc14: self, error })
     }
 
-    /// 释放所含资源
-    /// - Release of included resources
+    /// 释放所含资源，电源控制引脚见 [`Hc14::release_with_power_pin`]
+    /// - Release of included resources, see [`Hc14::release_with_power_pin`] for the power-control pin
     pub fn release(self) -> (S, P, D) {
         (self.serial, self.key_pin, self.delay)
     }
@@ -53,23 +84,22 @@ where
         &mut self,
         buffer: &'a mut [u8],
     ) -> Result<&'a [u8], Error<crate::Error>> {
-        self.delay.delay_us(100_000_u32); // delay 0.1s
-        let mut count: usize = 0;
-        for v in buffer.iter_mut() {
-            if let Ok(ch) = block!(self.serial.read()) {
-                *v = ch;
-                count += 1;
-                if ch == b'\n' {
-                    break;
-                }
-            }
-        }
+        let count = receive::read_buffer(&mut self.serial, &mut self.stats, buffer);
+        Ok(&buffer[..count])
+    }
+
+    /// **[Normal]**: 不阻塞地读取串口当前可用的所有字节，适用于由中断填充的 [`buffered::RxConsumer`]
+    /// - Reads every byte currently available without blocking, suited to an interrupt-filled [`buffered::RxConsumer`].
+    pub fn read_available<'a>(&mut self, buffer: &'a mut [u8]) -> Result<&'a [u8], crate::Error> {
+        let count = receive::read_available(&mut self.serial, &mut self.stats, buffer)?;
         Ok(&buffer[..count])
     }
 
-    /// 发送字节 send byte (computing)
+    /// 发送字节，按照 [`Pacing`] 在数据包已满时等待；单独发送的字节占用一个完整的数据包
+    /// - Send byte, waiting per [`Pacing`] once a packet is full; a byte sent on its own takes a
+    ///   whole packet
     pub fn send_byte(&mut self, word: u8) -> Result<bool, ()> {
-        match block!(self.serial.write(word)) {
+        match self.transmit(&[word]) {
             Ok(_) => Ok(true),
             Err(_) => Err(nb::Error::Other(())),
         }
@@ -77,23 +107,47 @@ where
 
     /// 发送字符串(Send String)
     pub fn send_string(&mut self, words: &str) {
-        for word in words.as_bytes() {
-            if *word == b'\0' {
-                break;
-            }
-            self.send_byte(*word).unwrap();
-        }
+        let bytes = words.as_bytes();
+        let end = bytes
+            .iter()
+            .position(|b| *b == b'\0')
+            .unwrap_or(bytes.len());
+        self.send_buffer(&bytes[..end]).unwrap();
     }
 
-    /// **[Normal]**: 将整个缓冲区写入串行端口
-    ///  - Write the entire buffer to the serial port
+    /// **[Normal]**: 将整个缓冲区写入串行端口，写入出错(包括 [`crate::Error::DutyCycle`])时返回错误
+    ///  - Write the entire buffer to the serial port, returning the error (including
+    ///    [`crate::Error::DutyCycle`]) when a write fails
     pub fn send_buffer(&mut self, buffer: &[u8]) -> Result<bool, Error<crate::Error>> {
-        self.delay.delay_us(100_000_u32); // delay 0.1s
-        let mut verify: bool = false;
-        for ch in buffer {
-            verify = self.send_byte(*ch).is_ok();
+        self.transmit(buffer).map_err(Error::Other)?;
+        Ok(!buffer.is_empty())
+    }
+
+    /// 作为一次发送写入 `bytes`：先为整段数据向占空比限制器申请发射时间，再逐字节写入
+    ///
+    /// Write `bytes` as one transmission: the duty-cycle limiter admits the whole of it first, then
+    /// the bytes are written one by one.
+    pub(crate) fn transmit(&mut self, bytes: &[u8]) -> core::result::Result<(), crate::Error> {
+        self.admit(bytes.len())?;
+        for byte in bytes {
+            self.write_byte(*byte)?;
         }
-        Ok(verify)
+        Ok(())
+    }
+
+    /// 按照 [`Pacing`] 写入一个字节，不经过占空比限制器；调用者需要先为整次发送调用 `admit`
+    ///
+    /// Write one byte per [`Pacing`], bypassing the duty-cycle limiter; callers `admit` the whole
+    /// transmission first.
+    pub(crate) fn write_byte(&mut self, word: u8) -> core::result::Result<(), crate::Error> {
+        pacing::write_paced(
+            &mut self.serial,
+            &mut self.pacing,
+            &mut self.duty_cycle,
+            &mut self.delay,
+            &mut self.stats,
+            word,
+        )
     }
 
     /// 发送无符号数字
@@ -108,26 +162,13 @@ where
             }
         }
 
+        let mut digits = [0u8; 10];
         for i in 0..length {
             let v: u32 = number / 10_u32.pow(length - i - 1) % 10 + 48_u32;
-            self.send_byte(v as u8).unwrap();
+            digits[i as usize] = v as u8;
         }
+        self.send_buffer(&digits[..length as usize]).unwrap();
     }
-
-    // 接收字符串, 最大长度: 40
-    // Receive String, Maximum Length: 40
-    // pub fn read_string_40(&mut self) -> String<40> {
-    //     let mut result: String<40> = String::new();
-    //     loop {
-    //         if let Ok(ch) = block!(self.serial.read()) {
-    //             result.push(ch as char).unwrap();
-    //             if ch == b'\n' {
-    //                 break;
-    //             }
-    //         }
-    //     }
-    //     result
-    // }
 }
 
 /// 将模块返回的参数格式化为i32
@@ -0,0 +1,321 @@
+//! 数据包发送节奏控制
+//!
+//! Packet pacing.
+//!
+//! HC-14 每个数据包最多容纳 [`Speed::get_max_bytes_size`] 字节，发送一个满数据包需要
+//! [`Speed::get_packet_delay_ms`] 毫秒。只有在未发出的字节填满一个数据包时才需要等待，
+//! 因此短消息可以连续发送，而不必每次固定延迟。
+//!
+//! A HC-14 packet holds at most [`Speed::get_max_bytes_size`] bytes and a full packet takes
+//! [`Speed::get_packet_delay_ms`] milliseconds on air. Waiting is only needed once the bytes not yet
+//! on air fill a packet, so short messages go out back to back instead of after a fixed delay.
+//!
+//! 模块按 [`Speed::get_packet_delay_ms`] 的速度清空缓冲区。驱动自己的等待会清空未发出的字节，
+//! 其余时间需要调用 [`Hc14::tick_pacing`] 告知；不调用时发送节奏只会偏保守。
+//!
+//! The module drains its buffer at the pace of [`Speed::get_packet_delay_ms`]. The driver's own
+//! waits clear the bytes not yet on air, other time has to be reported through
+//! [`Hc14::tick_pacing`]; without it the pacing merely errs on the safe side.
+//!
+//! # Example
+//! ```rust
+//! let mut hc14 = Hc14::new(serial, key, delay).unwrap();
+//! // 与模块的无线速率保持一致(Keep in line with the module's wireless speed)
+//! hc14.set_speed(Speed::S8);
+//! // 或自定义：每 64 字节等待 500 ms，之后 set_speed 不再覆盖它
+//! // (Or custom: wait 500 ms every 64 bytes, set_speed no longer replaces it afterwards)
+//! hc14.set_pacing(Pacing::new(64, 500_000));
+//! // 在定时器中断中(In a timer interrupt)
+//! hc14.tick_pacing(100);
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use nb::block;
+
+use super::{
+    duty::DutyCycle,
+    stats::{self, LinkStats},
+    Hc14,
+};
+use crate::{setting::speed::Speed, Error};
+
+/// 发送节奏策略：每发出 `packet_bytes` 字节后等待 `packet_interval_us` 微秒
+///
+/// Pacing policy: wait `packet_interval_us` microseconds after every `packet_bytes` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacing {
+    packet_bytes: usize,
+    packet_interval_us: u32,
+    pending: usize,
+    follows_speed: bool,
+}
+
+impl Pacing {
+    /// 构建自定义的发送节奏，[`Hc14::set_speed`] 不会覆盖它
+    ///
+    /// Build a custom pacing policy, [`Hc14::set_speed`] leaves it in place.
+    pub const fn new(packet_bytes: usize, packet_interval_us: u32) -> Self {
+        Self {
+            packet_bytes,
+            packet_interval_us,
+            pending: 0,
+            follows_speed: false,
+        }
+    }
+
+    /// 不做任何等待
+    ///
+    /// Never wait.
+    pub const fn disabled() -> Self {
+        Self::new(usize::MAX, 0)
+    }
+
+    /// 由速率等级的数据包容量和发送时间得出的发送节奏
+    ///
+    /// Pacing derived from the packet capacity and packet time of a rate class.
+    pub fn from_speed(speed: &Speed) -> Self {
+        Self {
+            follows_speed: true,
+            ..Self::new(
+                speed.get_max_bytes_size(),
+                speed.get_packet_delay_ms() * 1000,
+            )
+        }
+    }
+
+    /// 是否由速率等级得出，此时 [`Hc14::set_speed`] 会按新的等级更新它
+    ///
+    /// Whether derived from a rate class, in which case [`Hc14::set_speed`] updates it to the new
+    /// class.
+    pub fn follows_speed(&self) -> bool {
+        self.follows_speed
+    }
+
+    /// 单个数据包的字节数
+    ///
+    /// Bytes per packet.
+    pub fn packet_bytes(&self) -> usize {
+        self.packet_bytes
+    }
+
+    /// 满数据包之间的等待时间(微秒)
+    ///
+    /// Wait between full packets in microseconds.
+    pub fn packet_interval_us(&self) -> u32 {
+        self.packet_interval_us
+    }
+
+    /// 尚未确认发出的字节数
+    ///
+    /// Bytes not yet known to be on air.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// 在写入一个字节之前调用，返回写入前需要等待的微秒数
+    ///
+    /// Call before writing one byte, returns the microseconds to wait before the write.
+    pub fn before_write(&mut self) -> u32 {
+        let mut wait: u32 = 0;
+        if self.pending >= self.packet_bytes {
+            wait = self.packet_interval_us;
+            self.pending = 0;
+        }
+        self.pending += 1;
+        wait
+    }
+
+    /// 时间过去了 `us` 微秒，模块在此期间按发送节奏发出了缓冲区中的字节
+    ///
+    /// `us` microseconds have passed, during which the module put buffered bytes on air at the
+    /// paced rate.
+    pub fn advance_us(&mut self, us: u32) {
+        if self.packet_interval_us == 0 {
+            self.pending = 0;
+            return;
+        }
+        let sent = u64::from(us) * self.packet_bytes as u64 / u64::from(self.packet_interval_us);
+        self.pending = self
+            .pending
+            .saturating_sub(sent.min(usize::MAX as u64) as usize);
+    }
+
+    /// 已知模块空闲(例如已经过了足够长的时间)，清除未发出的字节数
+    ///
+    /// The module is known to be idle (e.g. enough time has passed), clear the pending bytes.
+    pub fn idle(&mut self) {
+        self.pending = 0;
+    }
+}
+
+impl Default for Pacing {
+    /// 默认速率等级(S3)的发送节奏
+    ///
+    /// Pacing of the default rate class (S3).
+    fn default() -> Self {
+        Self::from_speed(&Speed::default())
+    }
+}
+
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 驱动所记录的模块无线速率等级
+    ///
+    /// Wireless rate class of the module as recorded by the driver.
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// 记录模块的无线速率等级；发送节奏由速率等级得出时改用新等级的发送节奏，自定义的发送节奏保持不变
+    ///
+    /// Record the module's wireless rate class; pacing derived from a rate class switches to that of
+    /// the new class, custom pacing stays as it is.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        if self.pacing.follows_speed() {
+            self.pacing = Pacing::from_speed(&speed);
+        }
+    }
+
+    /// 当前的发送节奏
+    ///
+    /// Current pacing policy.
+    pub fn pacing(&self) -> &Pacing {
+        &self.pacing
+    }
+
+    /// 可修改的发送节奏，例如在已知模块空闲时调用 [`Pacing::idle`]
+    ///
+    /// Mutable pacing policy, e.g. to call [`Pacing::idle`] when the module is known to be idle.
+    pub fn pacing_mut(&mut self) -> &mut Pacing {
+        &mut self.pacing
+    }
+
+    /// 使用自定义的发送节奏；传入 [`Pacing::from_speed`] 可以恢复跟随速率等级
+    ///
+    /// Use a custom pacing policy; passing [`Pacing::from_speed`] follows the rate class again.
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+    }
+
+    /// 告知发送节奏过去了 `elapsed_ms` 毫秒，例如在定时器中断中调用
+    ///
+    /// Tell the pacing `elapsed_ms` milliseconds have passed, e.g. from a timer interrupt.
+    pub fn tick_pacing(&mut self, elapsed_ms: u32) {
+        self.pacing.advance_us(elapsed_ms.saturating_mul(1000));
+    }
+
+    /// 使用驱动的延迟等待 `us` 微秒，供上层协议使用；发送节奏和占空比限制器随之推进
+    ///
+    /// Wait `us` microseconds with the driver's delay, for use by protocols on top; the pacing and
+    /// the duty-cycle limiter advance with it.
+    pub(crate) fn wait_us(&mut self, us: u32) {
+        if us > 0 {
+            self.delay.delay_us(us);
+            self.pacing.advance_us(us);
+            if let Some(limiter) = &mut self.duty_cycle {
+                limiter.advance_us(us);
+            }
+        }
+    }
+}
+
+/// 按照发送节奏写入一个字节，等待的时间同时推进占空比限制器；完整驱动与拆分的发送端共用，
+/// 不经过占空比限制器的申请，调用者需要先为整次发送调用 `admit`
+///
+/// Write one byte per the pacing, the time waited advancing the duty-cycle limiter too; shared by
+/// the full driver and the split transmit half, it bypasses admission by the duty-cycle limiter,
+/// callers `admit` the whole transmission first.
+pub(crate) fn write_paced<S: Write<u8>>(
+    serial: &mut S,
+    pacing: &mut Pacing,
+    duty_cycle: &mut Option<DutyCycle>,
+    delay: &mut impl DelayUs<u32>,
+    stats: &mut LinkStats,
+    word: u8,
+) -> Result<(), Error> {
+    let wait = pacing.before_write();
+    if wait > 0 {
+        delay.delay_us(wait);
+        if let Some(limiter) = duty_cycle {
+            limiter.advance_us(wait);
+        }
+    }
+    block!(serial.write(word)).map_err(|_| Error::Write)?;
+    stats::add(&mut stats.bytes_sent, 1);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_once_a_packet_is_full() {
+        let mut pacing = Pacing::new(4, 1_000);
+        assert_eq!([(); 4].map(|_| pacing.before_write()), [0; 4]);
+        assert_eq!(pacing.before_write(), 1_000);
+        assert_eq!(pacing.pending(), 1);
+
+        let mut pacing = Pacing::disabled();
+        assert!((0..1_000).all(|_| pacing.before_write() == 0));
+    }
+
+    #[test]
+    fn pending_bytes_drain_with_time() {
+        let mut pacing = Pacing::new(4, 1_000);
+        for _ in 0..4 {
+            pacing.before_write();
+        }
+        pacing.advance_us(500);
+        assert_eq!(pacing.pending(), 2);
+        pacing.advance_us(u32::MAX);
+        assert_eq!(pacing.pending(), 0);
+        assert_eq!(pacing.before_write(), 0);
+
+        pacing.idle();
+        assert_eq!(pacing.pending(), 0);
+    }
+
+    #[test]
+    fn derived_from_the_rate_class() {
+        let pacing = Pacing::default();
+        assert!(pacing.follows_speed());
+        assert_eq!(
+            (pacing.packet_bytes(), pacing.packet_interval_us()),
+            (80, 2_300_000)
+        );
+        assert!(!Pacing::new(80, 2_300_000).follows_speed());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn set_speed_keeps_custom_pacing() {
+        use crate::{
+            setting::parameters::Parameters,
+            sim::{SimConfig, Simulator},
+        };
+
+        let mut sim = Simulator::new(SimConfig::default());
+        let node = sim.add_node(Parameters::default());
+        let mut hc14 = Hc14::new(node.serial, node.key, node.delay).unwrap();
+
+        hc14.set_speed(Speed::S8);
+        assert_eq!(hc14.pacing().packet_bytes(), 250);
+        hc14.set_pacing(Pacing::new(64, 500_000));
+        hc14.set_speed(Speed::S1);
+        assert_eq!(*hc14.pacing(), Pacing::new(64, 500_000));
+        hc14.set_pacing(Pacing::from_speed(&Speed::S1));
+        hc14.set_speed(Speed::S5);
+        assert_eq!(hc14.pacing().packet_bytes(), 160);
+    }
+}
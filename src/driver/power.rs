@@ -0,0 +1,239 @@
+//! 电源控制引脚与强制恢复
+//!
+//! Power-control pin and forced recovery.
+//!
+//! 有些板子通过 MOSFET 切换 HC-14 的电源，高电平接通。按住 Key 引脚为低电平上电时，模块无论
+//! 之前的设置如何，都以出厂波特率 9600 bps 进入AT配置模式；[`Hc14::recover`] 利用这一点找回
+//! 波特率未知的模块，并写入一组已知的 [`Parameters`]。恢复期间主机串口必须工作在 9600 bps，
+//! 恢复后模块使用 `parameters.baud`。没有电源控制引脚的驱动使用 [`NoPowerPin`]。
+//!
+//! Some boards switch the HC-14's supply through a MOSFET, high means on. Powered up with the key
+//! pin held low, the module enters AT configuration mode at the factory rate of 9600 bps whatever
+//! its previous settings; [`Hc14::recover`] uses this to bring back a module stuck at an unknown
+//! baud rate and writes a known set of [`Parameters`]. The host serial port must run at 9600 bps
+//! during recovery, the module uses `parameters.baud` afterwards. Drivers without a power-control
+//! pin use [`NoPowerPin`].
+//!
+//! # Example
+//! ```rust
+//! let mut hc14 = Hc14::with_power_pin(serial, key, power, delay).unwrap();
+//!
+//! // 不用时断电(Cut the supply while idle)
+//! hc14.power_off().unwrap();
+//! hc14.power_on().unwrap();
+//!
+//! let profile = Parameters {
+//!     channel: Channel::from(12),
+//!     ..Parameters::default()
+//! };
+//! let hc14 = match hc14.recover(&profile) {
+//!     Ok(hc14) => hc14,
+//!     Err(e) => panic!("module lost: {:?}", e.error),
+//! };
+//! ```
+use core::{convert::Infallible, marker::PhantomData};
+
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{
+    handshake::ModeTiming, pacing::Pacing, sleep::SleepMethod, stats::LinkStats, Configuration,
+    Hc14, Normal, TransitionError,
+};
+use crate::{
+    setting::{band::BandPlan, parameters::Parameters, speed::Speed},
+    Error,
+};
+
+/// 没有电源控制引脚，模块一直通电
+///
+/// No power-control pin, the module is always powered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoPowerPin;
+
+impl OutputPin for NoPowerPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// 电源控制引脚(Power-control pin)
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 在任何模式下释放所含资源，包括电源控制引脚
+    /// - Release of included resources in any mode, the power-control pin included
+    pub fn release_with_power_pin(self) -> (S, P, D, W) {
+        (self.serial, self.key_pin, self.delay, self.power_pin)
+    }
+}
+
+/// 电源控制(Power control)
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 以正常模式构建带电源控制引脚的 Hc14 实例，接通电源并等待模块启动
+    ///
+    /// Build an Hc14 instance with a power-control pin in normal mode, switching the supply on and
+    /// waiting for the module to boot.
+    pub fn with_power_pin(
+        serial: S,
+        mut key_pin: P,
+        mut power_pin: W,
+        mut delay: D,
+    ) -> Result<Self, Error> {
+        let mode_timing = ModeTiming::default();
+        key_pin.set_high().map_err(|_| Error::Pin)?;
+        power_pin.set_high().map_err(|_| Error::Pin)?;
+        delay.delay_us(mode_timing.boot_us);
+        Ok(Self {
+            serial,
+            key_pin,
+            delay,
+            speed: Speed::default(),
+            pacing: Pacing::default(),
+            stats: LinkStats::default(),
+            mode_timing,
+            power_pin,
+            has_power_pin: true,
+            sleep_method: SleepMethod::default(),
+            duty_cycle: None,
+            band_plan: BandPlan::DEFAULT,
+            mode: PhantomData::<Normal>,
+        })
+    }
+
+    /// **[Normal]**: 断开模块电源，模块的缓冲区中尚未发出的数据会丢失
+    /// - Switch the module's supply off, data still waiting in the module's buffer is lost.
+    pub fn power_off(&mut self) -> Result<(), Error> {
+        self.power_pin.set_low().map_err(|_| Error::Pin)
+    }
+
+    /// **[Normal]**: 接通模块电源，等待 [`ModeTiming::boot_us`] 并丢弃启动期间串口上的数据
+    /// - Switch the module's supply on, waiting [`ModeTiming::boot_us`] and discarding whatever the
+    ///   serial port picked up while it booted.
+    pub fn power_on(&mut self) -> Result<(), Error> {
+        // Key 引脚为低电平时上电会进入强制恢复(Powering up with the key pin low forces recovery)
+        self.key_pin.set_high().map_err(|_| Error::Pin)?;
+        self.power_pin.set_high().map_err(|_| Error::Pin)?;
+        self.delay.delay_us(self.mode_timing.boot_us);
+        self.discard_input();
+        Ok(())
+    }
+
+    /// **[Normal]**: 按住 Key 引脚重新上电，以出厂波特率进入AT配置模式，写入 `parameters` 后回到
+    /// 正常模式；失败时交还正常模式的驱动，模块的设置未知
+    /// - Power-cycle with the key pin held low into AT configuration mode at the factory rate, write
+    ///   `parameters` and return to normal mode; hands back the normal-mode driver on failure, with the
+    ///   module's settings unknown.
+    ///
+    /// 没有电源控制引脚时不会重新上电，只能恢复波特率已知的模块。
+    ///
+    /// Without a power-control pin there is no power cycle, only modules at a known baud rate can be
+    /// recovered.
+    #[allow(clippy::result_large_err)] // 错误中带着整个驱动(The error carries the whole driver)
+    pub fn recover(mut self, parameters: &Parameters) -> Result<Self, TransitionError<Self>> {
+        if let Err(error) = self.power_cycle_key_low() {
+            return Err(TransitionError { hc14: self, error });
+        }
+        self.discard_input();
+        if !(0..=self.mode_timing.retries).any(|_| self.at_probe()) {
+            return Err(self.abort_recovery(Error::NoResponse));
+        }
+
+        let mut configure: Hc14<S, P, D, Configuration, W> = self.into_mode();
+        if let Err(error) = configure.apply_parameters(parameters) {
+            return Err(configure.into_mode().abort_recovery(error));
+        }
+        configure.into_normal_mode().map_err(|e| TransitionError {
+            hc14: e.hc14.into_mode(),
+            error: e.error,
+        })
+    }
+
+    fn power_cycle_key_low(&mut self) -> Result<(), Error> {
+        let timing = self.mode_timing;
+        self.key_pin.set_low().map_err(|_| Error::Pin)?;
+        self.power_pin.set_low().map_err(|_| Error::Pin)?;
+        self.delay.delay_us(timing.power_off_us);
+        self.power_pin.set_high().map_err(|_| Error::Pin)?;
+        self.delay.delay_us(timing.boot_us);
+        Ok(())
+    }
+
+    /// 拉高 Key 引脚回到正常模式并返回错误
+    ///
+    /// Pull the key pin high back to normal mode and return the error.
+    fn abort_recovery(mut self, error: Error) -> TransitionError<Self> {
+        let error = match self.key_pin.set_high() {
+            Ok(_) => error,
+            Err(_) => Error::Pin,
+        };
+        self.delay.delay_us(self.mode_timing.settle_us);
+        TransitionError { hc14: self, error }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        conf::AT_COMMAND_QUERY_MODE,
+        setting::{baudrate::BaudRate, channel::Channel, power::TransmissionPower},
+        sim::{SimConfig, Simulator},
+    };
+
+    #[test]
+    fn recover_restores_a_profile() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let node = sim.add_node(Parameters::default());
+        let mut hc14 = Hc14::with_power_pin(node.serial, node.key, node.power, node.delay).unwrap();
+        // 模块停在未知的设置上(The module is stuck with unknown settings)
+        sim.set_parameters(
+            node.id,
+            Parameters {
+                baud: BaudRate::Bps57600,
+                channel: Channel::from(40),
+                power: TransmissionPower::new(6).unwrap(),
+                speed: Speed::S8,
+            },
+        );
+        hc14.set_speed(Speed::S8);
+
+        let profile = Parameters {
+            channel: Channel::from(12),
+            speed: Speed::S5,
+            ..Parameters::default()
+        };
+        let hc14 = hc14.recover(&profile).map_err(|e| e.error).unwrap();
+        assert_eq!(sim.parameters(node.id), profile);
+        assert_eq!(hc14.speed(), Speed::S5);
+
+        // 任何模式下都能取回电源控制引脚(The power-control pin comes back in any mode)
+        let configure = hc14.into_configuration_mode().map_err(|e| e.error).unwrap();
+        let (mut serial, _, _, mut power) = configure.release_with_power_pin();
+        power.set_low().unwrap();
+        // 断电后不再应答(No answer once the supply is cut)
+        for byte in AT_COMMAND_QUERY_MODE {
+            serial.write(byte).unwrap();
+        }
+        assert!(matches!(serial.read(), Err(nb::Error::WouldBlock)));
+    }
+}
@@ -0,0 +1,338 @@
+//! 基于分隔符、固定长度和行的接收接口，并报告被截断的字节数
+//!
+//! Delimiter-, length- and line-based receive API reporting truncated bytes.
+//!
+//! # Example
+//! ```rust
+//! let mut hc14 = Hc14::new(serial, key, delay).unwrap();
+//!
+//! let (line, outcome) = hc14.read_line::<40>().unwrap();
+//! if outcome.is_truncated() {
+//!     hprintln!("dropped {} bytes", outcome.dropped);
+//! }
+//!
+//! let mut buffer = [0u8; 16];
+//! let outcome = hc14.read_until(b';', &mut buffer).unwrap();
+//! let message = &buffer[..outcome.len];
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use heapless::{String, Vec};
+use nb::block;
+
+use super::{
+    split::Hc14Rx,
+    stats::{self, LinkStats},
+    Hc14, Normal,
+};
+use crate::Error;
+
+/// 一次接收的结果：写入缓冲区的字节数及因缓冲区不足而丢弃的字节数
+///
+/// Outcome of one receive: bytes stored in the buffer and bytes dropped because the buffer was too small.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadOutcome {
+    /// 写入缓冲区的字节数
+    ///
+    /// Bytes stored in the buffer.
+    pub len: usize,
+    /// 丢弃的字节数
+    ///
+    /// Bytes dropped.
+    pub dropped: usize,
+}
+
+impl ReadOutcome {
+    /// 消息是否被截断
+    ///
+    /// Whether the message was truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.dropped > 0
+    }
+}
+
+fn read_byte<R: Read<u8>>(serial: &mut R) -> Result<u8, Error> {
+    block!(serial.read()).map_err(|_| Error::Read)
+}
+
+/// 读取至换行符(包含在内)或缓冲区已满，串口出错时记为溢出并继续读取，返回写入的字节数
+///
+/// Read up to and including a newline or until the buffer is full; a serial error is counted as
+/// an overrun and reading goes on. Returns the bytes stored.
+pub(crate) fn read_buffer<R: Read<u8>>(
+    serial: &mut R,
+    stats: &mut LinkStats,
+    buffer: &mut [u8],
+) -> usize {
+    let mut count: usize = 0;
+    for v in buffer.iter_mut() {
+        match block!(serial.read()) {
+            Ok(ch) => {
+                *v = ch;
+                count += 1;
+                if ch == b'\n' {
+                    break;
+                }
+            }
+            Err(_) => stats::add(&mut stats.overruns, 1),
+        }
+    }
+    stats::add(&mut stats.bytes_received, count);
+    count
+}
+
+/// 不阻塞地读取当前可用的所有字节，返回写入的字节数
+///
+/// Read every byte currently available without blocking. Returns the bytes stored.
+pub(crate) fn read_available<R: Read<u8>>(
+    serial: &mut R,
+    stats: &mut LinkStats,
+    buffer: &mut [u8],
+) -> Result<usize, Error> {
+    let mut count: usize = 0;
+    while count < buffer.len() {
+        match serial.read() {
+            Ok(ch) => {
+                buffer[count] = ch;
+                count += 1;
+            }
+            Err(nb::Error::WouldBlock) => break,
+            Err(nb::Error::Other(_)) => {
+                stats::add(&mut stats.overruns, 1);
+                return Err(Error::Read);
+            }
+        }
+    }
+    stats::add(&mut stats.bytes_received, count);
+    Ok(count)
+}
+
+/// 读取至 `delim`(包含在内)，缓冲区满后继续读取并丢弃，直到遇到 `delim`
+///
+/// Read up to and including `delim`; once the buffer is full keep reading and dropping until `delim`.
+pub(crate) fn read_until<R: Read<u8>>(
+    serial: &mut R,
+    delim: u8,
+    buffer: &mut [u8],
+) -> Result<ReadOutcome, Error> {
+    let mut outcome = ReadOutcome::default();
+    loop {
+        let ch = read_byte(serial)?;
+        if outcome.len < buffer.len() {
+            buffer[outcome.len] = ch;
+            outcome.len += 1;
+        } else {
+            outcome.dropped += 1;
+        }
+        if ch == delim {
+            return Ok(outcome);
+        }
+    }
+}
+
+/// 读取正好 `len` 个字节，超出缓冲区的部分被丢弃
+///
+/// Read exactly `len` bytes, the part beyond the buffer is dropped.
+pub(crate) fn read_exact<R: Read<u8>>(
+    serial: &mut R,
+    len: usize,
+    buffer: &mut [u8],
+) -> Result<ReadOutcome, Error> {
+    let mut outcome = ReadOutcome::default();
+    for _ in 0..len {
+        let ch = read_byte(serial)?;
+        if outcome.len < buffer.len() {
+            buffer[outcome.len] = ch;
+            outcome.len += 1;
+        } else {
+            outcome.dropped += 1;
+        }
+    }
+    Ok(outcome)
+}
+
+/// 读取一行(不含 `\r\n`)，超过 `N` 字节的部分被丢弃
+///
+/// Read one line (without `\r\n`), the part beyond `N` bytes is dropped.
+pub(crate) fn read_line<R: Read<u8>, const N: usize>(
+    serial: &mut R,
+) -> Result<(String<N>, ReadOutcome), Error> {
+    let mut bytes: Vec<u8, N> = Vec::new();
+    let mut outcome = ReadOutcome::default();
+    // `\r` 只有在后面不是 `\n` 时才属于行内容(A `\r` only belongs to the line when no `\n` follows)
+    let mut pending_cr = false;
+    loop {
+        let ch = read_byte(serial)?;
+        if ch == b'\n' {
+            break;
+        }
+        if core::mem::replace(&mut pending_cr, false) && bytes.push(b'\r').is_err() {
+            outcome.dropped += 1;
+        }
+        if ch == b'\r' {
+            pending_cr = true;
+        } else if bytes.push(ch).is_err() {
+            outcome.dropped += 1;
+        }
+    }
+
+    // 截断可能发生在多字节字符的中间，保留完整的前缀
+    // Truncation may split a multi-byte character, keep the complete prefix
+    let valid = match core::str::from_utf8(&bytes) {
+        Ok(s) => s,
+        Err(e) if outcome.is_truncated() && e.error_len().is_none() => {
+            outcome.dropped += bytes.len() - e.valid_up_to();
+            core::str::from_utf8(&bytes[..e.valid_up_to()]).map_err(|_| Error::Utf8)?
+        }
+        Err(_) => return Err(Error::Utf8),
+    };
+    let mut line: String<N> = String::new();
+    line.push_str(valid).map_err(|_| Error::Utf8)?;
+    outcome.len = line.len();
+    Ok((line, outcome))
+}
+
+/// 正常模式接收接口(Normal mode receive API)
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// **[Normal]**: 读取至分隔符 `delim`(包含在内)，返回写入和丢弃的字节数
+    /// - Read up to and including the delimiter `delim`, returns the bytes stored and dropped
+    pub fn read_until(&mut self, delim: u8, buffer: &mut [u8]) -> Result<ReadOutcome, Error> {
+        let result = read_until(&mut self.serial, delim, buffer);
+        self.stats.on_read(result)
+    }
+
+    /// **[Normal]**: 读取正好 `len` 个字节的消息，返回写入和丢弃的字节数
+    /// - Read a message of exactly `len` bytes, returns the bytes stored and dropped
+    pub fn read_exact(&mut self, len: usize, buffer: &mut [u8]) -> Result<ReadOutcome, Error> {
+        let result = read_exact(&mut self.serial, len, buffer);
+        self.stats.on_read(result)
+    }
+
+    /// **[Normal]**: 读取一行字符串，最大长度为 `N` 字节
+    /// - Read one line as a string, maximum length `N` bytes
+    pub fn read_line<const N: usize>(&mut self) -> Result<(String<N>, ReadOutcome), Error> {
+        let result = read_line(&mut self.serial);
+        self.stats.on_line(result)
+    }
+}
+
+impl<RX> Hc14Rx<RX>
+where
+    RX: Read<u8>,
+{
+    /// 读取至分隔符 `delim`(包含在内)，返回写入和丢弃的字节数
+    ///
+    /// Read up to and including the delimiter `delim`, returns the bytes stored and dropped.
+    pub fn read_until(&mut self, delim: u8, buffer: &mut [u8]) -> Result<ReadOutcome, Error> {
+        let result = read_until(&mut self.rx, delim, buffer);
+        self.stats.on_read(result)
+    }
+
+    /// 读取正好 `len` 个字节的消息，返回写入和丢弃的字节数
+    ///
+    /// Read a message of exactly `len` bytes, returns the bytes stored and dropped.
+    pub fn read_exact(&mut self, len: usize, buffer: &mut [u8]) -> Result<ReadOutcome, Error> {
+        let result = read_exact(&mut self.rx, len, buffer);
+        self.stats.on_read(result)
+    }
+
+    /// 读取一行字符串，最大长度为 `N` 字节
+    ///
+    /// Read one line as a string, maximum length `N` bytes.
+    pub fn read_line<const N: usize>(&mut self) -> Result<(String<N>, ReadOutcome), Error> {
+        let result = read_line(&mut self.rx);
+        self.stats.on_line(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 依次读出给定字节的串口，读完后报错(Serial port yielding the given bytes, then an error)
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Read<u8> for Bytes<'_> {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            let (&first, rest) = self.0.split_first().ok_or(nb::Error::Other(()))?;
+            self.0 = rest;
+            Ok(first)
+        }
+    }
+
+    fn line<const N: usize>(input: &[u8]) -> Result<(String<N>, ReadOutcome), Error> {
+        read_line(&mut Bytes(input))
+    }
+
+    #[test]
+    fn line_filling_the_buffer_is_not_truncated() {
+        let (text, outcome) = line::<4>(b"abcd\r\n").unwrap();
+        assert_eq!(
+            (text.as_str(), outcome.len, outcome.dropped),
+            ("abcd", 4, 0)
+        );
+        let (text, outcome) = line::<4>(b"abcd\n").unwrap();
+        assert_eq!((text.as_str(), outcome.dropped), ("abcd", 0));
+    }
+
+    #[test]
+    fn long_line_is_truncated() {
+        let (text, outcome) = line::<4>(b"abcdef\r\n").unwrap();
+        assert_eq!((text.as_str(), outcome.dropped), ("abcd", 2));
+        assert!(outcome.is_truncated());
+        // 缓冲区之外的 `\r` 也算作丢弃(A `\r` beyond the buffer counts as dropped too)
+        let (text, outcome) = line::<4>(b"abcd\rx\n").unwrap();
+        assert_eq!((text.as_str(), outcome.dropped), ("abcd", 2));
+    }
+
+    #[test]
+    fn lone_carriage_return_is_kept() {
+        let (text, _) = line::<8>(b"a\rb\r\r\n").unwrap();
+        assert_eq!(text.as_str(), "a\rb\r");
+    }
+
+    #[test]
+    fn truncation_keeps_whole_characters() {
+        let (text, outcome) = line::<4>("ab€\n".as_bytes()).unwrap();
+        assert_eq!((text.as_str(), outcome.len, outcome.dropped), ("ab", 2, 3));
+        assert!(matches!(line::<4>(b"a\xffb\n"), Err(Error::Utf8)));
+    }
+
+    #[test]
+    fn serial_error_is_reported() {
+        assert!(matches!(line::<4>(b"ab"), Err(Error::Read)));
+        assert!(matches!(
+            read_until(&mut Bytes(b"ab"), 0, &mut [0u8; 4]),
+            Err(Error::Read)
+        ));
+    }
+
+    #[test]
+    fn read_until_drops_the_overflow() {
+        let mut buffer = [0u8; 3];
+        let outcome = read_until(&mut Bytes(b"ab\0cd"), 0, &mut buffer).unwrap();
+        assert_eq!((outcome.len, outcome.dropped, &buffer), (3, 0, b"ab\0"));
+        let outcome = read_until(&mut Bytes(b"abcde\0"), 0, &mut buffer).unwrap();
+        assert_eq!((outcome.len, outcome.dropped, &buffer), (3, 3, b"abc"));
+    }
+
+    #[test]
+    fn read_exact_drops_the_overflow() {
+        let mut buffer = [0u8; 2];
+        let mut serial = Bytes(b"abcde");
+        let outcome = read_exact(&mut serial, 4, &mut buffer).unwrap();
+        assert_eq!((outcome.len, outcome.dropped, &buffer), (2, 2, b"ab"));
+        assert_eq!(serial.0, b"e");
+    }
+}
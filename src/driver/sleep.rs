@@ -0,0 +1,358 @@
+//! 睡眠模式与空闲策略，适用于电池供电的节点
+//!
+//! Sleep mode and idle policy for battery-powered nodes.
+//!
+//! 模块可以用两种方式睡眠：在AT配置模式下发送 `AT+SLEEP`，离开AT配置模式后模块进入睡眠，
+//! 再次拉低 Key 引脚才会唤醒，睡眠期间收不到无线数据；或者通过电源控制引脚直接断电，唤醒时
+//! 重新上电。固件不支持 `AT+SLEEP` 的模块返回 [`Error::CommandRejected`]，此时需要使用
+//! [`SleepMethod::PowerPin`]。[`IdlePolicy`] 根据 [`LinkStats`] 中的收发字节数判断链路是否
+//! 空闲，配合 [`Hc14Any::tick`](super::any::Hc14Any::tick) 在没有流量一段时间后自动睡眠。
+//!
+//! The module sleeps in one of two ways: sending `AT+SLEEP` in AT configuration mode makes it
+//! sleep once AT configuration mode is left, until the key pin is pulled low again, hearing no
+//! radio data meanwhile; or the power-control pin cuts its supply, and waking powers it up again.
+//! Modules whose firmware lacks `AT+SLEEP` answer with [`Error::CommandRejected`], those need
+//! [`SleepMethod::PowerPin`]. [`IdlePolicy`] judges whether the link is idle from the bytes counted
+//! in [`LinkStats`] and, together with [`Hc14Any::tick`](super::any::Hc14Any::tick), puts the
+//! module to sleep after a while without traffic.
+//!
+//! # Example
+//! ```rust
+//! let hc14 = Hc14::new(serial, key, delay).unwrap();
+//! let sleeping = hc14.into_sleep().map_err(|e| e.error)?;
+//! // 等待下一次上报(Wait for the next report)
+//! let mut hc14 = sleeping.wake().map_err(|e| e.error)?;
+//! hc14.send_buffer(b"report").unwrap();
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{stats::LinkStats, Hc14, Normal, Sleep, TransitionError};
+use crate::{
+    conf::{AT_COMMAND_SLEEP, RESPONSE_SLEEP},
+    Error,
+};
+
+/// 模块的睡眠方式
+///
+/// How the module is put to sleep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SleepMethod {
+    /// `AT+SLEEP` 指令，拉低 Key 引脚唤醒(默认)(The `AT+SLEEP` command, woken by pulling the key pin low (default))
+    #[default]
+    Command,
+    /// 通过电源控制引脚断电，驱动须由 [`Hc14::with_power_pin`] 构建
+    /// (Cutting the supply through the power-control pin, the driver must be built by
+    /// [`Hc14::with_power_pin`])
+    PowerPin,
+}
+
+/// 空闲策略：连续 `timeout_ms` 毫秒没有收发字节后认为链路空闲，由调用者定时调用 [`IdlePolicy::tick`]
+///
+/// Idle policy: the link counts as idle after `timeout_ms` milliseconds without bytes sent or
+/// received, the caller invokes [`IdlePolicy::tick`] periodically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdlePolicy {
+    timeout_ms: u32,
+    idle_ms: u32,
+    traffic: u32,
+}
+
+impl IdlePolicy {
+    /// 空闲 `timeout_ms` 毫秒后睡眠
+    ///
+    /// Sleep after `timeout_ms` milliseconds idle.
+    pub const fn new(timeout_ms: u32) -> Self {
+        Self {
+            timeout_ms,
+            idle_ms: 0,
+            traffic: 0,
+        }
+    }
+
+    /// 空闲多久后睡眠(毫秒)
+    ///
+    /// Idle time before sleeping, in milliseconds.
+    pub fn timeout_ms(&self) -> u32 {
+        self.timeout_ms
+    }
+
+    /// 到目前为止的空闲时间(毫秒)
+    ///
+    /// Idle time so far, in milliseconds.
+    pub fn idle_ms(&self) -> u32 {
+        self.idle_ms
+    }
+
+    /// 重新开始计算空闲时间，例如唤醒之后
+    ///
+    /// Start counting idle time afresh, e.g. after waking up.
+    pub fn activity(&mut self) {
+        self.idle_ms = 0;
+    }
+
+    /// 经过 `elapsed_ms` 毫秒后调用，计数有变化时重新开始计时；返回链路是否已经空闲足够久
+    ///
+    /// Call after `elapsed_ms` milliseconds, counting starts afresh when the counters moved; returns
+    /// whether the link has been idle long enough.
+    pub fn tick(&mut self, stats: &LinkStats, elapsed_ms: u32) -> bool {
+        let traffic = stats.bytes_sent.wrapping_add(stats.bytes_received);
+        if traffic != self.traffic {
+            self.traffic = traffic;
+            self.idle_ms = 0;
+            return false;
+        }
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+        self.idle_ms >= self.timeout_ms
+    }
+}
+
+/// 睡眠设置(Sleep settings)
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 当前的睡眠方式
+    ///
+    /// The current sleep method.
+    pub fn sleep_method(&self) -> SleepMethod {
+        self.sleep_method
+    }
+
+    /// 修改睡眠方式
+    ///
+    /// Change the sleep method.
+    pub fn set_sleep_method(&mut self, method: SleepMethod) {
+        self.sleep_method = method;
+    }
+}
+
+impl<S, P, D, W> Hc14<S, P, D, Normal, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// ! **"正常模式"** 切换到: "**睡眠模式**"，失败时交还正常模式的驱动；使用
+    /// [`SleepMethod::PowerPin`] 但驱动不是由 [`Hc14::with_power_pin`] 构建时返回 [`Error::NoPowerPin`]
+    /// - **"Normal Mode"** Switch to: "**Sleep Mode**", handing back the normal-mode driver on failure;
+    ///   returns [`Error::NoPowerPin`] for [`SleepMethod::PowerPin`] when the driver was not built by
+    ///   [`Hc14::with_power_pin`]
+    #[allow(clippy::result_large_err)] // 错误中带着整个驱动(The error carries the whole driver)
+    pub fn into_sleep(mut self) -> Result<Hc14<S, P, D, Sleep, W>, TransitionError<Self>> {
+        if self.sleep_method == SleepMethod::PowerPin {
+            if !self.has_power_pin {
+                return Err(TransitionError {
+                    hc14: self,
+                    error: Error::NoPowerPin,
+                });
+            }
+            return match self.power_off() {
+                Ok(_) => Ok(self.asleep()),
+                Err(error) => Err(TransitionError { hc14: self, error }),
+            };
+        }
+
+        let mut configure = self.into_configuration_mode()?;
+        let mut line = [0u8; RESPONSE_SLEEP.len()];
+        if configure.at_command(&AT_COMMAND_SLEEP, &mut line) != Some(&RESPONSE_SLEEP[..]) {
+            return match configure.into_normal_mode() {
+                Ok(hc14) => Err(TransitionError {
+                    hc14,
+                    error: Error::CommandRejected,
+                }),
+                Err(e) => Err(TransitionError {
+                    hc14: e.hc14.into_mode(),
+                    error: e.error,
+                }),
+            };
+        }
+        // 模块已经睡眠，不再以 `AT` 确认离开(The module sleeps, leaving is not confirmed with `AT`)
+        let mut hc14: Self = configure.into_mode();
+        if hc14.key_pin.set_high().is_err() {
+            return Err(TransitionError {
+                hc14,
+                error: Error::Pin,
+            });
+        }
+        hc14.delay.delay_us(hc14.mode_timing.settle_us);
+        Ok(hc14.asleep())
+    }
+
+    fn asleep(mut self) -> Hc14<S, P, D, Sleep, W> {
+        // 模块缓冲区中的数据已经发出或丢失(Whatever sat in the module buffer is sent or lost)
+        self.pacing.idle();
+        self.into_mode()
+    }
+}
+
+/// 睡眠模式(Sleep Mode)
+impl<S, P, D, W> Hc14<S, P, D, Sleep, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// ! **"睡眠模式"** 切换到 "**正常模式**"，失败时交还睡眠模式的驱动，可以再次尝试唤醒
+    /// - **"Sleep Mode"** Switch to "**Normal Mode**", handing back the sleep-mode driver on failure,
+    ///   waking may be tried again
+    #[allow(clippy::result_large_err)] // 错误中带着整个驱动(The error carries the whole driver)
+    pub fn wake(self) -> Result<Hc14<S, P, D, Normal, W>, TransitionError<Self>> {
+        let mut hc14: Hc14<S, P, D, Normal, W> = self.into_mode();
+        if hc14.sleep_method == SleepMethod::PowerPin {
+            return match hc14.power_on() {
+                Ok(_) => Ok(hc14),
+                Err(error) => Err(TransitionError {
+                    hc14: hc14.into_mode(),
+                    error,
+                }),
+            };
+        }
+
+        // 拉低 Key 引脚唤醒模块并确认它在应答(Pulling the key pin low wakes the module, confirm it answers)
+        let configure = hc14
+            .into_configuration_mode()
+            .map_err(|e| TransitionError {
+                hc14: e.hc14.into_mode(),
+                error: e.error,
+            })?;
+        configure.into_normal_mode().map_err(|e| TransitionError {
+            hc14: e.hc14.into_mode(),
+            error: e.error,
+        })
+    }
+
+    /// 释放所含资源，电源控制引脚见 [`Hc14::release_with_power_pin`]
+    /// - Release of included resources, see [`Hc14::release_with_power_pin`] for the power-control pin
+    pub fn release(self) -> (S, P, D) {
+        (self.serial, self.key_pin, self.delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_policy_restarts_on_traffic() {
+        let mut idle = IdlePolicy::new(1_000);
+        let mut stats = LinkStats::default();
+        assert!(!idle.tick(&stats, 600));
+        assert!(idle.tick(&stats, 600));
+        assert_eq!(idle.idle_ms(), 1_200);
+
+        stats.bytes_received = 1;
+        assert!(!idle.tick(&stats, 600));
+        assert_eq!(idle.idle_ms(), 0);
+        assert!(!idle.tick(&stats, 600));
+        idle.activity();
+        assert!(!idle.tick(&stats, 600));
+        assert!(idle.tick(&stats, u32::MAX));
+        assert_eq!(idle.idle_ms(), u32::MAX);
+    }
+
+    #[cfg(feature = "std")]
+    mod sim {
+        use super::*;
+        use crate::{
+            driver::frame::MAX_FRAME_SIZE,
+            setting::parameters::Parameters,
+            sim::{SimConfig, SimDelay, SimNode, SimPin, SimSerial, Simulator},
+        };
+
+        type Radio = Hc14<SimSerial, SimPin, SimDelay, Normal>;
+
+        /// 一个普通节点和一个要睡眠的节点(A plain node and one about to sleep)
+        fn nodes(sim: &mut Simulator) -> (Radio, SimNode) {
+            let a = sim.add_node(Parameters::default());
+            let b = sim.add_node(Parameters::default());
+            (Hc14::new(a.serial, a.key, a.delay).unwrap(), b)
+        }
+
+        /// 发送一帧并返回它是否送达了某个节点(Send a frame and return whether it reached a node)
+        fn delivered(sim: &mut Simulator, a: &mut Radio) -> bool {
+            let before = sim.stats().deliveries;
+            a.send_frame(b"ping").unwrap();
+            sim.advance_us(10_000_000);
+            sim.stats().deliveries > before
+        }
+
+        fn receive<W: OutputPin>(hc14: &mut Hc14<SimSerial, SimPin, SimDelay, Normal, W>) {
+            let mut buffer = [0u8; MAX_FRAME_SIZE];
+            let frame = hc14.receive_frame_timeout(&mut buffer, 10_000_000).unwrap();
+            assert_eq!(frame, b"ping");
+        }
+
+        #[test]
+        fn command_sleep_lasts_until_woken() {
+            let mut sim = Simulator::new(SimConfig::default());
+            let (mut a, b) = nodes(&mut sim);
+            let sleeping = Hc14::new(b.serial, b.key, b.delay)
+                .unwrap()
+                .into_sleep()
+                .map_err(|e| e.error)
+                .unwrap();
+            assert!(!delivered(&mut sim, &mut a));
+
+            let mut b = sleeping.wake().map_err(|e| e.error).unwrap();
+            assert!(delivered(&mut sim, &mut a));
+            receive(&mut b);
+        }
+
+        #[test]
+        fn power_pin_sleep_cuts_the_supply() {
+            let mut sim = Simulator::new(SimConfig::default());
+            let (mut a, b) = nodes(&mut sim);
+            let mut hc14 = Hc14::with_power_pin(b.serial, b.key, b.power, b.delay).unwrap();
+            hc14.set_sleep_method(SleepMethod::PowerPin);
+            let sleeping = hc14.into_sleep().map_err(|e| e.error).unwrap();
+            assert!(!delivered(&mut sim, &mut a));
+
+            let mut b = sleeping.wake().map_err(|e| e.error).unwrap();
+            assert!(delivered(&mut sim, &mut a));
+            receive(&mut b);
+        }
+
+        #[test]
+        fn power_pin_sleep_needs_a_power_pin() {
+            let mut sim = Simulator::new(SimConfig::default());
+            let (mut a, b) = nodes(&mut sim);
+            let mut hc14 = Hc14::new(b.serial, b.key, b.delay).unwrap();
+            hc14.set_sleep_method(SleepMethod::PowerPin);
+
+            let e = hc14.into_sleep().map(|_| ()).unwrap_err();
+            assert!(matches!(e.error, Error::NoPowerPin));
+            // 驱动仍处于正常模式(The driver is still in normal mode)
+            let mut b = e.hc14;
+            assert!(delivered(&mut sim, &mut a));
+            receive(&mut b);
+        }
+
+        #[test]
+        fn rejected_sleep_command_falls_back_to_the_power_pin() {
+            let mut sim = Simulator::new(SimConfig::default());
+            let (mut a, b) = nodes(&mut sim);
+            sim.set_sleep_supported(b.id, false);
+            let hc14 = Hc14::with_power_pin(b.serial, b.key, b.power, b.delay).unwrap();
+
+            let e = hc14.into_sleep().map(|_| ()).unwrap_err();
+            assert!(matches!(e.error, Error::CommandRejected));
+            let mut hc14 = e.hc14;
+            assert!(delivered(&mut sim, &mut a));
+            receive(&mut hc14);
+
+            hc14.set_sleep_method(SleepMethod::PowerPin);
+            let sleeping = hc14.into_sleep().map_err(|e| e.error).unwrap();
+            assert!(!delivered(&mut sim, &mut a));
+            sleeping.wake().map_err(|e| e.error).unwrap();
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! 将正常模式驱动拆分为独立的发送端和接收端
+//!
+//! Split the normal-mode driver into independent transmit and receive halves.
+//!
+//! # Example
+//! ```rust
+//! let (tx, rx) = serial.split();
+//! let hc14 = Hc14::new(Duplex::new(tx, rx), key, delay).unwrap();
+//!
+//! // 在不同的任务中发送和接收(Transmit and receive from different tasks)
+//! let (mut hc14_tx, mut hc14_rx) = hc14.split();
+//! hc14_tx.send_buffer(b"hc14").unwrap();
+//! let mut buffer = [0u8; 32];
+//! hc14_rx.read_buffer(&mut buffer).unwrap();
+//!
+//! // 合并后才能切换至AT配置模式(Rejoin before switching to AT configuration mode)
+//! let hc14 = Hc14::join(hc14_tx, hc14_rx);
+//! let hc14_configure = hc14.into_configuration_mode().unwrap();
+//! ```
+use core::marker::PhantomData;
+
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use nb::block;
+
+use super::{
+    buffered::Duplex,
+    duty::{self, DutyCycle},
+    handshake::ModeTiming,
+    pacing::{self, Pacing},
+    power::NoPowerPin,
+    receive,
+    sleep::SleepMethod,
+    stats::LinkStats,
+    Hc14, Normal,
+};
+use crate::{
+    setting::{band::BandPlan, speed::Speed},
+    Error,
+};
+
+/// 正常模式的发送端，同时保管 Key 引脚、电源控制引脚和延迟
+///
+/// Normal-mode transmit half, also holding the key pin, the power-control pin and the delay.
+#[derive(Debug)]
+pub struct Hc14Tx<TX, P, D, W = NoPowerPin>
+where
+    TX: Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    tx: TX,
+    key_pin: P,
+    delay: D,
+    speed: Speed,
+    pacing: Pacing,
+    stats: LinkStats,
+    mode_timing: ModeTiming,
+    sleep_method: SleepMethod,
+    duty_cycle: Option<DutyCycle>,
+    band_plan: BandPlan,
+    power_pin: W,
+    has_power_pin: bool,
+}
+
+/// 正常模式的接收端，拆分期间单独统计接收的计数
+///
+/// Normal-mode receive half, counting the receive side on its own while split.
+#[derive(Debug)]
+pub struct Hc14Rx<RX>
+where
+    RX: Read<u8>,
+{
+    pub(crate) rx: RX,
+    pub(crate) stats: LinkStats,
+}
+
+impl<TX, RX, P, D, W> Hc14<Duplex<TX, RX>, P, D, Normal, W>
+where
+    TX: Write<u8>,
+    RX: Read<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 拆分为发送端和接收端，Key 引脚保持为高电平(正常模式)
+    ///
+    /// Split into transmit and receive halves, the key pin stays high (normal mode).
+    pub fn split(self) -> (Hc14Tx<TX, P, D, W>, Hc14Rx<RX>) {
+        let (tx, rx) = self.serial.release();
+        (
+            Hc14Tx {
+                tx,
+                key_pin: self.key_pin,
+                delay: self.delay,
+                speed: self.speed,
+                pacing: self.pacing,
+                stats: self.stats,
+                mode_timing: self.mode_timing,
+                sleep_method: self.sleep_method,
+                duty_cycle: self.duty_cycle,
+                band_plan: self.band_plan,
+                power_pin: self.power_pin,
+                has_power_pin: self.has_power_pin,
+            },
+            Hc14Rx {
+                rx,
+                stats: LinkStats::default(),
+            },
+        )
+    }
+
+    /// 将发送端和接收端合并为完整的驱动，之后可切换至AT配置模式；两端的计数相加
+    ///
+    /// Rejoin the transmit and receive halves into the full driver, e.g. to switch to AT configuration mode;
+    /// the counters of both halves are added up.
+    pub fn join(tx: Hc14Tx<TX, P, D, W>, rx: Hc14Rx<RX>) -> Self {
+        let mut stats = tx.stats;
+        stats.merge(&rx.stats);
+        Hc14 {
+            serial: Duplex::new(tx.tx, rx.rx),
+            key_pin: tx.key_pin,
+            delay: tx.delay,
+            speed: tx.speed,
+            pacing: tx.pacing,
+            stats,
+            mode_timing: tx.mode_timing,
+            power_pin: tx.power_pin,
+            has_power_pin: tx.has_power_pin,
+            sleep_method: tx.sleep_method,
+            duty_cycle: tx.duty_cycle,
+            band_plan: tx.band_plan,
+            mode: PhantomData::<Normal>,
+        }
+    }
+}
+
+impl<TX, P, D, W> Hc14Tx<TX, P, D, W>
+where
+    TX: Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 发送字节，按照 [`Pacing`] 在数据包已满时等待；单独发送的字节占用一个完整的数据包
+    ///
+    /// Send byte, waiting per [`Pacing`] once a packet is full; a byte sent on its own takes a whole
+    /// packet.
+    pub fn send_byte(&mut self, word: u8) -> Result<(), Error> {
+        self.send_buffer(&[word])
+    }
+
+    /// 将整个缓冲区作为一次发送写入串行端口
+    ///
+    /// Write the entire buffer to the serial port as one transmission.
+    pub fn send_buffer(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.admit(buffer.len())?;
+        for ch in buffer {
+            self.write_byte(*ch)?;
+        }
+        Ok(())
+    }
+
+    /// 发送字符串，遇到 `\0` 时停止，与 [`Hc14::send_string`] 相同
+    ///
+    /// Send string, stopping at `\0` as [`Hc14::send_string`] does.
+    pub fn send_string(&mut self, words: &str) -> Result<(), Error> {
+        let bytes = words.as_bytes();
+        let end = bytes
+            .iter()
+            .position(|b| *b == b'\0')
+            .unwrap_or(bytes.len());
+        self.send_buffer(&bytes[..end])
+    }
+
+    /// 等待串口发送完成
+    ///
+    /// Wait until the serial port has finished transmitting.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        block!(self.tx.flush()).map_err(|_| Error::Write)
+    }
+
+    /// 发送端的链路计数器，接收的计数见 [`Hc14Rx::stats`]，合并时两者相加
+    ///
+    /// The link counters of the transmit half, the receive side is counted in [`Hc14Rx::stats`],
+    /// both are added up on joining.
+    pub fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    /// 告知发送节奏过去了 `elapsed_ms` 毫秒，见 [`Hc14::tick_pacing`]
+    ///
+    /// Tell the pacing `elapsed_ms` milliseconds have passed, see [`Hc14::tick_pacing`].
+    pub fn tick_pacing(&mut self, elapsed_ms: u32) {
+        self.pacing.advance_us(elapsed_ms.saturating_mul(1000));
+    }
+
+    /// 告知占空比限制器过去了 `elapsed_ms` 毫秒，见 [`Hc14::tick_duty_cycle`]
+    ///
+    /// Tell the duty-cycle limiter `elapsed_ms` milliseconds have passed, see
+    /// [`Hc14::tick_duty_cycle`].
+    pub fn tick_duty_cycle(&mut self, elapsed_ms: u32) {
+        if let Some(limiter) = &mut self.duty_cycle {
+            limiter.advance_us(elapsed_ms.saturating_mul(1000));
+        }
+    }
+
+    fn admit(&mut self, bytes: usize) -> Result<(), Error> {
+        duty::admit(
+            &mut self.duty_cycle,
+            &self.speed,
+            &mut self.pacing,
+            &mut self.delay,
+            bytes,
+        )
+    }
+
+    fn write_byte(&mut self, word: u8) -> Result<(), Error> {
+        pacing::write_paced(
+            &mut self.tx,
+            &mut self.pacing,
+            &mut self.duty_cycle,
+            &mut self.delay,
+            &mut self.stats,
+            word,
+        )
+    }
+}
+
+impl<RX> Hc14Rx<RX>
+where
+    RX: Read<u8>,
+{
+    /// 接收端的链路计数器，合并时累加到驱动的计数中
+    ///
+    /// The link counters of the receive half, added to the driver's counters on joining.
+    pub fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    /// 读取至换行符或缓冲区已满，串口出错时记为溢出并继续读取，与 [`Hc14::read_buffer`] 相同
+    ///
+    /// Read until a newline or until the buffer is full; a serial error is counted as an overrun
+    /// and reading goes on, as in [`Hc14::read_buffer`].
+    pub fn read_buffer<'a>(&mut self, buffer: &'a mut [u8]) -> Result<&'a [u8], Error> {
+        let count = receive::read_buffer(&mut self.rx, &mut self.stats, buffer);
+        Ok(&buffer[..count])
+    }
+
+    /// 不阻塞地读取当前可用的所有字节
+    ///
+    /// Read every byte currently available without blocking.
+    pub fn read_available<'a>(&mut self, buffer: &'a mut [u8]) -> Result<&'a [u8], Error> {
+        let count = receive::read_available(&mut self.rx, &mut self.stats, buffer)?;
+        Ok(&buffer[..count])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 丢弃写入的串口发送端(Transmit half discarding what is written)
+    struct Sink;
+
+    impl Write<u8> for Sink {
+        type Error = ();
+
+        fn write(&mut self, _word: u8) -> nb::Result<(), ()> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    /// 依次读出给定字节的接收端，读完后报错(Receive half yielding the given bytes, then an error)
+    struct Bytes(&'static [u8]);
+
+    impl Read<u8> for Bytes {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            let (&first, rest) = self.0.split_first().ok_or(nb::Error::Other(()))?;
+            self.0 = rest;
+            Ok(first)
+        }
+    }
+
+    struct Pin;
+
+    impl OutputPin for Pin {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    struct NoDelay;
+
+    impl DelayUs<u32> for NoDelay {
+        fn delay_us(&mut self, _us: u32) {}
+    }
+
+    #[test]
+    fn join_merges_the_counters_of_both_halves() {
+        let hc14 = Hc14::new(Duplex::new(Sink, Bytes(b"ab\ncd\n")), Pin, NoDelay).unwrap();
+        let (mut tx, mut rx) = hc14.split();
+
+        tx.send_buffer(b"xyz").unwrap();
+        tx.send_string("ab\0cd").unwrap();
+        let mut buffer = [0u8; 8];
+        assert_eq!(rx.read_buffer(&mut buffer).unwrap(), b"ab\n");
+        assert_eq!(rx.read_line::<8>().unwrap().0.as_str(), "cd");
+        // 与 Hc14::read_buffer 相同，串口出错时继续读取(Keeps reading on serial errors like Hc14::read_buffer)
+        assert_eq!(rx.read_buffer(&mut buffer).unwrap(), b"");
+        assert_eq!((tx.stats().bytes_sent, tx.stats().bytes_received), (5, 0));
+        assert_eq!((rx.stats().bytes_received, rx.stats().overruns), (6, 8));
+
+        let hc14 = Hc14::join(tx, rx);
+        let stats = hc14.stats();
+        assert_eq!(
+            (stats.bytes_sent, stats.bytes_received, stats.overruns),
+            (5, 6, 8)
+        );
+    }
+}
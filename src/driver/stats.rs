@@ -0,0 +1,516 @@
+//! 链路质量统计与健康状况
+//!
+//! Link quality statistics and health.
+//!
+//! 驱动在正常模式的收发路径中累计字节数、帧数、解码与 CRC 错误、串口错误(溢出)，请求/应答
+//! 类协议([`modbus`](super::modbus)、[`transfer`](crate::transfer)、[`mqttsn`](crate::mqttsn))
+//! 另外记录重试、超时和每个对端的往返时间。[`LinkHealth`] 是可以记录日志或通过无线发送的摘要。
+//!
+//! The driver accumulates bytes, frames, decode and CRC errors and serial errors (overruns) in the
+//! normal-mode send and receive paths; request/response protocols ([`modbus`](super::modbus),
+//! [`transfer`](crate::transfer), [`mqttsn`](crate::mqttsn)) also record retries, timeouts and the
+//! round-trip time per peer. [`LinkHealth`] is a summary that can be logged or sent over the radio.
+//!
+//! # Example
+//! ```rust
+//! let health = hc14.health();
+//! if health.status != HealthStatus::Good {
+//!     hprintln!("{:?} rtt {} us", health.status, health.average_rtt_us);
+//! }
+//! hc14.send_frame(&health.to_bytes()).unwrap();
+//!
+//! if let Some(peer) = hc14.stats().peer(3) {
+//!     hprintln!("unit 3: {} us ({} samples)", peer.average_us, peer.samples);
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use super::{receive::ReadOutcome, Hc14};
+use crate::Error;
+
+/// 记录往返时间的对端数量，已满时替换样本最少的对端
+///
+/// Peers whose round-trip time is tracked, the one with the fewest samples is replaced when full.
+pub const MAX_PEERS: usize = 8;
+
+/// 一个对端的往返时间
+///
+/// Round-trip time of one peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeerLatency {
+    /// 对端标识：Modbus 从站地址、MQTT-SN 网关或传输会话号
+    /// (Peer identifier: Modbus unit, MQTT-SN gateway or transfer session)
+    pub peer: u16,
+    /// 样本数(Number of samples)
+    pub samples: u32,
+    /// 最近一次(微秒)(Latest, in microseconds)
+    pub last_us: u32,
+    /// 最小值(微秒)(Minimum, in microseconds)
+    pub min_us: u32,
+    /// 最大值(微秒)(Maximum, in microseconds)
+    pub max_us: u32,
+    /// 滑动平均(微秒，新样本权重 1/8)(Moving average in microseconds, new samples weigh 1/8)
+    pub average_us: u32,
+}
+
+impl PeerLatency {
+    fn record(&mut self, us: u32) {
+        if self.samples == 0 {
+            self.min_us = us;
+            self.max_us = us;
+            self.average_us = us;
+        } else {
+            self.min_us = self.min_us.min(us);
+            self.max_us = self.max_us.max(us);
+            self.average_us = ((u64::from(self.average_us) * 7 + u64::from(us)) / 8) as u32;
+        }
+        self.last_us = us;
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// 合并同一对端的另一组样本，平均值按样本数加权，`other` 视为较新的样本
+    ///
+    /// Merge another set of samples of the same peer, averages are weighted by sample count and
+    /// `other` is taken as the newer samples.
+    fn merge(&mut self, other: &PeerLatency) {
+        if other.samples == 0 {
+            return;
+        }
+        if self.samples == 0 {
+            *self = *other;
+            return;
+        }
+        let samples = u64::from(self.samples) + u64::from(other.samples);
+        self.average_us = ((u64::from(self.average_us) * u64::from(self.samples)
+            + u64::from(other.average_us) * u64::from(other.samples))
+            / samples) as u32;
+        self.min_us = self.min_us.min(other.min_us);
+        self.max_us = self.max_us.max(other.max_us);
+        self.last_us = other.last_us;
+        self.samples = self.samples.saturating_add(other.samples);
+    }
+}
+
+/// 链路计数器，计数在溢出时回绕
+///
+/// Link counters, counts wrap around on overflow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// 发送的字节数(Bytes sent)
+    pub bytes_sent: u32,
+    /// 接收的字节数(Bytes received)
+    pub bytes_received: u32,
+    /// 发送的帧数(Frames sent)
+    pub frames_sent: u32,
+    /// 完整接收的帧数(Frames received intact)
+    pub frames_received: u32,
+    /// 无法解码的帧(Frames that could not be decoded)
+    pub frame_errors: u32,
+    /// CRC 校验失败的帧或数据块(Frames or blocks failing their CRC)
+    pub crc_errors: u32,
+    /// 重发的请求或数据(Requests or data sent again)
+    pub retries: u32,
+    /// 超时未收到应答的请求(Requests left without an answer in time)
+    pub timeouts: u32,
+    /// 串口读取错误，通常是溢出(Serial read errors, usually overruns)
+    pub overruns: u32,
+    peers: [PeerLatency; MAX_PEERS],
+}
+
+impl LinkStats {
+    /// 记录与 `peer` 的一次往返时间(微秒)，自定义协议也可以调用
+    ///
+    /// Record one round trip with `peer` in microseconds, custom protocols may call it too.
+    pub fn record_round_trip(&mut self, peer: u16, us: u32) {
+        self.slot(peer).record(us);
+    }
+
+    /// `peer` 的记录，没有时替换样本最少的对端
+    ///
+    /// The record of `peer`, replacing the peer with the fewest samples when there is none.
+    fn slot(&mut self, peer: u16) -> &mut PeerLatency {
+        let slot = match self
+            .peers
+            .iter()
+            .position(|p| p.samples > 0 && p.peer == peer)
+        {
+            Some(i) => i,
+            None => {
+                let (i, _) = self
+                    .peers
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, p)| p.samples)
+                    .unwrap_or((0, &self.peers[0]));
+                self.peers[i] = PeerLatency {
+                    peer,
+                    ..PeerLatency::default()
+                };
+                i
+            }
+        };
+        &mut self.peers[slot]
+    }
+
+    /// `peer` 的往返时间
+    ///
+    /// Round-trip time of `peer`.
+    pub fn peer(&self, peer: u16) -> Option<&PeerLatency> {
+        self.peers().find(|p| p.peer == peer)
+    }
+
+    /// 所有记录了往返时间的对端
+    ///
+    /// Every peer with a recorded round-trip time.
+    pub fn peers(&self) -> impl Iterator<Item = &PeerLatency> {
+        self.peers.iter().filter(|p| p.samples > 0)
+    }
+
+    /// 统计一次读取：读到的字节，或一次串口错误
+    ///
+    /// Count one read: the bytes read, or one serial error.
+    pub(crate) fn on_read(
+        &mut self,
+        result: Result<ReadOutcome, Error>,
+    ) -> Result<ReadOutcome, Error> {
+        match &result {
+            Ok(outcome) => add(&mut self.bytes_received, outcome.len + outcome.dropped),
+            Err(Error::Read) => add(&mut self.overruns, 1),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// 统计一次读取一行：读到的字节加上换行符，或一次串口错误
+    ///
+    /// Count one line read: the bytes read plus the newline, or one serial error.
+    pub(crate) fn on_line<T>(
+        &mut self,
+        result: Result<(T, ReadOutcome), Error>,
+    ) -> Result<(T, ReadOutcome), Error> {
+        match &result {
+            Ok((_, outcome)) => add(&mut self.bytes_received, outcome.len + outcome.dropped + 1),
+            Err(Error::Read) => add(&mut self.overruns, 1),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// 统计一次读取：读到的字节数，或一次串口错误
+    ///
+    /// Count one read: the number of bytes read, or one serial error.
+    pub(crate) fn on_bytes(&mut self, result: Result<usize, Error>) -> Result<usize, Error> {
+        match &result {
+            Ok(len) => add(&mut self.bytes_received, *len),
+            Err(Error::Read) => add(&mut self.overruns, 1),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// 累加另一组计数器，例如拆分后接收端的计数；往返时间按对端合并，平均值按样本数加权
+    ///
+    /// Add up another set of counters, e.g. those of the receive half after a split; round-trip
+    /// times are merged per peer, averages weighted by sample count.
+    pub(crate) fn merge(&mut self, other: &LinkStats) {
+        add(&mut self.bytes_sent, other.bytes_sent as usize);
+        add(&mut self.bytes_received, other.bytes_received as usize);
+        add(&mut self.frames_sent, other.frames_sent as usize);
+        add(&mut self.frames_received, other.frames_received as usize);
+        add(&mut self.frame_errors, other.frame_errors as usize);
+        add(&mut self.crc_errors, other.crc_errors as usize);
+        add(&mut self.retries, other.retries as usize);
+        add(&mut self.timeouts, other.timeouts as usize);
+        add(&mut self.overruns, other.overruns as usize);
+        for peer in other.peers() {
+            self.slot(peer.peer).merge(peer);
+        }
+    }
+
+    /// 统计一个收到的帧：完整接收或解码失败
+    ///
+    /// Count one frame received: intact or failing to decode.
+    pub(crate) fn on_frame<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        match &result {
+            Ok(_) => add(&mut self.frames_received, 1),
+            Err(Error::Crc) => add(&mut self.crc_errors, 1),
+            Err(_) => add(&mut self.frame_errors, 1),
+        }
+        result
+    }
+
+    /// 清零所有计数
+    ///
+    /// Clear every counter.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// 计算健康状况摘要
+    ///
+    /// Compute the health summary.
+    pub fn health(&self) -> LinkHealth {
+        let frames = u64::from(self.frames_received)
+            + u64::from(self.frame_errors)
+            + u64::from(self.crc_errors);
+        let (samples, total_us) = self.peers().fold((0u64, 0u64), |(n, sum), p| {
+            (
+                n + u64::from(p.samples),
+                sum + u64::from(p.average_us) * u64::from(p.samples),
+            )
+        });
+        let requests = samples + u64::from(self.timeouts);
+
+        let error_permille = permille(
+            u64::from(self.frame_errors) + u64::from(self.crc_errors),
+            frames,
+        );
+        let loss_permille = permille(u64::from(self.timeouts), requests);
+        let retry_permille = permille(u64::from(self.retries), u64::from(self.frames_sent));
+        let status = if frames == 0 && requests == 0 {
+            HealthStatus::Unknown
+        } else if error_permille >= 100 || loss_permille >= 200 {
+            HealthStatus::Poor
+        } else if error_permille >= 20 || loss_permille >= 50 || self.overruns > 0 {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Good
+        };
+        LinkHealth {
+            status,
+            error_permille,
+            loss_permille,
+            retry_permille,
+            average_rtt_us: total_us.checked_div(samples).unwrap_or(0) as u32,
+            overruns: self.overruns,
+        }
+    }
+}
+
+/// 计数器加 `n`，溢出时回绕
+///
+/// Add `n` to a counter, wrapping around on overflow.
+pub(crate) fn add(counter: &mut u32, n: usize) {
+    *counter = counter.wrapping_add(n as u32);
+}
+
+fn permille(part: u64, whole: u64) -> u16 {
+    (part * 1000).checked_div(whole).unwrap_or(0).min(1000) as u16
+}
+
+/// 链路的总体状况
+///
+/// Overall condition of the link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// 还没有流量(No traffic yet)
+    Unknown = 0,
+    /// 良好(Good)
+    Good = 1,
+    /// 出现错误、丢失或溢出(Errors, losses or overruns seen)
+    Degraded = 2,
+    /// 错误率或丢失率过高(Error or loss rate too high)
+    Poor = 3,
+}
+
+/// 链路健康状况摘要
+///
+/// Link health summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkHealth {
+    /// 总体状况(Overall condition)
+    pub status: HealthStatus,
+    /// 每千帧中的解码和 CRC 错误(Decode and CRC errors per thousand frames)
+    pub error_permille: u16,
+    /// 每千次请求中未得到应答的次数(Unanswered requests per thousand)
+    pub loss_permille: u16,
+    /// 每千帧发送中的重发次数(Resends per thousand frames sent)
+    pub retry_permille: u16,
+    /// 所有对端的平均往返时间(微秒)(Average round-trip time over all peers, in microseconds)
+    pub average_rtt_us: u32,
+    /// 串口溢出次数(Serial overruns)
+    pub overruns: u32,
+}
+
+impl LinkHealth {
+    /// 编码后的字节数
+    ///
+    /// Encoded length in bytes.
+    pub const ENCODED_LEN: usize = 15;
+
+    /// 编码为字节，便于通过无线发送
+    ///
+    /// Encode into bytes, e.g. to send it over the radio.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        bytes[0] = self.status as u8;
+        bytes[1..3].copy_from_slice(&self.error_permille.to_be_bytes());
+        bytes[3..5].copy_from_slice(&self.loss_permille.to_be_bytes());
+        bytes[5..7].copy_from_slice(&self.retry_permille.to_be_bytes());
+        bytes[7..11].copy_from_slice(&self.average_rtt_us.to_be_bytes());
+        bytes[11..15].copy_from_slice(&self.overruns.to_be_bytes());
+        bytes
+    }
+
+    /// 从字节解码
+    ///
+    /// Decode from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..Self::ENCODED_LEN)?;
+        let half = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+        let word = |at: usize| {
+            u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        let status = match bytes[0] {
+            0 => HealthStatus::Unknown,
+            1 => HealthStatus::Good,
+            2 => HealthStatus::Degraded,
+            3 => HealthStatus::Poor,
+            _ => return None,
+        };
+        Some(Self {
+            status,
+            error_permille: half(1),
+            loss_permille: half(3),
+            retry_permille: half(5),
+            average_rtt_us: word(7),
+            overruns: word(11),
+        })
+    }
+}
+
+/// 链路统计(Link statistics)
+impl<S, P, D, M, W> Hc14<S, P, D, M, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 链路计数器
+    ///
+    /// The link counters.
+    pub fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    /// 可修改的链路计数器，例如清零或记录自定义协议的往返时间
+    ///
+    /// Mutable link counters, e.g. to clear them or to record round trips of a custom protocol.
+    pub fn stats_mut(&mut self) -> &mut LinkStats {
+        &mut self.stats
+    }
+
+    /// 链路健康状况摘要
+    ///
+    /// Link health summary.
+    pub fn health(&self) -> LinkHealth {
+        self.stats.health()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(frames: u32, errors: u32, timeouts: u32) -> LinkStats {
+        let mut stats = LinkStats {
+            frames_received: frames - errors,
+            crc_errors: errors,
+            timeouts,
+            ..LinkStats::default()
+        };
+        for _ in 0..frames - timeouts {
+            stats.record_round_trip(1, 1_000);
+        }
+        stats
+    }
+
+    #[test]
+    fn health_follows_the_thresholds() {
+        assert_eq!(LinkStats::default().health().status, HealthStatus::Unknown);
+        assert_eq!(stats(100, 0, 0).health().status, HealthStatus::Good);
+        assert_eq!(stats(100, 1, 4).health().status, HealthStatus::Good);
+        // 2% 错误、5% 丢失或任何溢出(2% errors, 5% losses or any overrun)
+        assert_eq!(stats(100, 2, 0).health().status, HealthStatus::Degraded);
+        assert_eq!(stats(100, 0, 5).health().status, HealthStatus::Degraded);
+        let overrun = LinkStats {
+            overruns: 1,
+            ..stats(100, 0, 0)
+        };
+        assert_eq!(overrun.health().status, HealthStatus::Degraded);
+        // 10% 错误或 20% 丢失(10% errors or 20% losses)
+        assert_eq!(stats(100, 10, 0).health().status, HealthStatus::Poor);
+        assert_eq!(stats(100, 0, 20).health().status, HealthStatus::Poor);
+
+        let health = stats(100, 10, 20).health();
+        assert_eq!((health.error_permille, health.loss_permille), (100, 200));
+        assert_eq!(health.average_rtt_us, 1_000);
+    }
+
+    #[test]
+    fn health_round_trips_through_bytes() {
+        let health = LinkHealth {
+            status: HealthStatus::Degraded,
+            error_permille: 25,
+            loss_permille: 1000,
+            retry_permille: 7,
+            average_rtt_us: 123_456,
+            overruns: 0x0102_0304,
+        };
+        let bytes = health.to_bytes();
+        assert_eq!(LinkHealth::from_bytes(&bytes), Some(health));
+        assert_eq!(
+            LinkHealth::from_bytes(&bytes[..LinkHealth::ENCODED_LEN - 1]),
+            None
+        );
+
+        let mut bad = bytes;
+        bad[0] = 4;
+        assert_eq!(LinkHealth::from_bytes(&bad), None);
+    }
+
+    #[test]
+    fn the_peer_with_the_fewest_samples_is_evicted() {
+        let mut stats = LinkStats::default();
+        for peer in 0..MAX_PEERS as u16 {
+            for _ in 0..=peer {
+                stats.record_round_trip(peer, 100);
+            }
+        }
+        assert_eq!(stats.peers().count(), MAX_PEERS);
+
+        stats.record_round_trip(100, 500);
+        assert_eq!(stats.peers().count(), MAX_PEERS);
+        assert!(stats.peer(0).is_none());
+        let peer = stats.peer(100).unwrap();
+        assert_eq!((peer.samples, peer.average_us), (1, 500));
+        assert_eq!(stats.peer(1).unwrap().samples, 2);
+    }
+
+    #[test]
+    fn merge_combines_the_latency_of_each_peer() {
+        let mut tx = LinkStats::default();
+        let mut rx = LinkStats::default();
+        for us in [100, 300, 200] {
+            tx.record_round_trip(1, us);
+        }
+        rx.record_round_trip(1, 1_000);
+        rx.record_round_trip(2, 50);
+        let tx_average = tx.peer(1).unwrap().average_us;
+
+        tx.merge(&rx);
+        let peer = tx.peer(1).unwrap();
+        assert_eq!(peer.samples, 4);
+        assert_eq!(
+            (peer.min_us, peer.max_us, peer.last_us),
+            (100, 1_000, 1_000)
+        );
+        assert_eq!(peer.average_us, (tx_average * 3 + 1_000) / 4);
+        assert_eq!(*tx.peer(2).unwrap(), *rx.peer(2).unwrap());
+    }
+}
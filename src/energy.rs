@@ -0,0 +1,287 @@
+//! 电池寿命与能耗估算
+//!
+//! Battery life and energy consumption estimation.
+//!
+//! 模型把一次上报分为三段：发送(电流随 [`TransmissionPower`] 在两个实测值之间按输出功率插值，
+//! 时间取自 [`Speed::get_airtime_ms`])、接收(唤醒和等待应答的时间)以及其余时间的睡眠或持续
+//! 接收。电流数据由使用者根据自己的模块和电源测得，结果只是估算，实际电池容量还受温度、
+//! 自放电和截止电压影响，可以传入打过折扣的容量。
+//!
+//! The model splits one report into three parts: transmitting (the current is interpolated by
+//! output power between two measured figures according to [`TransmissionPower`], the time comes
+//! from [`Speed::get_airtime_ms`]), receiving (time spent waking up and waiting for an answer), and
+//! sleeping or listening for the rest of the time. Current figures are measured by the user for
+//! their own module and supply, the results are estimates only; real battery capacity also
+//! depends on temperature, self-discharge and cut-off voltage, so a derated capacity may be passed.
+//!
+//! # Example
+//! ```rust
+//! let model = EnergyModel::new(CurrentProfile {
+//!     tx_min_ma: 40.0,
+//!     tx_max_ma: 100.0,
+//!     rx_ma: 16.0,
+//!     sleep_ua: 22.0,
+//! });
+//! let schedule = Schedule {
+//!     interval_s: 600.0,
+//!     message_bytes: 24,
+//!     wake_ms: 250.0,
+//!     listen_ms: 1_000.0,
+//!     sleeps: true,
+//! };
+//! let estimate = model.estimate(&parameters, &schedule, 2_000.0);
+//! hprintln!("{} uAh per report, {} days", estimate.charge_per_message_uah, estimate.life_days());
+//! ```
+use crate::setting::{parameters::Parameters, power::TransmissionPower};
+
+#[cfg(doc)]
+use crate::setting::speed::Speed;
+
+/// 6 dBm 到 20 dBm 每个等级的输出功率(毫瓦)
+///
+/// Output power per level from 6 dBm to 20 dBm, in milliwatts.
+const OUTPUT_MW: [f32; 15] = [
+    3.98, 5.01, 6.31, 7.94, 10.0, 12.59, 15.85, 19.95, 25.12, 31.62, 39.81, 50.12, 63.10, 79.43,
+    100.0,
+];
+
+/// 模块各状态下的电流，由使用者测得
+///
+/// Module current per state, as measured by the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurrentProfile {
+    /// 以 6 dBm 发送时的电流(毫安)(Current transmitting at 6 dBm, in milliamperes)
+    pub tx_min_ma: f32,
+    /// 以 20 dBm 发送时的电流(毫安)(Current transmitting at 20 dBm, in milliamperes)
+    pub tx_max_ma: f32,
+    /// 接收时的电流(毫安)(Current while receiving, in milliamperes)
+    pub rx_ma: f32,
+    /// 睡眠或断电时的电流(微安)(Current asleep or powered off, in microamperes)
+    pub sleep_ua: f32,
+}
+
+impl CurrentProfile {
+    /// 以 `power` 发送时的电流(毫安)，按输出功率在两个实测值之间插值
+    ///
+    /// Current transmitting at `power` in milliamperes, interpolated by output power between the two
+    /// measured figures.
+    pub fn tx_ma(&self, power: &TransmissionPower) -> f32 {
+        let index = usize::from(power.get_power_dbm().clamp(6, 20) - 6);
+        let share = (OUTPUT_MW[index] - OUTPUT_MW[0]) / (OUTPUT_MW[14] - OUTPUT_MW[0]);
+        self.tx_min_ma + (self.tx_max_ma - self.tx_min_ma) * share
+    }
+}
+
+/// 上报计划：每个周期唤醒、发送一条消息、等待应答，然后睡眠或继续接收
+///
+/// Reporting schedule: every period the node wakes up, sends one message, waits for an answer and
+/// then sleeps or keeps listening.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    /// 两次上报之间的时间(秒)(Time between reports, in seconds)
+    pub interval_s: f32,
+    /// 每条消息的字节数(Bytes per message)
+    pub message_bytes: usize,
+    /// 发送前唤醒模块所需的时间(毫秒)(Time needed to wake the module before sending, in milliseconds)
+    pub wake_ms: f32,
+    /// 发送后等待应答或下行数据的时间(毫秒)(Time waiting for an answer or downlink after sending, in milliseconds)
+    pub listen_ms: f32,
+    /// 两次上报之间是否睡眠，否则一直接收(Whether the module sleeps between reports, otherwise it keeps receiving)
+    pub sleeps: bool,
+}
+
+/// 估算结果
+///
+/// Estimation results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// 每条消息的空中时间(毫秒)(Air time per message, in milliseconds)
+    pub airtime_ms: u32,
+    /// 发送电流(毫安)(Transmit current, in milliamperes)
+    pub tx_ma: f32,
+    /// 每次上报的发送和接收电量(微安时)，不含睡眠
+    /// (Charge for transmitting and receiving per report, in microampere-hours, sleep excluded)
+    pub charge_per_message_uah: f32,
+    /// 平均电流(毫安)(Average current, in milliamperes)
+    pub average_ma: f32,
+    /// 电池寿命(小时)(Battery life, in hours)
+    pub life_hours: f32,
+}
+
+impl Estimate {
+    /// 电池寿命(天)
+    ///
+    /// Battery life in days.
+    pub fn life_days(&self) -> f32 {
+        self.life_hours / 24.0
+    }
+}
+
+/// 能耗模型
+///
+/// Energy model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyModel {
+    currents: CurrentProfile,
+}
+
+impl EnergyModel {
+    /// 以实测电流构建模型
+    ///
+    /// Build the model from measured currents.
+    pub const fn new(currents: CurrentProfile) -> Self {
+        Self { currents }
+    }
+
+    /// 模型使用的电流
+    ///
+    /// The currents the model uses.
+    pub fn currents(&self) -> &CurrentProfile {
+        &self.currents
+    }
+
+    /// 以 `parameters` 发送 `bytes` 字节消耗的电量(微安时)
+    ///
+    /// Charge consumed sending `bytes` bytes with `parameters`, in microampere-hours.
+    pub fn message_charge_uah(&self, parameters: &Parameters, bytes: usize) -> f32 {
+        charge_uah(
+            self.currents.tx_ma(&parameters.power),
+            parameters.speed.get_airtime_ms(bytes) as f32,
+        )
+    }
+
+    /// 估算按 `schedule` 上报时的平均电流和容量为 `capacity_mah` 毫安时的电池寿命；周期短于
+    /// 一次上报所需的时间时，模型假定模块一直处于工作状态
+    ///
+    /// Estimate the average current reporting on `schedule` and the life of a battery holding
+    /// `capacity_mah` milliampere-hours; with a period shorter than one report takes, the model
+    /// assumes the module never rests.
+    pub fn estimate(
+        &self,
+        parameters: &Parameters,
+        schedule: &Schedule,
+        capacity_mah: f32,
+    ) -> Estimate {
+        let airtime = parameters.speed.get_airtime_ms(schedule.message_bytes);
+        let tx_ma = self.currents.tx_ma(&parameters.power);
+        let awake_ms = schedule.wake_ms + schedule.listen_ms;
+        let active_ms = airtime as f32 + awake_ms;
+        let period_ms = (schedule.interval_s * 1000.0).max(active_ms);
+
+        let message = charge_uah(tx_ma, airtime as f32) + charge_uah(self.currents.rx_ma, awake_ms);
+        let rest_ma = if schedule.sleeps {
+            self.currents.sleep_ua / 1000.0
+        } else {
+            self.currents.rx_ma
+        };
+        let cycle = message + charge_uah(rest_ma, period_ms - active_ms);
+        // 微安时/毫秒 换算为毫安(microampere-hours per millisecond into milliamperes)
+        let average_ma = if period_ms > 0.0 {
+            cycle * 3600.0 / period_ms
+        } else {
+            rest_ma
+        };
+        Estimate {
+            airtime_ms: airtime,
+            tx_ma,
+            charge_per_message_uah: message,
+            average_ma,
+            life_hours: if average_ma > 0.0 {
+                capacity_mah / average_ma
+            } else {
+                f32::INFINITY
+            },
+        }
+    }
+}
+
+/// 毫安乘毫秒换算为微安时
+///
+/// Milliamperes times milliseconds into microampere-hours.
+fn charge_uah(ma: f32, ms: f32) -> f32 {
+    ma * ms / 3600.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setting::speed::Speed;
+
+    const CURRENTS: CurrentProfile = CurrentProfile {
+        tx_min_ma: 40.0,
+        tx_max_ma: 100.0,
+        rx_ma: 16.0,
+        sleep_ua: 22.0,
+    };
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= b.abs() * 1e-4
+    }
+
+    fn schedule(interval_s: f32, sleeps: bool) -> Schedule {
+        Schedule {
+            interval_s,
+            message_bytes: 24,
+            wake_ms: 250.0,
+            listen_ms: 1_000.0,
+            sleeps,
+        }
+    }
+
+    fn parameters(dbm: u8) -> Parameters {
+        Parameters {
+            power: TransmissionPower::new(dbm).unwrap(),
+            speed: Speed::S3,
+            ..Parameters::default()
+        }
+    }
+
+    #[test]
+    fn tx_current_follows_the_output_power() {
+        let tx = |dbm| CURRENTS.tx_ma(&TransmissionPower::new(dbm).unwrap());
+        assert!(close(tx(6), 40.0));
+        assert!(close(tx(20), 100.0));
+        // 13 dBm 约为 20 mW，只占 6 到 20 dBm 输出功率差的六分之一
+        // (13 dBm is about 20 mW, only a sixth of the way from 6 to 20 dBm in output power)
+        assert!(close(tx(13), 40.0 + 60.0 * (19.95 - 3.98) / (100.0 - 3.98)));
+    }
+
+    #[test]
+    fn sleeping_between_reports_saves_the_receive_current() {
+        let model = EnergyModel::new(CURRENTS);
+        let parameters = parameters(20);
+        let airtime = Speed::S3.get_airtime_ms(24) as f32;
+        let message = (100.0 * airtime + 16.0 * 1_250.0) / 3600.0;
+        let rest_ms = 600_000.0 - airtime - 1_250.0;
+
+        let sleeping = model.estimate(&parameters, &schedule(600.0, true), 2_000.0);
+        assert_eq!(sleeping.airtime_ms, airtime as u32);
+        assert!(close(sleeping.tx_ma, 100.0));
+        assert!(close(sleeping.charge_per_message_uah, message));
+        let average = (message + 0.022 * rest_ms / 3600.0) * 3600.0 / 600_000.0;
+        assert!(close(sleeping.average_ma, average));
+        assert!(close(sleeping.life_hours, 2_000.0 / average));
+        assert!(close(sleeping.life_days(), 2_000.0 / average / 24.0));
+
+        let listening = model.estimate(&parameters, &schedule(600.0, false), 2_000.0);
+        assert!(close(listening.charge_per_message_uah, message));
+        let average = (message + 16.0 * rest_ms / 3600.0) * 3600.0 / 600_000.0;
+        assert!(close(listening.average_ma, average));
+        assert!(listening.life_hours < sleeping.life_hours / 10.0);
+    }
+
+    #[test]
+    fn short_intervals_keep_the_module_busy() {
+        let model = EnergyModel::new(CURRENTS);
+        let parameters = parameters(6);
+        let airtime = Speed::S3.get_airtime_ms(24) as f32;
+        let active_ms = airtime + 1_250.0;
+        let busy = (40.0 * airtime + 16.0 * 1_250.0) / active_ms;
+
+        for interval_s in [0.0, 0.5] {
+            let estimate = model.estimate(&parameters, &schedule(interval_s, true), 2_000.0);
+            assert!(close(estimate.average_ma, busy));
+            assert!(close(estimate.life_hours, 2_000.0 / busy));
+        }
+    }
+}
@@ -0,0 +1,943 @@
+//! MQTT-SN 网关：将无线上的 [`mqttsn`](crate::mqttsn) 客户端桥接到 MQTT 代理
+//!
+//! MQTT-SN gateway: bridges [`mqttsn`](crate::mqttsn) clients on the radio to an MQTT broker.
+//!
+//! 每个无线客户端对应一个到代理的 MQTT 3.1.1 连接(透明网关)。只支持普通主题名称，
+//! 不支持通配符订阅。到代理的连接在后台线程中建立，期间网关照常处理其他节点的无线消息，
+//! 连接建立后才应答 CONNACK。主机端程序 `mqttsn_gateway` 在串口上的 HC-14 上运行该网关。
+//!
+//! Every radio client gets its own MQTT 3.1.1 connection to the broker (transparent gateway).
+//! Only plain topic names are supported, wildcard subscriptions are not. Broker connections are
+//! set up on a background thread while the gateway keeps serving the other nodes' radio traffic,
+//! and the CONNACK is only sent once the connection is up. The host-side `mqttsn_gateway` binary
+//! runs this gateway on a serial-attached HC-14.
+//!
+//! # Example
+//! ```rust
+//! let hc14 = Hc14::new(HostSerial::open("/dev/ttyUSB0").unwrap(), NoPin, StdDelay).unwrap();
+//! let mut gateway = Gateway::new(hc14, "127.0.0.1:1883");
+//! loop {
+//!     gateway.poll(20_000);
+//! }
+//! ```
+use std::{
+    collections::HashMap,
+    io::{self, Read as _, Write as _},
+    net::TcpStream,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use crate::{
+    driver::{frame::MAX_FRAME_SIZE, power::NoPowerPin, Hc14, Normal},
+    mqttsn::{self, Message, QoS},
+    Error,
+};
+
+/// 到代理的心跳周期(秒)(Keep-alive towards the broker, in seconds)
+const BROKER_KEEP_ALIVE: u16 = 60;
+
+/// 等待代理 CONNACK 的时间(Wait for the broker's CONNACK)
+const BROKER_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// ---------------------------------------------------------------------------------------------
+// MQTT 3.1.1
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+/// 剩余长度最多占用的字节数(Most bytes the remaining length may take)
+const MAX_REMAINING_LEN_BYTES: usize = 4;
+
+/// 组装一个 MQTT 报文：固定头、剩余长度和报文体
+///
+/// Assemble an MQTT packet: fixed header, remaining length and body.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+fn put_str(body: &mut Vec<u8>, s: &str) {
+    body.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    body.extend_from_slice(s.as_bytes());
+}
+
+/// 从接收缓冲区中取出一个完整的报文，数据不足时返回 `Ok(None)`；
+/// 剩余长度超过 4 字节时返回错误，连接应当断开
+///
+/// Take one whole packet out of the receive buffer, returns `Ok(None)` while data is missing;
+/// a remaining length over 4 bytes is a protocol error and the connection should be dropped.
+fn take_packet(inbox: &mut Vec<u8>) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut len: usize = 0;
+    let mut pos = 1;
+    loop {
+        if pos > MAX_REMAINING_LEN_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "remaining length longer than 4 bytes",
+            ));
+        }
+        let Some(&byte) = inbox.get(pos) else {
+            return Ok(None);
+        };
+        len |= usize::from(byte & 0x7F) << (7 * (pos - 1));
+        pos += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if inbox.len() < pos + len {
+        return Ok(None);
+    }
+    let header = inbox[0];
+    let body = inbox[pos..pos + len].to_vec();
+    inbox.drain(..pos + len);
+    Ok(Some((header, body)))
+}
+
+/// 一个到代理的连接
+///
+/// One connection to the broker.
+struct Broker {
+    stream: TcpStream,
+    inbox: Vec<u8>,
+    next_id: u16,
+    last_sent: Instant,
+}
+
+impl Broker {
+    /// 连接代理并等待 CONNACK，会阻塞，只在后台线程中调用
+    ///
+    /// Connect to the broker and wait for the CONNACK; blocks, so only call it on a background
+    /// thread.
+    fn connect(addr: &str, client_id: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(BROKER_CONNECT_TIMEOUT))?;
+        let mut broker = Self {
+            stream,
+            inbox: Vec::new(),
+            next_id: 0,
+            last_sent: Instant::now(),
+        };
+
+        let mut body = Vec::new();
+        put_str(&mut body, "MQTT");
+        body.push(4);
+        body.push(0x02);
+        body.extend_from_slice(&BROKER_KEEP_ALIVE.to_be_bytes());
+        put_str(&mut body, client_id);
+        broker.send(&packet(CONNECT, &body))?;
+
+        let mut chunk = [0u8; 64];
+        let (header, body) = loop {
+            if let Some(packet) = take_packet(&mut broker.inbox)? {
+                break packet;
+            }
+            let n = broker.stream.read(&mut chunk)?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            broker.inbox.extend_from_slice(&chunk[..n]);
+        };
+        if header != CONNACK || body.get(1) != Some(&0) {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "broker refused the connection",
+            ));
+        }
+        broker.stream.set_nonblocking(true)?;
+        Ok(broker)
+    }
+
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.last_sent = Instant::now();
+        self.stream.write_all(packet)
+    }
+
+    fn next_id(&mut self) -> u16 {
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.next_id
+    }
+
+    fn publish(&mut self, topic: &str, data: &[u8], qos: QoS, retain: bool) -> io::Result<u16> {
+        let mut body = Vec::new();
+        put_str(&mut body, topic);
+        let mut header = PUBLISH;
+        let mut id = 0;
+        if qos == QoS::AtLeastOnce {
+            header |= 0x02;
+            id = self.next_id();
+            body.extend_from_slice(&id.to_be_bytes());
+        }
+        if retain {
+            header |= 0x01;
+        }
+        body.extend_from_slice(data);
+        self.send(&packet(header, &body))?;
+        Ok(id)
+    }
+
+    fn subscribe(&mut self, topic: &str, qos: QoS) -> io::Result<u16> {
+        let id = self.next_id();
+        let mut body = id.to_be_bytes().to_vec();
+        put_str(&mut body, topic);
+        body.push(u8::from(qos == QoS::AtLeastOnce));
+        self.send(&packet(SUBSCRIBE, &body))?;
+        Ok(id)
+    }
+
+    /// 不阻塞地读取一个报文，连接断开或协议错误时返回错误
+    ///
+    /// Read one packet without blocking, returns an error once the connection is gone or on a
+    /// protocol error.
+    fn poll(&mut self) -> io::Result<Option<(u8, Vec<u8>)>> {
+        let mut chunk = [0u8; 512];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.inbox.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.last_sent.elapsed() > Duration::from_secs(u64::from(BROKER_KEEP_ALIVE / 2)) {
+            self.send(&packet(PINGREQ, &[]))?;
+        }
+        take_packet(&mut self.inbox)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// 网关(Gateway)
+
+/// 一个无线客户端及其代理连接
+///
+/// One radio client and its broker connection.
+struct Client {
+    broker: Broker,
+    /// 主题 ID 为下标加一(The topic ID is the index plus one)
+    topics: Vec<String>,
+    /// 代理报文 ID -> (主题 ID, MQTT-SN 消息 ID)
+    pending_publishes: HashMap<u16, (u16, u16)>,
+    /// 代理报文 ID -> (主题 ID, MQTT-SN 消息 ID, QoS)
+    pending_subscribes: HashMap<u16, (u16, u16, QoS)>,
+    next_msg_id: u16,
+}
+
+impl Client {
+    fn new(broker: Broker) -> Self {
+        Self {
+            broker,
+            topics: Vec::new(),
+            pending_publishes: HashMap::new(),
+            pending_subscribes: HashMap::new(),
+            next_msg_id: 0,
+        }
+    }
+
+    fn topic_id(&mut self, name: &str) -> u16 {
+        let index = match self.topics.iter().position(|topic| topic == name) {
+            Some(index) => index,
+            None => {
+                self.topics.push(name.to_string());
+                self.topics.len() - 1
+            }
+        };
+        index as u16 + 1
+    }
+
+    fn topic_name(&self, topic_id: u16) -> Option<&str> {
+        let index = usize::from(topic_id).checked_sub(1)?;
+        self.topics.get(index).map(String::as_str)
+    }
+}
+
+/// 后台线程建立的代理连接及其客户端 ID
+///
+/// A broker connection set up on a background thread, with its client ID.
+type Connecting = Receiver<io::Result<(Broker, String)>>;
+
+/// MQTT-SN 网关：无线客户端及其代理连接
+///
+/// MQTT-SN gateway: the radio clients and their broker connections.
+pub struct Gateway<S, P, D, W = NoPowerPin>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    radio: Hc14<S, P, D, Normal, W>,
+    broker_addr: String,
+    clients: HashMap<u8, Client>,
+    connecting: HashMap<u8, Connecting>,
+}
+
+impl<S, P, D, W> Gateway<S, P, D, W>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    /// 由正常模式驱动和代理地址(`host:port`)构建网关
+    ///
+    /// Build a gateway from the normal-mode driver and the broker address (`host:port`).
+    pub fn new(radio: Hc14<S, P, D, Normal, W>, broker_addr: impl Into<String>) -> Self {
+        Self {
+            radio,
+            broker_addr: broker_addr.into(),
+            clients: HashMap::new(),
+            connecting: HashMap::new(),
+        }
+    }
+
+    /// 在 `timeout_us` 微秒内等待并处理一个无线帧，然后处理代理的数据
+    ///
+    /// Wait up to `timeout_us` microseconds for one radio frame and handle it, then serve the
+    /// brokers.
+    pub fn poll(&mut self, timeout_us: u32) {
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        match self.radio.receive_frame_timeout(&mut buffer, timeout_us) {
+            Ok(frame) => match mqttsn::decapsulate(frame) {
+                Some((node, message)) => self.on_radio(node, message),
+                None => eprintln!("ignoring frame {frame:02x?}"),
+            },
+            Err(Error::Timeout) => {}
+            Err(e) => eprintln!("radio: {e:?}"),
+        }
+        self.poll_brokers();
+    }
+
+    /// 处理节点 `node` 发来的一条消息
+    ///
+    /// Handle one message from node `node`.
+    pub fn on_radio(&mut self, node: u8, message: Message) {
+        let Gateway {
+            radio,
+            broker_addr,
+            clients,
+            connecting,
+        } = self;
+
+        if let Message::Connect { client_id, .. } = message {
+            clients.remove(&node);
+            let client_id = if client_id.is_empty() {
+                format!("hc14-{node}")
+            } else {
+                client_id.to_string()
+            };
+            // 连接在后台建立，不阻塞其他节点的无线消息；之前未完成的连接被丢弃
+            // Connect in the background so other nodes' radio traffic is not stalled; an earlier
+            // connection still being set up is dropped
+            let (tx, rx) = mpsc::channel();
+            let addr = broker_addr.clone();
+            thread::spawn(move || {
+                let result = Broker::connect(&addr, &client_id).map(|broker| (broker, client_id));
+                let _ = tx.send(result);
+            });
+            connecting.insert(node, rx);
+            return;
+        }
+        if message == Message::PingReq {
+            send(radio, node, &Message::PingResp);
+            return;
+        }
+        if connecting.contains_key(&node) {
+            // 客户端仍在等待 CONNACK(The client is still waiting for its CONNACK)
+            return;
+        }
+
+        let Some(client) = clients.get_mut(&node) else {
+            // 未连接的客户端(Client not connected)
+            send(radio, node, &Message::Disconnect { duration: None });
+            return;
+        };
+        let result = match message {
+            Message::Register {
+                msg_id, topic_name, ..
+            } => {
+                let topic_id = client.topic_id(topic_name);
+                let ack = Message::RegAck {
+                    topic_id,
+                    msg_id,
+                    return_code: mqttsn::ACCEPTED,
+                };
+                send(radio, node, &ack);
+                Ok(())
+            }
+            Message::Publish {
+                qos,
+                retain,
+                topic_id,
+                msg_id,
+                data,
+                ..
+            } => match client.topic_name(topic_id).map(str::to_string) {
+                Some(topic) => client.broker.publish(&topic, data, qos, retain).map(|id| {
+                    if qos == QoS::AtLeastOnce {
+                        client.pending_publishes.insert(id, (topic_id, msg_id));
+                    }
+                }),
+                None => {
+                    let ack = Message::PubAck {
+                        topic_id,
+                        msg_id,
+                        return_code: mqttsn::INVALID_TOPIC_ID,
+                    };
+                    send(radio, node, &ack);
+                    Ok(())
+                }
+            },
+            Message::Subscribe {
+                qos,
+                msg_id,
+                topic_name,
+                ..
+            } => {
+                if topic_name.contains(['+', '#']) {
+                    let ack = Message::SubAck {
+                        qos,
+                        topic_id: 0,
+                        msg_id,
+                        return_code: mqttsn::NOT_SUPPORTED,
+                    };
+                    send(radio, node, &ack);
+                    Ok(())
+                } else {
+                    let topic_id = client.topic_id(topic_name);
+                    client.broker.subscribe(topic_name, qos).map(|id| {
+                        client
+                            .pending_subscribes
+                            .insert(id, (topic_id, msg_id, qos));
+                    })
+                }
+            }
+            Message::Disconnect { .. } => {
+                let _ = client.broker.send(&packet(DISCONNECT, &[]));
+                clients.remove(&node);
+                println!("node {node}: disconnected");
+                send(radio, node, &Message::Disconnect { duration: None });
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("node {node}: broker connection lost: {e}");
+            clients.remove(&node);
+            send(radio, node, &Message::Disconnect { duration: None });
+        }
+    }
+
+    /// 完成后台建立的连接并应答 CONNACK，然后不阻塞地处理各代理连接收到的报文
+    ///
+    /// Complete the connections set up in the background and answer their CONNACK, then handle
+    /// the packets each broker connection received, without blocking.
+    pub fn poll_brokers(&mut self) {
+        let Gateway {
+            radio,
+            clients,
+            connecting,
+            ..
+        } = self;
+        connecting.retain(|&node, pending| {
+            let return_code = match pending.try_recv() {
+                Err(TryRecvError::Empty) => return true,
+                Ok(Ok((broker, client_id))) => {
+                    println!("node {node}: connected as {client_id}");
+                    clients.insert(node, Client::new(broker));
+                    mqttsn::ACCEPTED
+                }
+                Ok(Err(e)) => {
+                    eprintln!("node {node}: broker connection failed: {e}");
+                    mqttsn::CONGESTION
+                }
+                Err(TryRecvError::Disconnected) => mqttsn::CONGESTION,
+            };
+            send(radio, node, &Message::ConnAck { return_code });
+            false
+        });
+
+        let mut lost = Vec::new();
+        for (&node, client) in clients.iter_mut() {
+            loop {
+                match client.broker.poll() {
+                    Ok(Some((header, body))) => {
+                        if let Err(e) = on_broker(radio, node, client, header, &body) {
+                            eprintln!("node {node}: broker connection lost: {e}");
+                            lost.push(node);
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        eprintln!("node {node}: broker connection lost: {e}");
+                        lost.push(node);
+                        break;
+                    }
+                }
+            }
+        }
+        for node in lost {
+            clients.remove(&node);
+            send(radio, node, &Message::Disconnect { duration: None });
+        }
+    }
+}
+
+fn send<S, P, D, W>(radio: &mut Hc14<S, P, D, Normal, W>, node: u8, message: &Message)
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    let mut frame = [0u8; MAX_FRAME_SIZE];
+    let result = mqttsn::encapsulate(node, message, &mut frame)
+        .ok_or(Error::MessageTooLarge)
+        .and_then(|len| radio.send_frame(&frame[..len]));
+    if let Err(e) = result {
+        eprintln!("node {node}: cannot send {message:?}: {e:?}");
+    }
+}
+
+fn on_broker<S, P, D, W>(
+    radio: &mut Hc14<S, P, D, Normal, W>,
+    node: u8,
+    client: &mut Client,
+    header: u8,
+    body: &[u8],
+) -> io::Result<()>
+where
+    S: Read<u8> + Write<u8>,
+    P: OutputPin,
+    D: DelayUs<u32>,
+    W: OutputPin,
+{
+    let id_at = |at: usize| {
+        body.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    match header & 0xF0 {
+        PUBLISH => {
+            let Some(topic_len) = id_at(0).map(usize::from) else {
+                return Ok(());
+            };
+            let Some(topic) = body
+                .get(2..2 + topic_len)
+                .and_then(|t| std::str::from_utf8(t).ok())
+            else {
+                return Ok(());
+            };
+            let qos = (header >> 1) & 0x03;
+            let mut pos = 2 + topic_len;
+            if qos > 0 {
+                if let Some(id) = id_at(pos) {
+                    client.broker.send(&packet(PUBACK, &id.to_be_bytes()))?;
+                }
+                pos += 2;
+            }
+            let Some(topic_id) = client.topics.iter().position(|t| t == topic) else {
+                return Ok(());
+            };
+            let qos = if qos > 0 {
+                QoS::AtLeastOnce
+            } else {
+                QoS::AtMostOnce
+            };
+            client.next_msg_id = client.next_msg_id.wrapping_add(1).max(1);
+            let publish = Message::Publish {
+                dup: false,
+                qos,
+                retain: header & 0x01 != 0,
+                topic_id: topic_id as u16 + 1,
+                msg_id: if qos == QoS::AtLeastOnce {
+                    client.next_msg_id
+                } else {
+                    0
+                },
+                data: body.get(pos..).unwrap_or_default(),
+            };
+            send(radio, node, &publish);
+        }
+        PUBACK => {
+            if let Some((topic_id, msg_id)) =
+                id_at(0).and_then(|id| client.pending_publishes.remove(&id))
+            {
+                let ack = Message::PubAck {
+                    topic_id,
+                    msg_id,
+                    return_code: mqttsn::ACCEPTED,
+                };
+                send(radio, node, &ack);
+            }
+        }
+        SUBACK => {
+            if let Some((topic_id, msg_id, qos)) =
+                id_at(0).and_then(|id| client.pending_subscribes.remove(&id))
+            {
+                let granted = body.get(2).copied().unwrap_or(0x80);
+                let ack = Message::SubAck {
+                    qos: if granted == 0 { QoS::AtMostOnce } else { qos },
+                    topic_id,
+                    msg_id,
+                    return_code: if granted == 0x80 {
+                        mqttsn::NOT_SUPPORTED
+                    } else {
+                        mqttsn::ACCEPTED
+                    },
+                };
+                send(radio, node, &ack);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        setting::parameters::Parameters,
+        sim::{SimConfig, SimDelay, SimPin, SimSerial, Simulator},
+    };
+
+    type SimRadio = Hc14<SimSerial, SimPin, SimDelay, Normal>;
+
+    #[test]
+    fn packets_round_trip() {
+        for len in [0, 127, 128, 16_383, 16_384] {
+            let body = vec![0xA5; len];
+            let mut inbox = packet(PUBLISH, &body);
+            inbox.extend_from_slice(&packet(PINGREQ, &[]));
+            assert_eq!(take_packet(&mut inbox).unwrap(), Some((PUBLISH, body)));
+            assert_eq!(
+                take_packet(&mut inbox).unwrap(),
+                Some((PINGREQ, Vec::new()))
+            );
+            assert_eq!(take_packet(&mut inbox).unwrap(), None);
+        }
+        // 报文不完整(Incomplete packets)
+        let mut inbox = packet(PUBLISH, b"abc");
+        inbox.pop();
+        assert_eq!(take_packet(&mut inbox).unwrap(), None);
+        let mut inbox = vec![PUBLISH, 0xFF, 0xFF, 0xFF];
+        assert_eq!(take_packet(&mut inbox).unwrap(), None);
+    }
+
+    #[test]
+    fn over_long_remaining_length_is_an_error() {
+        let mut inbox = vec![PUBLISH, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+        let error = take_packet(&mut inbox).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // 不会因移位溢出而崩溃(Does not panic on a shift overflow)
+        let mut inbox = vec![PUBLISH];
+        inbox.extend([0x80; 16]);
+        assert!(take_packet(&mut inbox).is_err());
+    }
+
+    /// 进程内的假代理：应答 CONNECT、SUBSCRIBE 和 QoS 1 的 PUBLISH，订阅后在该主题上发布 `on`，
+    /// 收到的报文转交给测试
+    /// (In-process fake broker: answers CONNECT, SUBSCRIBE and QoS 1 PUBLISH, publishes `on` to a
+    /// topic once subscribed, and hands the packets it receives to the test)
+    fn fake_broker() -> (String, Receiver<(u8, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut inbox = Vec::new();
+            let mut chunk = [0u8; 256];
+            loop {
+                let Some((header, body)) = take_packet(&mut inbox).unwrap() else {
+                    match stream.read(&mut chunk) {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => inbox.extend_from_slice(&chunk[..n]),
+                    }
+                    continue;
+                };
+                let reply = match header {
+                    CONNECT => packet(CONNACK, &[0, 0]),
+                    SUBSCRIBE => {
+                        let topic = &body[4..body.len() - 1];
+                        let granted = body[body.len() - 1];
+                        let mut reply = packet(SUBACK, &[body[0], body[1], granted]);
+                        let mut publish = Vec::new();
+                        publish.extend_from_slice(&body[2..4]);
+                        publish.extend_from_slice(topic);
+                        publish.extend_from_slice(b"on");
+                        reply.extend(packet(PUBLISH, &publish));
+                        reply
+                    }
+                    _ if header & 0xF6 == PUBLISH | 0x02 => {
+                        let at = 2 + usize::from(u16::from_be_bytes([body[0], body[1]]));
+                        packet(PUBACK, &body[at..at + 2])
+                    }
+                    _ => Vec::new(),
+                };
+                if tx.send((header, body)).is_err() || stream.write_all(&reply).is_err() {
+                    return;
+                }
+            }
+        });
+        (addr, rx)
+    }
+
+    /// 网关和一个收听网关发送的节点(The gateway and a node listening to what it sends)
+    fn gateway(broker_addr: &str) -> (Simulator, Gateway<SimSerial, SimPin, SimDelay>, SimRadio) {
+        let mut sim = Simulator::new(SimConfig::default());
+        let [a, b] = [
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+        ];
+        let radio = Hc14::new(a.serial, a.key, a.delay).unwrap();
+        let node = Hc14::new(b.serial, b.key, b.delay).unwrap();
+        (sim, Gateway::new(radio, broker_addr), node)
+    }
+
+    /// 等待网关发出的下一条消息(Wait for the next message the gateway sends)
+    fn expect<'b>(
+        gateway: &mut Gateway<SimSerial, SimPin, SimDelay>,
+        node: &mut SimRadio,
+        buffer: &'b mut [u8; MAX_FRAME_SIZE],
+    ) -> (u8, Message<'b>) {
+        let mut len = None;
+        for _ in 0..1000 {
+            gateway.poll_brokers();
+            match node.receive_frame_timeout(buffer, 3_000_000) {
+                Ok(frame) => {
+                    len = Some(frame.len());
+                    break;
+                }
+                Err(Error::Timeout) => thread::sleep(Duration::from_millis(2)),
+                Err(e) => panic!("{e:?}"),
+            }
+        }
+        mqttsn::decapsulate(&buffer[..len.expect("no message from the gateway")]).unwrap()
+    }
+
+    #[test]
+    fn bridges_connect_register_publish_and_subscribe() {
+        let (addr, broker) = fake_broker();
+        let (_sim, mut gateway, mut node) = gateway(&addr);
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let timeout = Duration::from_secs(5);
+
+        gateway.on_radio(
+            7,
+            Message::Connect {
+                clean_session: true,
+                keep_alive: 300,
+                client_id: "sensor-7",
+            },
+        );
+        let connack = Message::ConnAck {
+            return_code: mqttsn::ACCEPTED,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, connack));
+        let (header, body) = broker.recv_timeout(timeout).unwrap();
+        assert_eq!(header, CONNECT);
+        assert!(body.ends_with(b"sensor-7"));
+
+        gateway.on_radio(
+            7,
+            Message::Register {
+                topic_id: 0,
+                msg_id: 1,
+                topic_name: "t/a",
+            },
+        );
+        let regack = Message::RegAck {
+            topic_id: 1,
+            msg_id: 1,
+            return_code: mqttsn::ACCEPTED,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, regack));
+
+        gateway.on_radio(
+            7,
+            Message::Publish {
+                dup: false,
+                qos: QoS::AtLeastOnce,
+                retain: false,
+                topic_id: 1,
+                msg_id: 2,
+                data: b"21.5",
+            },
+        );
+        let puback = Message::PubAck {
+            topic_id: 1,
+            msg_id: 2,
+            return_code: mqttsn::ACCEPTED,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, puback));
+        let (header, body) = broker.recv_timeout(timeout).unwrap();
+        assert_eq!(header, PUBLISH | 0x02);
+        assert_eq!(body, b"\0\x03t/a\0\x0121.5");
+
+        // 未注册的主题(A topic that was never registered)
+        gateway.on_radio(
+            7,
+            Message::Publish {
+                dup: false,
+                qos: QoS::AtLeastOnce,
+                retain: false,
+                topic_id: 9,
+                msg_id: 3,
+                data: b"x",
+            },
+        );
+        let rejected = Message::PubAck {
+            topic_id: 9,
+            msg_id: 3,
+            return_code: mqttsn::INVALID_TOPIC_ID,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, rejected));
+
+        gateway.on_radio(
+            7,
+            Message::Subscribe {
+                dup: false,
+                qos: QoS::AtLeastOnce,
+                msg_id: 4,
+                topic_name: "t/b",
+            },
+        );
+        let suback = Message::SubAck {
+            qos: QoS::AtLeastOnce,
+            topic_id: 2,
+            msg_id: 4,
+            return_code: mqttsn::ACCEPTED,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, suback));
+        let publish = Message::Publish {
+            dup: false,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            topic_id: 2,
+            msg_id: 0,
+            data: b"on",
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, publish));
+        assert_eq!(broker.recv_timeout(timeout).unwrap().0, SUBSCRIBE);
+
+        gateway.on_radio(
+            7,
+            Message::Subscribe {
+                dup: false,
+                qos: QoS::AtMostOnce,
+                msg_id: 5,
+                topic_name: "t/#",
+            },
+        );
+        let wildcard = Message::SubAck {
+            qos: QoS::AtMostOnce,
+            topic_id: 0,
+            msg_id: 5,
+            return_code: mqttsn::NOT_SUPPORTED,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (7, wildcard));
+
+        let disconnect = Message::Disconnect { duration: None };
+        gateway.on_radio(7, disconnect);
+        assert_eq!(
+            expect(&mut gateway, &mut node, &mut buffer),
+            (7, disconnect)
+        );
+        assert_eq!(broker.recv_timeout(timeout).unwrap().0, DISCONNECT);
+    }
+
+    #[test]
+    fn unreachable_broker_is_reported_as_congestion() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let (_sim, mut gateway, mut node) = gateway(&addr);
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+
+        gateway.on_radio(
+            3,
+            Message::Connect {
+                clean_session: true,
+                keep_alive: 60,
+                client_id: "",
+            },
+        );
+        let connack = Message::ConnAck {
+            return_code: mqttsn::CONGESTION,
+        };
+        assert_eq!(expect(&mut gateway, &mut node, &mut buffer), (3, connack));
+
+        // 未连接的客户端被要求断开(A client that is not connected is told to disconnect)
+        gateway.on_radio(
+            3,
+            Message::Register {
+                topic_id: 0,
+                msg_id: 1,
+                topic_name: "t/a",
+            },
+        );
+        let disconnect = Message::Disconnect { duration: None };
+        assert_eq!(
+            expect(&mut gateway, &mut node, &mut buffer),
+            (3, disconnect)
+        );
+    }
+
+    #[test]
+    fn connecting_does_not_stall_the_radio() {
+        // 接受连接但从不应答 CONNACK 的代理(A broker accepting the connection but never answering)
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (_sim, mut gateway, mut node) = gateway(&addr);
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+
+        let start = Instant::now();
+        gateway.on_radio(
+            7,
+            Message::Connect {
+                clean_session: true,
+                keep_alive: 60,
+                client_id: "slow",
+            },
+        );
+        gateway.on_radio(8, Message::PingReq);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            expect(&mut gateway, &mut node, &mut buffer),
+            (8, Message::PingResp)
+        );
+        drop(listener);
+    }
+}
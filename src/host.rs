@@ -0,0 +1,120 @@
+//! 主机端硬件资源：通过 USB 转串口连接的 HC-14
+//!
+//! Host-side hardware resources: a HC-14 attached through a USB serial adapter.
+//!
+//! 串口设备按普通文件打开，波特率等参数需要事先设置，例如
+//! `stty -F /dev/ttyUSB0 9600 raw -echo`。后台线程持续读取串口，因此读取不会阻塞。
+//!
+//! The serial device is opened as a plain file, so the baud rate and line settings must be set
+//! beforehand, e.g. `stty -F /dev/ttyUSB0 9600 raw -echo`. A background thread keeps reading the
+//! port, so reads never block.
+//!
+//! # Example
+//! ```rust
+//! let serial = HostSerial::open("/dev/ttyUSB0").unwrap();
+//! let hc14 = Hc14::new(serial, NoPin, StdDelay).unwrap();
+//! ```
+use std::{
+    convert::Infallible,
+    fs::{File, OpenOptions},
+    io::{self, Read as _, Write as _},
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+/// 以文件方式打开的串口
+///
+/// A serial port opened as a file.
+pub struct HostSerial {
+    writer: File,
+    rx: Receiver<u8>,
+}
+
+impl HostSerial {
+    /// 打开串口设备，并启动后台读取线程
+    ///
+    /// Open the serial device and start the background reader thread.
+    pub fn open<T: AsRef<Path>>(path: T) -> io::Result<Self> {
+        let writer = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut reader = writer.try_clone()?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut chunk = [0u8; 64];
+            loop {
+                match reader.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if chunk[..n].iter().any(|byte| tx.send(*byte).is_err()) {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self { writer, rx })
+    }
+}
+
+impl Read<u8> for HostSerial {
+    type Error = io::ErrorKind;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        match self.rx.try_recv() {
+            Ok(byte) => Ok(byte),
+            Err(TryRecvError::Empty) => Err(nb::Error::WouldBlock),
+            Err(TryRecvError::Disconnected) => Err(nb::Error::Other(io::ErrorKind::BrokenPipe)),
+        }
+    }
+}
+
+impl Write<u8> for HostSerial {
+    type Error = io::ErrorKind;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.writer
+            .write_all(&[word])
+            .map_err(|e| nb::Error::Other(e.kind()))
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.writer.flush().map_err(|e| nb::Error::Other(e.kind()))
+    }
+}
+
+/// 不连接的 Key 引脚(Key 接高电平，模块始终处于正常模式)
+///
+/// An unconnected Key pin (Key tied high, the module always stays in normal mode).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoPin;
+
+impl OutputPin for NoPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// 使用 `std::thread::sleep` 的延迟
+///
+/// Delay using `std::thread::sleep`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdDelay;
+
+impl DelayUs<u32> for StdDelay {
+    fn delay_us(&mut self, us: u32) {
+        thread::sleep(Duration::from_micros(u64::from(us)));
+    }
+}
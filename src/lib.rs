@@ -4,7 +4,7 @@
 //! When the Key pin is pulled low, you can configure the module using AT commands. 
 //! This driver program receives output pins, a serial port, and a delay time from `embedded-hal` and provides a convenient interface to interact with the HC-14 module.
 //! 
-//! The HC-14 can operate in two modes: Normal mode and AT configuration mode.
+//! The HC-14 can operate in three modes: Normal mode, AT configuration mode and sleep mode.
 //! 
 //! For more details, refer to the official documentation available [here](https://www.hc01.com/downloads).
 //! 
@@ -17,7 +17,7 @@
 //! 当 Key 引脚被拉低时，可以使用 AT 指令对该模块进行配置。该驱动程序从 `embedded-hal` 中获取输出引脚、
 //! 串行端口和延迟时间，提供了一个与 hc14 模块交互的简便接口。
 //! 
-//! HC-14 的运行状态：正常模式、AT配置配置。
+//! HC-14 的运行状态：正常模式、AT配置模式、睡眠模式。
 //! 
 //! 更多详情，请参阅此处提供的[官方文档](https://www.hc01.com/downloads)，
 //! 
@@ -39,7 +39,7 @@
 //! 
 //! // Set channel
 //! let mut buffer = [0u8; 32];
-//! hc14_configure.wirte_set_channel(28, &mut buffer);
+//! hc14_configure.wirte_set_channel(28, &mut buffer).unwrap();
 //! 
 //! // Switch to normal mode
 //! let mut hc14_normal = hc14_configure.into_normal_mode().unwrap();
@@ -55,7 +55,7 @@
 //! 
 
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 
@@ -68,6 +68,38 @@ pub mod conf;
 /// HC-14 Settings
 pub mod setting;
 
+/// 多跳网状路由(Multi-hop mesh routing)
+pub mod mesh;
+
+/// MQTT-SN 客户端(MQTT-SN client)
+pub mod mqttsn;
+
+/// 可续传的块传输(Resumable block transfer)
+pub mod transfer;
+
+/// 电池寿命与能耗估算(Battery life and energy estimation)
+pub mod energy;
+
+/// 无线固件升级(Over-the-air firmware update)
+#[cfg(feature = "ota")]
+pub mod ota;
+
+/// 主机端多节点模拟器(Host-side multi-node simulator)
+#[cfg(feature = "std")]
+pub mod sim;
+
+/// 主机端串口与延迟(Host-side serial port and delay)
+#[cfg(feature = "std")]
+pub mod host;
+
+/// MQTT-SN 网关(MQTT-SN gateway)
+#[cfg(feature = "std")]
+pub mod gateway;
+
+/// 数据压缩(Payload compression)
+#[cfg(feature = "compression")]
+pub mod compress;
+
 /// Crate 错误(Error)
 #[derive(Debug)]
 pub enum Error {
@@ -79,4 +111,36 @@ pub enum Error {
     InvalidBaudRate,
     /// 无效信道(invalid channel)
     InvalidChannel,
+    /// 频段规划不允许的发射功率(transmit power not allowed by the band plan)
+    InvalidPower,
+    /// 无效的 UTF-8 数据(invalid UTF-8 data)
+    Utf8,
+    /// 消息超出单个数据包的容量(message does not fit a single packet)
+    MessageTooLarge,
+    /// 消息解码失败(message could not be decoded)
+    Decode,
+    /// 超时未收到响应(no response within the timeout)
+    Timeout,
+    /// CRC 校验失败(CRC check failed)
+    Crc,
+    /// Modbus 从站返回的异常码(exception code returned by a Modbus slave)
+    ModbusException(u8),
+    /// 对端以返回码拒绝了请求(request rejected by the peer with a return code)
+    Rejected(u8),
+    /// Key 或电源引脚操作失败(key or power pin operation failed)
+    Pin,
+    /// 模块没有应答 `AT`(the module does not answer `AT`)
+    NoResponse,
+    /// 拉高 Key 引脚后模块仍在应答 `AT`(the module still answers `AT` with the key pin high)
+    StillInAtMode,
+    /// Flash 擦除、写入或读取失败(flash erase, write or read failed)
+    Flash,
+    /// 固件镜像校验失败(firmware image verification failed)
+    Verification,
+    /// 模块没有确认AT设置指令(the module did not confirm an AT setting command)
+    CommandRejected,
+    /// 发送会超出占空比限制(sending would exceed the duty-cycle limit)
+    DutyCycle,
+    /// 需要电源控制引脚，但驱动没有(a power-control pin is needed but the driver has none)
+    NoPowerPin,
 }
@@ -93,7 +93,7 @@ fn main() -> ! {
     // 设置信道
     // Set channel
     let mut buffer = [0u8; 32];
-    hc14_configure.wirte_set_channel(28, &mut buffer);
+    hc14_configure.wirte_set_channel(28, &mut buffer).unwrap();
 
     // 切换至普通模式
     // Switch to normal mode
@@ -0,0 +1,595 @@
+//! 基于 HC-14 正常模式的多跳网状路由
+//!
+//! Multi-hop mesh routing on top of the HC-14 normal mode.
+//!
+//! 协议是带路由表的受控泛洪：每个数据包带有 TTL 和 (源地址, 序号)，
+//! 节点通过重复缓存丢弃已处理过的数据包，并从收到的每个数据包中学习到源节点的路由(下一跳、跳数)。
+//! 已知路由时只由指定的下一跳转发，否则退化为泛洪。
+//!
+//! The protocol is controlled flooding with route tables: every packet carries a TTL and a
+//! (source, sequence) pair, nodes drop packets they have already handled using a duplicate cache,
+//! and learn a route (next hop, hop count) to the source from every packet they receive.
+//! With a known route only the designated next hop forwards, otherwise the packet is flooded.
+//!
+//! [`MeshNode`] 本身是不依赖硬件的状态机，[`MeshNode::send`] 和 [`MeshNode::receive`]
+//! 将其与 [`Hc14`] 的单数据包帧连接起来。
+//!
+//! [`MeshNode`] itself is a hardware-independent state machine, [`MeshNode::send`] and
+//! [`MeshNode::receive`] connect it to the single-packet frames of [`Hc14`].
+//!
+//! # Example
+//! ```rust
+//! let mut node: MeshNode<16, 32> = MeshNode::new(3);
+//! node.send(&mut hc14, 7, b"hello").unwrap();
+//!
+//! let mut buffer = [0u8; 256];
+//! if let Some(packet) = node.receive(&mut hc14, &mut buffer).unwrap() {
+//!     hprintln!("from {}: {:?}", packet.src, packet.payload);
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use heapless::{HistoryBuffer, Vec};
+
+use crate::{
+    driver::{Hc14, Normal},
+    Error,
+};
+
+/// 节点地址
+///
+/// Node address.
+pub type Address = u8;
+
+/// 广播地址
+///
+/// Broadcast address.
+pub const BROADCAST: Address = 0xFF;
+
+/// 网状数据包头的长度
+///
+/// Length of the mesh packet header.
+pub const HEADER_LEN: usize = 8;
+
+/// 默认的 TTL
+///
+/// Default TTL.
+pub const DEFAULT_TTL: u8 = 4;
+
+/// 网状数据包头：目的、源、上一跳、指定的下一跳、序号、TTL、已经过的跳数
+///
+/// Mesh packet header: destination, source, previous hop, designated next hop, sequence, TTL, hops so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// 目的地址
+    ///
+    /// Destination address.
+    pub dst: Address,
+    /// 源地址
+    ///
+    /// Source address.
+    pub src: Address,
+    /// 最后一次发送该数据包的节点
+    ///
+    /// Node that last transmitted the packet.
+    pub via: Address,
+    /// 指定的转发节点，[`BROADCAST`] 表示由所有节点转发
+    ///
+    /// Designated forwarder, [`BROADCAST`] lets every node forward.
+    pub next: Address,
+    /// 源节点的序号
+    ///
+    /// Sequence number of the source.
+    pub seq: u16,
+    /// 剩余跳数
+    ///
+    /// Remaining hops.
+    pub ttl: u8,
+    /// 已经过的跳数
+    ///
+    /// Hops so far.
+    pub hops: u8,
+}
+
+impl Header {
+    /// 从数据包中解析数据包头
+    ///
+    /// Parse the header from a packet.
+    pub fn parse(packet: &[u8]) -> Result<Self, Error> {
+        if packet.len() < HEADER_LEN {
+            return Err(Error::Decode);
+        }
+        Ok(Self {
+            dst: packet[0],
+            src: packet[1],
+            via: packet[2],
+            next: packet[3],
+            seq: u16::from_le_bytes([packet[4], packet[5]]),
+            ttl: packet[6],
+            hops: packet[7],
+        })
+    }
+
+    /// 将数据包头写入数据包的开头，数据包短于 [`HEADER_LEN`] 时返回 [`Error::MessageTooLarge`]
+    ///
+    /// Write the header to the start of a packet, returns [`Error::MessageTooLarge`] when the packet
+    /// is shorter than [`HEADER_LEN`].
+    pub fn write(&self, packet: &mut [u8]) -> Result<(), Error> {
+        if packet.len() < HEADER_LEN {
+            return Err(Error::MessageTooLarge);
+        }
+        let seq = self.seq.to_le_bytes();
+        packet[..HEADER_LEN].copy_from_slice(&[
+            self.dst, self.src, self.via, self.next, seq[0], seq[1], self.ttl, self.hops,
+        ]);
+        Ok(())
+    }
+}
+
+/// 路由表项
+///
+/// Route table entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// 目的地址
+    ///
+    /// Destination address.
+    pub dst: Address,
+    /// 下一跳
+    ///
+    /// Next hop.
+    pub next_hop: Address,
+    /// 到达目的地的跳数
+    ///
+    /// Hops to the destination.
+    pub hops: u8,
+    /// 自上次更新以来经过的 [`MeshNode::tick`] 次数
+    ///
+    /// [`MeshNode::tick`] calls since the last update.
+    pub age: u8,
+}
+
+/// 处理一个数据包后应采取的动作
+///
+/// Action to take after handling a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// 发给本节点，交给应用程序
+    ///
+    /// Addressed to this node, hand it to the application.
+    Deliver,
+    /// 数据包头已更新，需要重新发送
+    ///
+    /// The header has been updated, the packet must be retransmitted.
+    Forward,
+    /// 广播：交给应用程序并重新发送
+    ///
+    /// Broadcast: hand it to the application and retransmit.
+    DeliverAndForward,
+    /// 丢弃(重复、本节点发出、TTL 耗尽或不由本节点转发)
+    ///
+    /// Drop (duplicate, own packet, TTL exhausted or not ours to forward).
+    Drop,
+}
+
+/// 交给应用程序的数据包
+///
+/// Packet handed to the application.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Packet<'a> {
+    /// 源地址
+    ///
+    /// Source address.
+    pub src: Address,
+    /// 目的地址(本节点或 [`BROADCAST`])
+    ///
+    /// Destination address (this node or [`BROADCAST`]).
+    pub dst: Address,
+    /// 经过的跳数
+    ///
+    /// Hops travelled.
+    pub hops: u8,
+    /// 负载
+    ///
+    /// Payload.
+    pub payload: &'a [u8],
+}
+
+/// 网状节点：最多 `R` 条路由，记住最近 `C` 个数据包以过滤重复
+///
+/// Mesh node: up to `R` routes, remembers the last `C` packets to filter duplicates.
+pub struct MeshNode<const R: usize, const C: usize> {
+    address: Address,
+    seq: u16,
+    ttl: u8,
+    route_lifetime: u8,
+    forward_slots: u8,
+    routes: Vec<Route, R>,
+    seen: HistoryBuffer<(Address, u16), C>,
+}
+
+impl<const R: usize, const C: usize> MeshNode<R, C> {
+    /// 以给定地址构建节点
+    ///
+    /// Build a node with the given address.
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            seq: 0,
+            ttl: DEFAULT_TTL,
+            route_lifetime: 16,
+            forward_slots: 4,
+            routes: Vec::new(),
+            seen: HistoryBuffer::new(),
+        }
+    }
+
+    /// 本节点地址
+    ///
+    /// Address of this node.
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// 设置新数据包的 TTL
+    ///
+    /// Set the TTL of new packets.
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.ttl = ttl;
+    }
+
+    /// 设置路由的有效期(以 [`MeshNode::tick`] 次数计)
+    ///
+    /// Set the route lifetime (in [`MeshNode::tick`] calls).
+    pub fn set_route_lifetime(&mut self, ticks: u8) {
+        self.route_lifetime = ticks;
+    }
+
+    /// 设置转发时隙数：转发前等待 `地址 % slots` 个数据包的时间，以减少同时转发造成的碰撞；
+    /// [`MeshNode::receive`] 在等待期间阻塞，最长 `slots - 1` 个数据包的时间
+    ///
+    /// Set the number of forwarding slots: wait `address % slots` packet times before forwarding,
+    /// to reduce collisions between simultaneous forwarders; [`MeshNode::receive`] blocks while
+    /// waiting, for up to `slots - 1` packet times.
+    pub fn set_forward_slots(&mut self, slots: u8) {
+        self.forward_slots = slots.max(1);
+    }
+
+    /// 当前的路由表
+    ///
+    /// Current route table.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// 查找到达 `dst` 的路由
+    ///
+    /// Look up the route to `dst`.
+    pub fn route(&self, dst: Address) -> Option<&Route> {
+        self.routes.iter().find(|route| route.dst == dst)
+    }
+
+    /// 路由老化：所有路由的年龄加一，并删除超过有效期的路由
+    ///
+    /// Age the routes: every route gets one tick older and routes past their lifetime are removed.
+    pub fn tick(&mut self) {
+        for route in self.routes.iter_mut() {
+            route.age = route.age.saturating_add(1);
+        }
+        let lifetime = self.route_lifetime;
+        self.routes.retain(|route| route.age <= lifetime);
+    }
+
+    /// 在 `packet` 中构建发往 `dst` 的数据包，返回数据包长度
+    ///
+    /// Build a packet for `dst` in `packet`, returns the packet length.
+    pub fn prepare(
+        &mut self,
+        dst: Address,
+        payload: &[u8],
+        packet: &mut [u8],
+    ) -> Result<usize, Error> {
+        let len = HEADER_LEN + payload.len();
+        if len > packet.len() {
+            return Err(Error::MessageTooLarge);
+        }
+        self.seq = self.seq.wrapping_add(1);
+        let header = Header {
+            dst,
+            src: self.address,
+            via: self.address,
+            next: self.next_hop(dst),
+            seq: self.seq,
+            ttl: self.ttl,
+            hops: 0,
+        };
+        header.write(packet)?;
+        packet[HEADER_LEN..len].copy_from_slice(payload);
+        self.seen.write((self.address, self.seq));
+        Ok(len)
+    }
+
+    /// 处理收到的数据包；需要转发时数据包头会被原地更新
+    ///
+    /// Handle a received packet; the header is updated in place when the packet must be forwarded.
+    ///
+    /// 返回 [`Action::Forward`] 或 [`Action::DeliverAndForward`] 时数据包尚未记入重复缓存，
+    /// 转发成功后需要调用 [`MeshNode::forwarded`]；转发失败时，同一数据包的重传仍会被处理。
+    ///
+    /// On [`Action::Forward`] or [`Action::DeliverAndForward`] the packet is not yet in the duplicate
+    /// cache, call [`MeshNode::forwarded`] once it has been retransmitted; if forwarding fails, a
+    /// retransmission of the same packet is still handled.
+    pub fn handle(&mut self, packet: &mut [u8]) -> Result<Action, Error> {
+        let mut header = Header::parse(packet)?;
+        if header.src == self.address || self.seen.as_slice().contains(&(header.src, header.seq)) {
+            return Ok(Action::Drop);
+        }
+
+        self.learn(header.via, header.via, 1);
+        self.learn(header.src, header.via, header.hops.saturating_add(1));
+
+        if header.dst == self.address {
+            self.seen.write((header.src, header.seq));
+            return Ok(Action::Deliver);
+        }
+        let broadcast = header.dst == BROADCAST;
+        let ours = header.next == self.address || header.next == BROADCAST;
+        if header.ttl <= 1 || !ours {
+            self.seen.write((header.src, header.seq));
+            return Ok(if broadcast {
+                Action::Deliver
+            } else {
+                Action::Drop
+            });
+        }
+
+        header.ttl -= 1;
+        header.hops = header.hops.saturating_add(1);
+        header.via = self.address;
+        header.next = if broadcast {
+            BROADCAST
+        } else {
+            self.next_hop(header.dst)
+        };
+        header.write(packet)?;
+        Ok(if broadcast {
+            Action::DeliverAndForward
+        } else {
+            Action::Forward
+        })
+    }
+
+    /// 由 [`MeshNode::handle`] 更新过的数据包已经转发，将其记入重复缓存
+    ///
+    /// The packet updated by [`MeshNode::handle`] has been forwarded, record it in the duplicate
+    /// cache.
+    pub fn forwarded(&mut self, packet: &[u8]) -> Result<(), Error> {
+        let header = Header::parse(packet)?;
+        self.seen.write((header.src, header.seq));
+        Ok(())
+    }
+
+    /// **[Normal]**: 通过网状网络发送 `payload` 至 `dst`
+    /// - Send `payload` to `dst` through the mesh
+    pub fn send<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        dst: Address,
+        payload: &[u8],
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let mut packet = [0u8; 256];
+        let limit = hc14.max_frame_payload();
+        let len = self.prepare(dst, payload, &mut packet[..limit])?;
+        hc14.send_frame(&packet[..len])
+    }
+
+    /// **[Normal]**: 接收一帧并处理：需要时转发，发给本节点的数据包返回给调用者。转发前阻塞等待
+    /// 本节点的转发时隙(见 [`MeshNode::set_forward_slots`])；转发失败时返回错误，数据包不会被
+    /// 交付，也不会记入重复缓存，其重传仍会被处理
+    /// - Receive and handle one frame: forward it when needed, return packets addressed to this node.
+    ///   Blocks for this node's forwarding slot before forwarding (see
+    ///   [`MeshNode::set_forward_slots`]); when forwarding fails the error is returned, the packet is
+    ///   neither delivered nor recorded as seen, and a retransmission of it is still handled
+    pub fn receive<'a, S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        buffer: &'a mut [u8],
+    ) -> Result<Option<Packet<'a>>, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let len = hc14.receive_frame(buffer)?.len();
+        let action = self.handle(&mut buffer[..len])?;
+        if matches!(action, Action::Forward | Action::DeliverAndForward) {
+            let slot = (self.address % self.forward_slots) as u32;
+            hc14.wait_us(slot * hc14.speed().get_packet_delay_ms() * 1000);
+            hc14.send_frame(&buffer[..len])?;
+            self.forwarded(&buffer[..len])?;
+        }
+        if matches!(action, Action::Deliver | Action::DeliverAndForward) {
+            let header = Header::parse(buffer)?;
+            return Ok(Some(Packet {
+                src: header.src,
+                dst: header.dst,
+                hops: header.hops,
+                payload: &buffer[HEADER_LEN..len],
+            }));
+        }
+        Ok(None)
+    }
+
+    fn next_hop(&self, dst: Address) -> Address {
+        match self.route(dst) {
+            Some(route) if dst != BROADCAST => route.next_hop,
+            _ => BROADCAST,
+        }
+    }
+
+    /// 学习路由：跳数更少、来自同一下一跳或已过期的路由会被替换
+    ///
+    /// Learn a route: it replaces a route with more hops, from the same next hop, or that has aged.
+    fn learn(&mut self, dst: Address, next_hop: Address, hops: u8) {
+        if dst == self.address || dst == BROADCAST {
+            return;
+        }
+        let route = Route {
+            dst,
+            next_hop,
+            hops,
+            age: 0,
+        };
+        if let Some(existing) = self.routes.iter_mut().find(|r| r.dst == dst) {
+            if hops <= existing.hops || next_hop == existing.next_hop || existing.age > 0 {
+                *existing = route;
+            }
+            return;
+        }
+        if self.routes.push(route).is_err() {
+            // 路由表已满，替换最旧的路由
+            // The table is full, replace the oldest route
+            if let Some(oldest) = self.routes.iter_mut().max_by_key(|r| r.age) {
+                *oldest = route;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let header = Header {
+            dst: 7,
+            src: 3,
+            via: 5,
+            next: BROADCAST,
+            seq: 0x1234,
+            ttl: 2,
+            hops: 1,
+        };
+        let mut packet = [0u8; HEADER_LEN];
+        header.write(&mut packet).unwrap();
+        assert_eq!(Header::parse(&packet).unwrap(), header);
+        assert!(matches!(
+            header.write(&mut packet[..HEADER_LEN - 1]),
+            Err(Error::MessageTooLarge)
+        ));
+        assert!(matches!(
+            Header::parse(&packet[..HEADER_LEN - 1]),
+            Err(Error::Decode)
+        ));
+    }
+
+    #[test]
+    fn forward_until_recorded() {
+        let mut a: MeshNode<4, 8> = MeshNode::new(1);
+        let mut b: MeshNode<4, 8> = MeshNode::new(2);
+        let mut packet = [0u8; 32];
+        let len = a.prepare(3, b"hi", &mut packet).unwrap();
+
+        // 转发失败时重传仍会被处理(A retransmission is still handled when forwarding failed)
+        let mut copy = packet;
+        assert_eq!(b.handle(&mut copy[..len]).unwrap(), Action::Forward);
+        let mut copy = packet;
+        assert_eq!(b.handle(&mut copy[..len]).unwrap(), Action::Forward);
+        let header = Header::parse(&copy).unwrap();
+        assert_eq!(
+            (header.via, header.ttl, header.hops),
+            (2, DEFAULT_TTL - 1, 1)
+        );
+        b.forwarded(&copy[..len]).unwrap();
+        let mut copy = packet;
+        assert_eq!(b.handle(&mut copy[..len]).unwrap(), Action::Drop);
+        assert_eq!(b.route(1).map(|r| (r.next_hop, r.hops)), Some((1, 1)));
+    }
+
+    #[test]
+    fn own_and_expired_packets_are_dropped() {
+        let mut a: MeshNode<4, 8> = MeshNode::new(1);
+        let mut b: MeshNode<4, 8> = MeshNode::new(2);
+        let mut packet = [0u8; 32];
+        let len = a.prepare(3, b"", &mut packet).unwrap();
+        assert_eq!(a.handle(&mut packet[..len]).unwrap(), Action::Drop);
+
+        a.set_ttl(1);
+        let len = a.prepare(3, b"", &mut packet).unwrap();
+        assert_eq!(b.handle(&mut packet[..len]).unwrap(), Action::Drop);
+        let len = a.prepare(BROADCAST, b"", &mut packet).unwrap();
+        assert_eq!(b.handle(&mut packet[..len]).unwrap(), Action::Deliver);
+    }
+
+    #[test]
+    fn routes_age_out() {
+        let mut a: MeshNode<4, 8> = MeshNode::new(1);
+        let mut b: MeshNode<4, 8> = MeshNode::new(2);
+        b.set_route_lifetime(1);
+        let mut packet = [0u8; 32];
+        let len = a.prepare(2, b"", &mut packet).unwrap();
+        assert_eq!(b.handle(&mut packet[..len]).unwrap(), Action::Deliver);
+        b.tick();
+        assert!(b.route(1).is_some());
+        b.tick();
+        assert!(b.routes().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn relay_through_simulator() {
+        use crate::{
+            driver::Hc14,
+            setting::parameters::Parameters,
+            sim::{SimConfig, Simulator},
+        };
+
+        // A - B - C，A 与 C 互相听不到(A and C cannot hear each other)
+        let mut sim = Simulator::new(SimConfig::default());
+        let nodes = [
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+        ];
+        sim.set_in_range(0, 2, false);
+        let [mut hc14_a, mut hc14_b, mut hc14_c] =
+            nodes.map(|n| Hc14::new(n.serial, n.key, n.delay).unwrap());
+        let mut node_a: MeshNode<4, 8> = MeshNode::new(1);
+        let mut node_b: MeshNode<4, 8> = MeshNode::new(2);
+        let mut node_c: MeshNode<4, 8> = MeshNode::new(3);
+        let mut buffer = [0u8; 256];
+
+        node_a.send(&mut hc14_a, 3, b"ping").unwrap();
+        assert_eq!(node_b.receive(&mut hc14_b, &mut buffer).unwrap(), None);
+        let packet = node_c.receive(&mut hc14_c, &mut buffer).unwrap().unwrap();
+        assert_eq!(
+            (packet.src, packet.hops, packet.payload),
+            (1, 1, &b"ping"[..])
+        );
+        assert_eq!(node_c.route(1).map(|r| (r.next_hop, r.hops)), Some((2, 2)));
+
+        // A 听到 B 转发自己的数据包并丢弃(A hears B forward its own packet and drops it)
+        assert_eq!(node_a.receive(&mut hc14_a, &mut buffer).unwrap(), None);
+
+        // 回复沿学到的路由由 B 转发(The reply follows the learnt route through B)
+        node_c.send(&mut hc14_c, 1, b"pong").unwrap();
+        assert_eq!(node_b.receive(&mut hc14_b, &mut buffer).unwrap(), None);
+        let packet = node_a.receive(&mut hc14_a, &mut buffer).unwrap().unwrap();
+        assert_eq!(
+            (packet.src, packet.hops, packet.payload),
+            (3, 1, &b"pong"[..])
+        );
+        assert_eq!(node_a.route(3).map(|r| (r.next_hop, r.hops)), Some((2, 2)));
+    }
+}
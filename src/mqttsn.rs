@@ -0,0 +1,999 @@
+//! 基于 HC-14 正常模式的 MQTT-SN 客户端
+//!
+//! MQTT-SN client on top of the HC-14 normal mode.
+//!
+//! 实现 MQTT-SN 1.2 的一个子集：CONNECT、REGISTER、PUBLISH(QoS 0/1)、SUBSCRIBE、PINGREQ 和
+//! DISCONNECT，只支持普通主题 ID。每条消息占用一个单数据包帧，并使用转发封装(Forwarder
+//! Encapsulation)携带节点地址，使网关能在共享信道上区分各个客户端。
+//! [`gateway`](crate::gateway) 模块和主机端的网关程序 `mqttsn_gateway`(需要 `std` 特性)将这些消息桥接到 MQTT 代理。
+//!
+//! Implements a subset of MQTT-SN 1.2: CONNECT, REGISTER, PUBLISH (QoS 0/1), SUBSCRIBE, PINGREQ and
+//! DISCONNECT, with normal topic IDs only. Every message takes one single-packet frame and uses the
+//! Forwarder Encapsulation to carry the node address, so the gateway can tell clients apart on the
+//! shared channel. The [`gateway`](crate::gateway) module and the host-side `mqttsn_gateway`
+//! binary (need the `std` feature) bridge these messages to an MQTT broker.
+//!
+//! # Example
+//! ```rust
+//! let mut client = MqttSnClient::new(7, "sensor-7");
+//! client.connect(&mut hc14, 300).unwrap();
+//! let topic = client.register(&mut hc14, "sensors/7/temp").unwrap();
+//! client.publish(&mut hc14, topic, b"21.5", QoS::AtLeastOnce).unwrap();
+//!
+//! let command = client.subscribe(&mut hc14, "sensors/7/cmd", QoS::AtMostOnce).unwrap();
+//! let mut buffer = [0u8; 256];
+//! if let Some(publication) = client.poll(&mut hc14, &mut buffer, 1_000_000).unwrap() {
+//!     if publication.topic_id == command {
+//!         hprintln!("command: {:?}", publication.data);
+//!     }
+//! }
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+
+use crate::{
+    driver::{frame::MAX_FRAME_SIZE, stats, Hc14, Normal},
+    Error,
+};
+
+/// 消息类型(Message types)
+mod kind {
+    pub const CONNECT: u8 = 0x04;
+    pub const CONNACK: u8 = 0x05;
+    pub const REGISTER: u8 = 0x0A;
+    pub const REGACK: u8 = 0x0B;
+    pub const PUBLISH: u8 = 0x0C;
+    pub const PUBACK: u8 = 0x0D;
+    pub const SUBSCRIBE: u8 = 0x12;
+    pub const SUBACK: u8 = 0x13;
+    pub const PINGREQ: u8 = 0x16;
+    pub const PINGRESP: u8 = 0x17;
+    pub const DISCONNECT: u8 = 0x18;
+    pub const ENCAPSULATED: u8 = 0xFE;
+}
+
+const FLAG_DUP: u8 = 0x80;
+const FLAG_QOS: u8 = 0x60;
+const FLAG_RETAIN: u8 = 0x10;
+const FLAG_CLEAN_SESSION: u8 = 0x04;
+const FLAG_TOPIC_TYPE: u8 = 0x03;
+const PROTOCOL_ID: u8 = 0x01;
+
+/// 返回码：接受
+///
+/// Return code: accepted.
+pub const ACCEPTED: u8 = 0x00;
+/// 返回码：拥塞，稍后重试
+///
+/// Return code: congestion, retry later.
+pub const CONGESTION: u8 = 0x01;
+/// 返回码：无效的主题 ID
+///
+/// Return code: invalid topic ID.
+pub const INVALID_TOPIC_ID: u8 = 0x02;
+/// 返回码：不支持
+///
+/// Return code: not supported.
+pub const NOT_SUPPORTED: u8 = 0x03;
+
+/// 封装头长度：长度、类型、控制字节和 1 字节节点地址
+///
+/// Encapsulation header length: length, type, control byte and a 1-byte node address.
+pub const ENCAPSULATION_LEN: usize = 4;
+
+/// 网关在 [`LinkStats`](crate::driver::stats::LinkStats) 中的对端标识；网关在无线网络中没有地址，
+/// 取节点地址范围之外的值
+///
+/// Peer identifier of the gateway in [`LinkStats`](crate::driver::stats::LinkStats); the gateway
+/// has no address on the radio network, so a value outside the node address range is used.
+pub const GATEWAY_PEER: u16 = 0x0100;
+
+/// 服务质量
+///
+/// Quality of service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QoS {
+    /// 最多一次(At most once)
+    AtMostOnce,
+    /// 至少一次(At least once)
+    AtLeastOnce,
+}
+
+impl QoS {
+    fn flags(self) -> u8 {
+        match self {
+            QoS::AtMostOnce => 0x00,
+            QoS::AtLeastOnce => 0x20,
+        }
+    }
+
+    fn from_flags(flags: u8) -> Option<Self> {
+        match flags & FLAG_QOS {
+            0x00 => Some(QoS::AtMostOnce),
+            0x20 => Some(QoS::AtLeastOnce),
+            _ => None,
+        }
+    }
+}
+
+/// MQTT-SN 消息
+///
+/// MQTT-SN message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<'a> {
+    /// 连接请求(Connection request)
+    Connect {
+        /// 清除会话(Clean session)
+        clean_session: bool,
+        /// 心跳周期，单位秒(Keep-alive period in seconds)
+        keep_alive: u16,
+        /// 客户端 ID(Client ID)
+        client_id: &'a str,
+    },
+    /// 连接应答(Connection acknowledgement)
+    ConnAck {
+        /// 返回码(Return code)
+        return_code: u8,
+    },
+    /// 主题注册(Topic registration)
+    Register {
+        /// 主题 ID(Topic ID)
+        topic_id: u16,
+        /// 消息 ID(Message ID)
+        msg_id: u16,
+        /// 主题名称(Topic name)
+        topic_name: &'a str,
+    },
+    /// 注册应答(Registration acknowledgement)
+    RegAck {
+        /// 主题 ID(Topic ID)
+        topic_id: u16,
+        /// 消息 ID(Message ID)
+        msg_id: u16,
+        /// 返回码(Return code)
+        return_code: u8,
+    },
+    /// 发布消息(Publication)
+    Publish {
+        /// 重发标志(Duplicate flag)
+        dup: bool,
+        /// 服务质量(Quality of service)
+        qos: QoS,
+        /// 保留消息(Retained message)
+        retain: bool,
+        /// 主题 ID(Topic ID)
+        topic_id: u16,
+        /// 消息 ID(Message ID)
+        msg_id: u16,
+        /// 负载(Payload)
+        data: &'a [u8],
+    },
+    /// 发布应答(Publication acknowledgement)
+    PubAck {
+        /// 主题 ID(Topic ID)
+        topic_id: u16,
+        /// 消息 ID(Message ID)
+        msg_id: u16,
+        /// 返回码(Return code)
+        return_code: u8,
+    },
+    /// 订阅请求(Subscription request)
+    Subscribe {
+        /// 重发标志(Duplicate flag)
+        dup: bool,
+        /// 服务质量(Quality of service)
+        qos: QoS,
+        /// 消息 ID(Message ID)
+        msg_id: u16,
+        /// 主题名称(Topic name)
+        topic_name: &'a str,
+    },
+    /// 订阅应答(Subscription acknowledgement)
+    SubAck {
+        /// 服务质量(Quality of service)
+        qos: QoS,
+        /// 主题 ID(Topic ID)
+        topic_id: u16,
+        /// 消息 ID(Message ID)
+        msg_id: u16,
+        /// 返回码(Return code)
+        return_code: u8,
+    },
+    /// 心跳请求(Keep-alive ping)
+    PingReq,
+    /// 心跳应答(Keep-alive answer)
+    PingResp,
+    /// 断开连接(Disconnect)
+    Disconnect {
+        /// 休眠时长，单位秒(Sleep duration in seconds)
+        duration: Option<u16>,
+    },
+}
+
+/// 顺序写入的缓冲区(Sequential writer)
+struct Cursor<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn put(&mut self, bytes: &[u8]) -> Option<()> {
+        self.buf
+            .get_mut(self.pos..self.pos + bytes.len())?
+            .copy_from_slice(bytes);
+        self.pos += bytes.len();
+        Some(())
+    }
+
+    fn u8(&mut self, value: u8) -> Option<()> {
+        self.put(&[value])
+    }
+
+    fn u16(&mut self, value: u16) -> Option<()> {
+        self.put(&value.to_be_bytes())
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]))
+}
+
+fn str_at(bytes: &[u8], at: usize) -> Option<&str> {
+    core::str::from_utf8(bytes.get(at..)?).ok()
+}
+
+impl<'a> Message<'a> {
+    /// 编码为一条 MQTT-SN 消息(1 字节长度)，返回写入的长度
+    ///
+    /// Encode as one MQTT-SN message (1-byte length), returns the length written.
+    pub fn encode(&self, out: &mut [u8]) -> Option<usize> {
+        let mut w = Cursor { buf: out, pos: 1 };
+        match *self {
+            Message::Connect {
+                clean_session,
+                keep_alive,
+                client_id,
+            } => {
+                w.u8(kind::CONNECT)?;
+                w.u8(if clean_session { FLAG_CLEAN_SESSION } else { 0 })?;
+                w.u8(PROTOCOL_ID)?;
+                w.u16(keep_alive)?;
+                w.put(client_id.as_bytes())?;
+            }
+            Message::ConnAck { return_code } => {
+                w.u8(kind::CONNACK)?;
+                w.u8(return_code)?;
+            }
+            Message::Register {
+                topic_id,
+                msg_id,
+                topic_name,
+            } => {
+                w.u8(kind::REGISTER)?;
+                w.u16(topic_id)?;
+                w.u16(msg_id)?;
+                w.put(topic_name.as_bytes())?;
+            }
+            Message::RegAck {
+                topic_id,
+                msg_id,
+                return_code,
+            } => {
+                w.u8(kind::REGACK)?;
+                w.u16(topic_id)?;
+                w.u16(msg_id)?;
+                w.u8(return_code)?;
+            }
+            Message::Publish {
+                dup,
+                qos,
+                retain,
+                topic_id,
+                msg_id,
+                data,
+            } => {
+                let mut flags = qos.flags();
+                if dup {
+                    flags |= FLAG_DUP;
+                }
+                if retain {
+                    flags |= FLAG_RETAIN;
+                }
+                w.u8(kind::PUBLISH)?;
+                w.u8(flags)?;
+                w.u16(topic_id)?;
+                w.u16(msg_id)?;
+                w.put(data)?;
+            }
+            Message::PubAck {
+                topic_id,
+                msg_id,
+                return_code,
+            } => {
+                w.u8(kind::PUBACK)?;
+                w.u16(topic_id)?;
+                w.u16(msg_id)?;
+                w.u8(return_code)?;
+            }
+            Message::Subscribe {
+                dup,
+                qos,
+                msg_id,
+                topic_name,
+            } => {
+                w.u8(kind::SUBSCRIBE)?;
+                w.u8(qos.flags() | if dup { FLAG_DUP } else { 0 })?;
+                w.u16(msg_id)?;
+                w.put(topic_name.as_bytes())?;
+            }
+            Message::SubAck {
+                qos,
+                topic_id,
+                msg_id,
+                return_code,
+            } => {
+                w.u8(kind::SUBACK)?;
+                w.u8(qos.flags())?;
+                w.u16(topic_id)?;
+                w.u16(msg_id)?;
+                w.u8(return_code)?;
+            }
+            Message::PingReq => w.u8(kind::PINGREQ)?,
+            Message::PingResp => w.u8(kind::PINGRESP)?,
+            Message::Disconnect { duration } => {
+                w.u8(kind::DISCONNECT)?;
+                if let Some(duration) = duration {
+                    w.u16(duration)?;
+                }
+            }
+        }
+        let len = w.pos;
+        w.buf[0] = u8::try_from(len).ok()?;
+        Some(len)
+    }
+
+    /// 解码一条 MQTT-SN 消息(1 字节长度)，不支持的消息返回 `None`
+    ///
+    /// Decode one MQTT-SN message (1-byte length), `None` for unsupported messages.
+    pub fn decode(bytes: &'a [u8]) -> Option<Self> {
+        let len = usize::from(*bytes.first()?);
+        if len < 2 || len > bytes.len() {
+            return None;
+        }
+        let b = &bytes[..len];
+        let message = match b[1] {
+            kind::CONNECT => Message::Connect {
+                clean_session: *b.get(2)? & FLAG_CLEAN_SESSION != 0,
+                keep_alive: u16_at(b, 4)?,
+                client_id: str_at(b, 6)?,
+            },
+            kind::CONNACK => Message::ConnAck {
+                return_code: *b.get(2)?,
+            },
+            kind::REGISTER => Message::Register {
+                topic_id: u16_at(b, 2)?,
+                msg_id: u16_at(b, 4)?,
+                topic_name: str_at(b, 6)?,
+            },
+            kind::REGACK => Message::RegAck {
+                topic_id: u16_at(b, 2)?,
+                msg_id: u16_at(b, 4)?,
+                return_code: *b.get(6)?,
+            },
+            kind::PUBLISH => {
+                let flags = *b.get(2)?;
+                if flags & FLAG_TOPIC_TYPE != 0 {
+                    return None;
+                }
+                Message::Publish {
+                    dup: flags & FLAG_DUP != 0,
+                    qos: QoS::from_flags(flags)?,
+                    retain: flags & FLAG_RETAIN != 0,
+                    topic_id: u16_at(b, 3)?,
+                    msg_id: u16_at(b, 5)?,
+                    data: b.get(7..)?,
+                }
+            }
+            kind::PUBACK => Message::PubAck {
+                topic_id: u16_at(b, 2)?,
+                msg_id: u16_at(b, 4)?,
+                return_code: *b.get(6)?,
+            },
+            kind::SUBSCRIBE => {
+                let flags = *b.get(2)?;
+                if flags & FLAG_TOPIC_TYPE != 0 {
+                    return None;
+                }
+                Message::Subscribe {
+                    dup: flags & FLAG_DUP != 0,
+                    qos: QoS::from_flags(flags)?,
+                    msg_id: u16_at(b, 3)?,
+                    topic_name: str_at(b, 5)?,
+                }
+            }
+            kind::SUBACK => Message::SubAck {
+                qos: QoS::from_flags(*b.get(2)?)?,
+                topic_id: u16_at(b, 3)?,
+                msg_id: u16_at(b, 5)?,
+                return_code: *b.get(7)?,
+            },
+            kind::PINGREQ => Message::PingReq,
+            kind::PINGRESP => Message::PingResp,
+            kind::DISCONNECT => Message::Disconnect {
+                duration: u16_at(b, 2),
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+impl Message<'_> {
+    /// 重发时使用的副本：PUBLISH 和 SUBSCRIBE 设置重发标志，其他消息不变
+    ///
+    /// The copy used for a resend: PUBLISH and SUBSCRIBE get the duplicate flag, other messages
+    /// are unchanged.
+    fn duplicate(&self) -> Self {
+        let mut message = *self;
+        if let Message::Publish { dup, .. } | Message::Subscribe { dup, .. } = &mut message {
+            *dup = true;
+        }
+        message
+    }
+}
+
+/// 将消息封装并附上节点地址，返回写入的长度
+///
+/// Encapsulate a message with the node address, returns the length written.
+pub fn encapsulate(node: u8, message: &Message, out: &mut [u8]) -> Option<usize> {
+    let header = out.get_mut(..ENCAPSULATION_LEN)?;
+    header.copy_from_slice(&[ENCAPSULATION_LEN as u8, kind::ENCAPSULATED, 0, node]);
+    let len = message.encode(out.get_mut(ENCAPSULATION_LEN..)?)?;
+    Some(ENCAPSULATION_LEN + len)
+}
+
+/// 解开封装，返回节点地址和消息
+///
+/// Remove the encapsulation, returns the node address and the message.
+pub fn decapsulate(bytes: &[u8]) -> Option<(u8, Message<'_>)> {
+    if bytes.len() < ENCAPSULATION_LEN
+        || usize::from(bytes[0]) != ENCAPSULATION_LEN
+        || bytes[1] != kind::ENCAPSULATED
+    {
+        return None;
+    }
+    Some((bytes[3], Message::decode(&bytes[ENCAPSULATION_LEN..])?))
+}
+
+/// 客户端收到的发布消息
+///
+/// A publication received by the client.
+#[derive(Debug)]
+pub struct Publication<'a> {
+    /// 主题 ID(Topic ID)
+    pub topic_id: u16,
+    /// 服务质量(Quality of service)
+    pub qos: QoS,
+    /// 保留消息(Retained message)
+    pub retain: bool,
+    /// 负载(Payload)
+    pub data: &'a [u8],
+}
+
+/// MQTT-SN 客户端状态：节点地址、客户端 ID 和消息 ID 计数器
+///
+/// MQTT-SN client state: node address, client ID and message ID counter.
+#[derive(Debug)]
+pub struct MqttSnClient<'c> {
+    node: u8,
+    client_id: &'c str,
+    msg_id: u16,
+    retries: u8,
+    turnaround_us: u32,
+}
+
+impl<'c> MqttSnClient<'c> {
+    /// 构建客户端，`node` 为本节点在无线网络中的地址
+    ///
+    /// Build a client, `node` is this node's address on the radio network.
+    pub fn new(node: u8, client_id: &'c str) -> Self {
+        Self {
+            node,
+            client_id,
+            msg_id: 0,
+            retries: 3,
+            turnaround_us: 500_000,
+        }
+    }
+
+    /// 等待应答的重试次数(默认 3)
+    ///
+    /// Retries while waiting for an acknowledgement (3 by default).
+    pub fn set_retries(&mut self, retries: u8) {
+        self.retries = retries;
+    }
+
+    /// 网关与代理处理请求的时间余量，计入应答超时(默认 500 ms)
+    ///
+    /// Gateway and broker processing margin added to the acknowledgement timeout (500 ms by default).
+    pub fn set_turnaround_us(&mut self, turnaround_us: u32) {
+        self.turnaround_us = turnaround_us;
+    }
+
+    /// 连接网关，`keep_alive` 单位为秒
+    ///
+    /// Connect to the gateway, `keep_alive` in seconds.
+    pub fn connect<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        keep_alive: u16,
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let connect = Message::Connect {
+            clean_session: true,
+            keep_alive,
+            client_id: self.client_id,
+        };
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        match self.transact(hc14, &connect, &mut buffer, |m| {
+            matches!(m, Message::ConnAck { .. })
+        })? {
+            Message::ConnAck {
+                return_code: ACCEPTED,
+            } => Ok(()),
+            Message::ConnAck { return_code } => Err(Error::Rejected(return_code)),
+            _ => Err(Error::Decode),
+        }
+    }
+
+    /// 注册主题名称，返回网关分配的主题 ID
+    ///
+    /// Register a topic name, returns the topic ID assigned by the gateway.
+    pub fn register<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        topic_name: &str,
+    ) -> Result<u16, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let msg_id = self.next_msg_id();
+        let register = Message::Register {
+            topic_id: 0,
+            msg_id,
+            topic_name,
+        };
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        match self.transact(
+            hc14,
+            &register,
+            &mut buffer,
+            |m| matches!(m, Message::RegAck { msg_id: id, .. } if *id == msg_id),
+        )? {
+            Message::RegAck {
+                topic_id,
+                return_code: ACCEPTED,
+                ..
+            } => Ok(topic_id),
+            Message::RegAck { return_code, .. } => Err(Error::Rejected(return_code)),
+            _ => Err(Error::Decode),
+        }
+    }
+
+    /// 发布消息；QoS 1 时等待网关应答，超时后重发
+    ///
+    /// Publish a message; with QoS 1 waits for the gateway's acknowledgement and resends on timeout.
+    pub fn publish<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        topic_id: u16,
+        data: &[u8],
+        qos: QoS,
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let msg_id = match qos {
+            QoS::AtMostOnce => 0,
+            QoS::AtLeastOnce => self.next_msg_id(),
+        };
+        let publish = Message::Publish {
+            dup: false,
+            qos,
+            retain: false,
+            topic_id,
+            msg_id,
+            data,
+        };
+        if qos == QoS::AtMostOnce {
+            return self.send(hc14, &publish);
+        }
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        match self.transact(
+            hc14,
+            &publish,
+            &mut buffer,
+            |m| matches!(m, Message::PubAck { msg_id: id, .. } if *id == msg_id),
+        )? {
+            Message::PubAck {
+                return_code: ACCEPTED,
+                ..
+            } => Ok(()),
+            Message::PubAck { return_code, .. } => Err(Error::Rejected(return_code)),
+            _ => Err(Error::Decode),
+        }
+    }
+
+    /// 订阅主题名称(不支持通配符)，返回该主题的 ID
+    ///
+    /// Subscribe to a topic name (no wildcards), returns the ID of the topic.
+    pub fn subscribe<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        topic_name: &str,
+        qos: QoS,
+    ) -> Result<u16, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let msg_id = self.next_msg_id();
+        let subscribe = Message::Subscribe {
+            dup: false,
+            qos,
+            msg_id,
+            topic_name,
+        };
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        match self.transact(
+            hc14,
+            &subscribe,
+            &mut buffer,
+            |m| matches!(m, Message::SubAck { msg_id: id, .. } if *id == msg_id),
+        )? {
+            Message::SubAck {
+                topic_id,
+                return_code: ACCEPTED,
+                ..
+            } => Ok(topic_id),
+            Message::SubAck { return_code, .. } => Err(Error::Rejected(return_code)),
+            _ => Err(Error::Decode),
+        }
+    }
+
+    /// 发送心跳并等待网关应答
+    ///
+    /// Send a keep-alive ping and wait for the gateway's answer.
+    pub fn ping<S, P, D, W>(&mut self, hc14: &mut Hc14<S, P, D, Normal, W>) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        self.transact(hc14, &Message::PingReq, &mut buffer, |m| {
+            matches!(m, Message::PingResp)
+        })?;
+        Ok(())
+    }
+
+    /// 断开与网关的连接
+    ///
+    /// Disconnect from the gateway.
+    pub fn disconnect<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        self.send(hc14, &Message::Disconnect { duration: None })
+    }
+
+    /// 在 `timeout_us` 微秒内等待发给本节点的发布消息，QoS 1 的消息会自动应答；
+    /// 超时返回 `Ok(None)`
+    ///
+    /// Wait up to `timeout_us` microseconds for a publication to this node, QoS 1 messages are
+    /// acknowledged automatically; returns `Ok(None)` on timeout.
+    pub fn poll<'b, S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        buffer: &'b mut [u8],
+        timeout_us: u32,
+    ) -> Result<Option<Publication<'b>>, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let len = loop {
+            let len = match hc14.receive_frame_timeout(buffer, timeout_us) {
+                Ok(frame) => frame.len(),
+                Err(Error::Timeout) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if let Some((node, Message::Publish { .. })) = decapsulate(&buffer[..len]) {
+                if node == self.node {
+                    break len;
+                }
+            }
+        };
+        match decapsulate(&buffer[..len]) {
+            Some((
+                _,
+                Message::Publish {
+                    qos,
+                    retain,
+                    topic_id,
+                    msg_id,
+                    data,
+                    ..
+                },
+            )) => {
+                if qos == QoS::AtLeastOnce {
+                    let ack = Message::PubAck {
+                        topic_id,
+                        msg_id,
+                        return_code: ACCEPTED,
+                    };
+                    self.send(hc14, &ack)?;
+                }
+                Ok(Some(Publication {
+                    topic_id,
+                    qos,
+                    retain,
+                    data,
+                }))
+            }
+            _ => Err(Error::Decode),
+        }
+    }
+
+    fn next_msg_id(&mut self) -> u16 {
+        self.msg_id = self.msg_id.wrapping_add(1).max(1);
+        self.msg_id
+    }
+
+    fn send<S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        message: &Message,
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let mut frame = [0u8; MAX_FRAME_SIZE];
+        let len = encapsulate(self.node, message, &mut frame).ok_or(Error::MessageTooLarge)?;
+        hc14.send_frame(&frame[..len])
+    }
+
+    /// 发送请求并等待被 `accept` 接受的应答，超时后带重发标志重发
+    ///
+    /// Send a request and wait for an answer accepted by `accept`, resending with the duplicate
+    /// flag on timeout.
+    fn transact<'b, S, P, D, W>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        request: &Message,
+        buffer: &'b mut [u8; MAX_FRAME_SIZE],
+        accept: impl Fn(&Message) -> bool,
+    ) -> Result<Message<'b>, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        // 请求和应答各占一个数据包(A request and its answer take one packet each)
+        let timeout_us = hc14.speed().get_first_packet_delay_ms() * 2000 + self.turnaround_us;
+        let resend = request.duplicate();
+        let mut found = None;
+        'attempts: for attempt in 0..=self.retries {
+            if attempt > 0 {
+                stats::add(&mut hc14.stats_mut().retries, 1);
+            }
+            self.send(hc14, if attempt > 0 { &resend } else { request })?;
+            let mut waited: u32 = 0;
+            let mut round_trip: u32 = 0;
+            while waited < timeout_us {
+                let len = match hc14.receive_frame_timed(buffer, timeout_us - waited) {
+                    Ok((len, elapsed)) => {
+                        // 每一帧至少按一个数据包计，避免无关的帧无限延长等待
+                        // Every frame counts as one packet at least, so unrelated frames cannot
+                        // extend the wait forever
+                        let packet_us = hc14.speed().get_packet_delay_ms() * 1000;
+                        waited = waited.saturating_add(elapsed.max(packet_us));
+                        round_trip = round_trip.saturating_add(elapsed);
+                        len
+                    }
+                    Err(Error::Timeout) => break,
+                    Err(e) => return Err(e),
+                };
+                match decapsulate(&buffer[..len]) {
+                    Some((node, message)) if node == self.node && accept(&message) => {
+                        hc14.stats_mut().record_round_trip(GATEWAY_PEER, round_trip);
+                        found = Some(len);
+                        break 'attempts;
+                    }
+                    _ => {}
+                }
+            }
+            stats::add(&mut hc14.stats_mut().timeouts, 1);
+        }
+        let len = found.ok_or(Error::Timeout)?;
+        decapsulate(&buffer[..len])
+            .map(|(_, message)| message)
+            .ok_or(Error::Decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message<'_>) {
+        let mut buffer = [0u8; 64];
+        let len = message.encode(&mut buffer).unwrap();
+        assert_eq!(usize::from(buffer[0]), len);
+        assert_eq!(Message::decode(&buffer[..len]), Some(message));
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        round_trip(Message::Connect {
+            clean_session: true,
+            keep_alive: 300,
+            client_id: "sensor-7",
+        });
+        round_trip(Message::ConnAck {
+            return_code: ACCEPTED,
+        });
+        round_trip(Message::Register {
+            topic_id: 0,
+            msg_id: 2,
+            topic_name: "sensors/7/temp",
+        });
+        round_trip(Message::RegAck {
+            topic_id: 5,
+            msg_id: 2,
+            return_code: ACCEPTED,
+        });
+        round_trip(Message::Publish {
+            dup: true,
+            qos: QoS::AtLeastOnce,
+            retain: true,
+            topic_id: 5,
+            msg_id: 3,
+            data: b"21.5",
+        });
+        round_trip(Message::PubAck {
+            topic_id: 5,
+            msg_id: 3,
+            return_code: CONGESTION,
+        });
+        round_trip(Message::Subscribe {
+            dup: false,
+            qos: QoS::AtMostOnce,
+            msg_id: 4,
+            topic_name: "sensors/7/cmd",
+        });
+        round_trip(Message::SubAck {
+            qos: QoS::AtMostOnce,
+            topic_id: 6,
+            msg_id: 4,
+            return_code: ACCEPTED,
+        });
+        round_trip(Message::PingReq);
+        round_trip(Message::PingResp);
+        round_trip(Message::Disconnect { duration: None });
+        round_trip(Message::Disconnect { duration: Some(60) });
+    }
+
+    #[test]
+    fn wire_format() {
+        let mut buffer = [0u8; 16];
+        let publish = Message::Publish {
+            dup: false,
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            topic_id: 0x0102,
+            msg_id: 0x0304,
+            data: b"x",
+        };
+        let len = publish.encode(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[8, 0x0C, 0x20, 1, 2, 3, 4, b'x']);
+        assert_eq!(publish.encode(&mut buffer[..7]), None);
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        // 长度超出数据、未知类型、主题类型不支持(Length beyond the data, unknown type, unsupported topic type)
+        assert_eq!(Message::decode(&[5, 0x16]), None);
+        assert_eq!(Message::decode(&[2, 0x99]), None);
+        assert_eq!(Message::decode(&[7, 0x0C, 0x01, 0, 1, 0, 1]), None);
+        assert_eq!(Message::decode(&[]), None);
+    }
+
+    #[test]
+    fn encapsulation_carries_the_node() {
+        let mut buffer = [0u8; 16];
+        let len = encapsulate(7, &Message::PingReq, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[4, 0xFE, 0, 7, 2, 0x16]);
+        assert_eq!(decapsulate(&buffer[..len]), Some((7, Message::PingReq)));
+        assert_eq!(decapsulate(&buffer[2..len]), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn resends_carry_the_duplicate_flag() {
+        use crate::{
+            setting::parameters::Parameters,
+            sim::{SimConfig, Simulator},
+        };
+
+        let mut sim = Simulator::new(SimConfig::default());
+        let [a, b] = [
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+        ];
+        let mut hc14 = Hc14::new(a.serial, a.key, a.delay).unwrap();
+        let mut gateway = Hc14::new(b.serial, b.key, b.delay).unwrap();
+        let mut client = MqttSnClient::new(7, "sensor-7");
+        client.set_retries(1);
+
+        // 网关不应答，请求被重发一次(The gateway never answers, the request is resent once)
+        assert!(matches!(
+            client.publish(&mut hc14, 5, b"21.5", QoS::AtLeastOnce),
+            Err(Error::Timeout)
+        ));
+        assert!(matches!(
+            client.subscribe(&mut hc14, "sensors/7/cmd", QoS::AtMostOnce),
+            Err(Error::Timeout)
+        ));
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let mut flags = [(0u8, false, 0u16); 4];
+        for flag in flags.iter_mut() {
+            let frame = gateway
+                .receive_frame_timeout(&mut buffer, 1_000_000)
+                .unwrap();
+            *flag = match decapsulate(frame).unwrap() {
+                (_, Message::Publish { dup, msg_id, .. }) => (kind::PUBLISH, dup, msg_id),
+                (_, Message::Subscribe { dup, msg_id, .. }) => (kind::SUBSCRIBE, dup, msg_id),
+                (_, message) => panic!("unexpected {message:?}"),
+            };
+        }
+        assert_eq!(
+            flags,
+            [
+                (kind::PUBLISH, false, 1),
+                (kind::PUBLISH, true, 1),
+                (kind::SUBSCRIBE, false, 2),
+                (kind::SUBSCRIBE, true, 2),
+            ]
+        );
+    }
+}
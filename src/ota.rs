@@ -0,0 +1,607 @@
+//! 通过 HC-14 链路的无线固件升级
+//!
+//! Over-the-air firmware update through the HC-14 link.
+//!
+//! 升级镜像由固定长度的清单([`Manifest`]：版本、固件大小、SHA-256 摘要和对这些字段的可选签名)加上固件本身
+//! 组成，用 [`Transfer`](crate::transfer::Transfer) 传输。[`FlashSink`] 把固件写入实现了
+//! `embedded-storage` [`NorFlash`] 的分区，按需擦除扇区；全部收到后读回 Flash 计算摘要，与清单
+//! 比较，再交给 [`Verifier`] 检查签名。校验失败时接收端取消传输，发送端得到
+//! [`Error::Rejected`]`(`[`CANCEL_REJECTED`](crate::transfer::CANCEL_REJECTED)`)`。
+//!
+//! 链路中断后再次以同一个 `FlashSink` 调用 `receive`，传输从已收到的位置继续。
+//!
+//! An update image is a fixed-size manifest ([`Manifest`]: version, firmware size, SHA-256 digest
+//! and an optional signature over those fields) followed by the firmware itself, carried by
+//! [`Transfer`](crate::transfer::Transfer). [`FlashSink`] writes the firmware into a partition of
+//! an `embedded-storage` [`NorFlash`], erasing sectors on demand; once everything has arrived it
+//! reads the flash back, compares the digest with the manifest and hands it to a [`Verifier`] for
+//! the signature. On a failed verification the receiver cancels the transfer and the sender gets
+//! [`Error::Rejected`]`(`[`CANCEL_REJECTED`](crate::transfer::CANCEL_REJECTED)`)`.
+//!
+//! After a link loss, call `receive` again with the same `FlashSink` and the transfer continues
+//! where it stopped.
+//!
+//! # Example
+//! ```rust
+//! // 接收端：写入从 0x0802_0000 开始的 64 KiB 备用分区(Receiver: into the 64 KiB slot at 0x0802_0000)
+//! let mut sink = FlashSink::new(flash, 0x2_0000, 0x1_0000, HashOnly);
+//! let mut transfer = Transfer::new(7);
+//! loop {
+//!     match transfer.receive(&mut hc14, &mut sink, 600_000_000, |_| {}) {
+//!         Ok(_) => break,
+//!         // 链路中断，等待发送端重新开始(Link lost, wait for the sender to come back)
+//!         Err(Error::Timeout) => continue,
+//!         Err(e) => panic!("{:?}", e),
+//!     }
+//! }
+//! hprintln!("firmware v{} ready", sink.manifest().unwrap().version);
+//! ```
+use embedded_storage::nor_flash::NorFlash;
+use heapless::String;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    transfer::{BlockSink, MAX_NAME_LEN},
+    Error,
+};
+
+/// 清单的魔数
+///
+/// Manifest magic.
+pub const MAGIC: [u8; 8] = *b"HC14OTA1";
+
+/// 签名的最大长度(例如 Ed25519 签名)
+///
+/// Longest signature (e.g. an Ed25519 signature).
+pub const SIGNATURE_LEN: usize = 64;
+
+/// 被签名的清单头部的字节数：魔数、版本、大小和摘要
+///
+/// Length in bytes of the signed manifest header: magic, version, size and digest.
+pub const SIGNED_LEN: usize = 8 + 4 + 4 + 32;
+
+/// 清单的字节数：签名的头部加签名
+///
+/// Manifest length in bytes: the signed header followed by the signature.
+pub const MANIFEST_LEN: usize = SIGNED_LEN + SIGNATURE_LEN;
+
+/// 写入 Flash 前缓存的字节数，必须是 Flash 写入单位的整数倍
+const PAGE_LEN: usize = 256;
+
+/// 固件镜像的清单，位于传输数据的开头
+///
+/// Manifest of a firmware image, at the start of the transferred data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// 固件版本(Firmware version)
+    pub version: u32,
+    /// 固件字节数，不含清单(Firmware size in bytes, without the manifest)
+    pub size: u32,
+    /// 固件的 SHA-256 摘要(SHA-256 digest of the firmware)
+    pub digest: [u8; 32],
+    /// 对 [`signed_bytes`](Self::signed_bytes) 的签名，未签名时全为 0
+    /// (Signature over [`signed_bytes`](Self::signed_bytes), all zero when unsigned)
+    pub signature: [u8; SIGNATURE_LEN],
+}
+
+impl Manifest {
+    /// 为固件计算摘要并构建清单(未签名)
+    ///
+    /// Compute the digest of a firmware and build its manifest (unsigned).
+    pub fn new(version: u32, firmware: &[u8]) -> Self {
+        Self {
+            version,
+            size: firmware.len() as u32,
+            digest: Sha256::digest(firmware).into(),
+            signature: [0; SIGNATURE_LEN],
+        }
+    }
+
+    /// 是否带有签名
+    ///
+    /// Whether a signature is attached.
+    pub fn is_signed(&self) -> bool {
+        self.signature.iter().any(|byte| *byte != 0)
+    }
+
+    /// 被签名的字节：序列化的清单头部 `魔数 ‖ 版本 ‖ 大小 ‖ 摘要`(整数为大端)。签名同时覆盖版本和
+    /// 大小，防止把旧固件的签名套用到新的版本号上
+    ///
+    /// The bytes covered by the signature: the serialized manifest header
+    /// `magic ‖ version ‖ size ‖ digest` (integers big-endian). Signing the version and size as
+    /// well keeps a signature from being replayed under another version number.
+    pub fn signed_bytes(&self) -> [u8; SIGNED_LEN] {
+        let mut bytes = [0u8; SIGNED_LEN];
+        bytes[..8].copy_from_slice(&MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.size.to_be_bytes());
+        bytes[16..].copy_from_slice(&self.digest);
+        bytes
+    }
+
+    /// 序列化为字节
+    ///
+    /// Serialize into bytes.
+    pub fn to_bytes(&self) -> [u8; MANIFEST_LEN] {
+        let mut bytes = [0u8; MANIFEST_LEN];
+        bytes[..SIGNED_LEN].copy_from_slice(&self.signed_bytes());
+        bytes[SIGNED_LEN..].copy_from_slice(&self.signature);
+        bytes
+    }
+
+    /// 从字节解析，魔数不符时返回 [`Error::Decode`]
+    ///
+    /// Parse from bytes, returns [`Error::Decode`] when the magic does not match.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < MANIFEST_LEN || bytes[..8] != MAGIC {
+            return Err(Error::Decode);
+        }
+        let word = |at: usize| {
+            u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&bytes[16..SIGNED_LEN]);
+        let mut signature = [0u8; SIGNATURE_LEN];
+        signature.copy_from_slice(&bytes[SIGNED_LEN..MANIFEST_LEN]);
+        Ok(Self {
+            version: word(8),
+            size: word(12),
+            digest,
+            signature,
+        })
+    }
+}
+
+/// 在摘要比对之后检查镜像，例如用 [`Manifest::signed_bytes`] 验证签名或拒绝降级
+///
+/// Checks an image after the digest comparison, e.g. verifies the signature over
+/// [`Manifest::signed_bytes`] or refuses downgrades.
+pub trait Verifier {
+    /// 接受镜像时返回 `true`；`digest` 是从 Flash 读回计算的摘要
+    ///
+    /// Returns `true` to accept the image; `digest` is computed from what was read back from flash.
+    fn verify(&mut self, manifest: &Manifest, digest: &[u8; 32]) -> bool;
+}
+
+/// 只比对摘要，不检查签名
+///
+/// Compares the digest only, no signature check.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HashOnly;
+
+impl Verifier for HashOnly {
+    fn verify(&mut self, _manifest: &Manifest, _digest: &[u8; 32]) -> bool {
+        true
+    }
+}
+
+impl<F: FnMut(&Manifest, &[u8; 32]) -> bool> Verifier for F {
+    fn verify(&mut self, manifest: &Manifest, digest: &[u8; 32]) -> bool {
+        self(manifest, digest)
+    }
+}
+
+/// 把固件镜像写入 Flash 分区的接收存储
+///
+/// Receiving storage writing a firmware image into a flash partition.
+pub struct FlashSink<F, V> {
+    flash: F,
+    verifier: V,
+    /// 分区在 Flash 中的起始偏移(Partition start within the flash)
+    base: u32,
+    /// 分区大小(Partition size)
+    capacity: u32,
+    name: String<MAX_NAME_LEN>,
+    /// 传输的总字节数，含清单(Transfer size, manifest included)
+    total: u32,
+    /// 已收到的字节数，含清单(Bytes received, manifest included)
+    received: u32,
+    header: [u8; MANIFEST_LEN],
+    manifest: Option<Manifest>,
+    page: [u8; PAGE_LEN],
+    page_len: usize,
+    /// 已写入 Flash 的固件字节数(Firmware bytes written to flash)
+    committed: u32,
+    /// 已擦除到的分区偏移(Partition offset erased up to)
+    erased: u32,
+    verified: bool,
+}
+
+impl<F: NorFlash, V: Verifier> FlashSink<F, V> {
+    /// 以 `flash` 中从 `base` 开始、大小为 `capacity` 的分区构建，`base` 和 `capacity` 必须按
+    /// 擦除单位对齐
+    ///
+    /// Build over the partition of `flash` starting at `base` with `capacity` bytes, `base` and
+    /// `capacity` must be aligned to the erase size.
+    pub fn new(flash: F, base: u32, capacity: u32, verifier: V) -> Self {
+        debug_assert!(PAGE_LEN.is_multiple_of(F::WRITE_SIZE));
+        Self {
+            flash,
+            verifier,
+            base,
+            capacity,
+            name: String::new(),
+            total: 0,
+            received: 0,
+            header: [0; MANIFEST_LEN],
+            manifest: None,
+            page: [0xFF; PAGE_LEN],
+            page_len: 0,
+            committed: 0,
+            erased: 0,
+            verified: false,
+        }
+    }
+
+    /// 已收到的清单
+    ///
+    /// The manifest received so far.
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// 镜像是否已完整写入并通过校验
+    ///
+    /// Whether the image has been written completely and passed verification.
+    pub fn is_verified(&self) -> bool {
+        self.verified
+    }
+
+    /// 是否有未完成、可续传的镜像
+    ///
+    /// Whether an unfinished image is waiting to be resumed.
+    pub fn is_partial(&self) -> bool {
+        self.received > 0 && self.received < self.total
+    }
+
+    /// 已写入 Flash 的固件字节数
+    ///
+    /// Firmware bytes written to flash.
+    pub fn committed(&self) -> u32 {
+        self.committed
+    }
+
+    /// 释放 Flash 和校验器
+    ///
+    /// Release the flash and the verifier.
+    pub fn release(self) -> (F, V) {
+        (self.flash, self.verifier)
+    }
+
+    /// 把缓存的数据写入 Flash，末尾不足写入单位的部分以 0xFF 填充
+    ///
+    /// Write the buffered data to flash, padding the tail to the write size with 0xFF.
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.page_len == 0 {
+            return Ok(());
+        }
+        let len = self.page_len.div_ceil(F::WRITE_SIZE) * F::WRITE_SIZE;
+        let end = self.committed + len as u32;
+        if end > self.capacity {
+            return Err(Error::MessageTooLarge);
+        }
+        while self.erased < end {
+            let from = self.base + self.erased;
+            self.flash
+                .erase(from, from + F::ERASE_SIZE as u32)
+                .map_err(|_| Error::Flash)?;
+            self.erased += F::ERASE_SIZE as u32;
+        }
+        self.flash
+            .write(self.base + self.committed, &self.page[..len])
+            .map_err(|_| Error::Flash)?;
+        self.committed += self.page_len as u32;
+        self.page = [0xFF; PAGE_LEN];
+        self.page_len = 0;
+        Ok(())
+    }
+
+    /// 读回 Flash 中的固件并计算摘要
+    ///
+    /// Read the firmware back from flash and compute its digest.
+    fn digest(&mut self, size: u32) -> Result<[u8; 32], Error> {
+        let mut hasher = Sha256::new();
+        let mut chunk = [0u8; PAGE_LEN];
+        let mut offset = 0;
+        while offset < size {
+            let n = ((size - offset) as usize).min(PAGE_LEN);
+            let aligned = n.div_ceil(F::READ_SIZE) * F::READ_SIZE;
+            self.flash
+                .read(self.base + offset, &mut chunk[..aligned])
+                .map_err(|_| Error::Flash)?;
+            hasher.update(&chunk[..n]);
+            offset += n as u32;
+        }
+        Ok(hasher.finalize().into())
+    }
+}
+
+impl<F: NorFlash, V: Verifier> BlockSink for FlashSink<F, V> {
+    fn begin(&mut self, name: &str, size: u32) -> Result<u32, Error> {
+        if (size as usize) < MANIFEST_LEN || size - MANIFEST_LEN as u32 > self.capacity {
+            return Err(Error::MessageTooLarge);
+        }
+        // 同名同大小的镜像从中断处续传(The same image resumes where it stopped)
+        if name == self.name.as_str() && size == self.total && !self.verified {
+            return Ok(self.received);
+        }
+        self.name.clear();
+        let mut len = name.len().min(MAX_NAME_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let _ = self.name.push_str(&name[..len]);
+        self.total = size;
+        self.received = 0;
+        self.manifest = None;
+        self.page = [0xFF; PAGE_LEN];
+        self.page_len = 0;
+        self.committed = 0;
+        self.erased = 0;
+        self.verified = false;
+        Ok(0)
+    }
+
+    fn write(&mut self, offset: u32, mut data: &[u8]) -> Result<(), Error> {
+        let end = offset + data.len() as u32;
+        let mut offset = offset as usize;
+        // 清单(Manifest)
+        if offset < MANIFEST_LEN {
+            let n = data.len().min(MANIFEST_LEN - offset);
+            self.header[offset..offset + n].copy_from_slice(&data[..n]);
+            offset += n;
+            data = &data[n..];
+            if offset == MANIFEST_LEN {
+                let manifest = Manifest::from_bytes(&self.header)?;
+                if manifest.size as usize + MANIFEST_LEN != self.total as usize {
+                    return Err(Error::Decode);
+                }
+                self.manifest = Some(manifest);
+            }
+        }
+        // 固件(Firmware)
+        while !data.is_empty() {
+            let n = data.len().min(PAGE_LEN - self.page_len);
+            self.page[self.page_len..self.page_len + n].copy_from_slice(&data[..n]);
+            self.page_len += n;
+            data = &data[n..];
+            if self.page_len == PAGE_LEN {
+                self.flush()?;
+            }
+        }
+        self.received = end;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        let manifest = self.manifest.clone().ok_or(Error::Decode)?;
+        let digest = self.digest(manifest.size)?;
+        if digest != manifest.digest || !self.verifier.verify(&manifest, &digest) {
+            // 下次从头开始(Start over next time)
+            self.total = 0;
+            self.received = 0;
+            return Err(Error::Verification);
+        }
+        self.verified = true;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::vec::Vec;
+
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    use super::*;
+    use crate::{
+        driver::frame::MAX_FRAME_SIZE,
+        sim::{SimConfig, Simulator},
+        transfer::{
+            tests::{link, message, FakeSender, SESSION},
+            Transfer, CANCEL, CANCEL_REJECTED,
+        },
+    };
+
+    const SECTOR: u32 = 512;
+    const BASE: u32 = SECTOR;
+    const CAPACITY: u32 = 4 * SECTOR;
+
+    /// 内存中的 NOR Flash：写入只能把 1 变为 0，未擦除时为 0x00
+    /// (NOR flash in memory: writes can only clear bits, unerased cells read 0x00)
+    struct MemFlash {
+        cells: [u8; 8 * SECTOR as usize],
+        erases: usize,
+    }
+
+    impl MemFlash {
+        fn new() -> Self {
+            Self {
+                cells: [0; 8 * SECTOR as usize],
+                erases: 0,
+            }
+        }
+    }
+
+    impl ErrorType for MemFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MemFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let at = offset as usize;
+            bytes.copy_from_slice(&self.cells[at..at + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.cells.len()
+        }
+    }
+
+    impl NorFlash for MemFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = SECTOR as usize;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            if !from.is_multiple_of(SECTOR) || !to.is_multiple_of(SECTOR) {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            self.cells[from as usize..to as usize].fill(0xFF);
+            self.erases += 1;
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            if !(offset as usize).is_multiple_of(Self::WRITE_SIZE)
+                || !bytes.len().is_multiple_of(Self::WRITE_SIZE)
+            {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            let at = offset as usize;
+            for (cell, byte) in self.cells[at..at + bytes.len()].iter_mut().zip(bytes) {
+                *cell &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn firmware(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 13 + 5) as u8).collect()
+    }
+
+    /// 清单加固件(Manifest followed by the firmware)
+    fn image(manifest: &Manifest, firmware: &[u8]) -> Vec<u8> {
+        let mut image = manifest.to_bytes().to_vec();
+        image.extend_from_slice(firmware);
+        image
+    }
+
+    fn sink<V: Verifier>(verifier: V) -> FlashSink<MemFlash, V> {
+        FlashSink::new(MemFlash::new(), BASE, CAPACITY, verifier)
+    }
+
+    #[test]
+    fn manifest_round_trips_and_signs_its_header() {
+        let mut manifest = Manifest::new(12, &firmware(600));
+        assert!(!manifest.is_signed());
+        manifest.signature[0] = 0x5A;
+        assert!(manifest.is_signed());
+
+        let bytes = manifest.to_bytes();
+        assert_eq!(Manifest::from_bytes(&bytes).unwrap(), manifest);
+        assert_eq!(bytes[..SIGNED_LEN], manifest.signed_bytes());
+        assert_eq!(manifest.signed_bytes()[8..16], [0, 0, 0, 12, 0, 0, 2, 88]);
+
+        let mut bad = bytes;
+        bad[0] ^= 1;
+        assert!(matches!(Manifest::from_bytes(&bad), Err(Error::Decode)));
+        assert!(matches!(
+            Manifest::from_bytes(&bytes[..MANIFEST_LEN - 1]),
+            Err(Error::Decode)
+        ));
+    }
+
+    #[test]
+    fn sectors_are_erased_on_demand() {
+        let data = firmware(600);
+        let image = image(&Manifest::new(1, &data), &data);
+        let mut sink = sink(HashOnly);
+        sink.begin("fw", image.len() as u32).unwrap();
+
+        // 一页之前不写入 Flash(Nothing reaches the flash before a whole page)
+        sink.write(0, &image[..MANIFEST_LEN + 100]).unwrap();
+        assert_eq!((sink.committed(), sink.flash.erases), (0, 0));
+        sink.write(MANIFEST_LEN as u32 + 100, &image[MANIFEST_LEN + 100..])
+            .unwrap();
+        assert_eq!((sink.committed(), sink.flash.erases), (512, 1));
+        sink.finish().unwrap();
+        assert_eq!((sink.committed(), sink.flash.erases), (600, 2));
+        assert!(sink.is_verified());
+
+        let (flash, _) = sink.release();
+        let (before, rest) = flash.cells.split_at(BASE as usize);
+        assert!(before.iter().all(|cell| *cell == 0));
+        assert_eq!(rest[..600], data[..]);
+        // 固件之后的扇区保持不变(Sectors past the firmware are left alone)
+        assert!(rest[2 * SECTOR as usize..].iter().all(|cell| *cell == 0));
+    }
+
+    #[test]
+    fn the_last_page_is_padded_to_the_write_size() {
+        let data = firmware(601);
+        let image = image(&Manifest::new(1, &data), &data);
+        let mut sink = sink(HashOnly);
+        sink.begin("fw", image.len() as u32).unwrap();
+        sink.write(0, &image).unwrap();
+        sink.finish().unwrap();
+        assert_eq!(sink.committed(), 601);
+
+        let (flash, _) = sink.release();
+        let end = (BASE + 600) as usize;
+        assert_eq!(flash.cells[end], data[600]);
+        assert_eq!(flash.cells[end + 1..end + 4], [0xFF; 3]);
+    }
+
+    #[test]
+    fn the_same_image_resumes() {
+        let data = firmware(600);
+        let image = image(&Manifest::new(1, &data), &data);
+        let size = image.len() as u32;
+        let mut sink = sink(HashOnly);
+        sink.begin("fw", size).unwrap();
+        sink.write(0, &image[..300]).unwrap();
+        assert!(sink.is_partial());
+
+        assert_eq!(sink.begin("fw", size).unwrap(), 300);
+        sink.write(300, &image[300..]).unwrap();
+        sink.finish().unwrap();
+        assert!(sink.is_verified());
+        assert_eq!(sink.manifest().unwrap().version, 1);
+
+        // 另一个镜像从头开始(Another image starts over)
+        assert_eq!(sink.begin("fw", size - 1).unwrap(), 0);
+        assert!(sink.manifest().is_none());
+    }
+
+    #[test]
+    fn digest_mismatch_is_rejected_and_starts_over() {
+        let data = firmware(600);
+        let mut manifest = Manifest::new(1, &data);
+        manifest.digest[0] ^= 1;
+        let image = image(&manifest, &data);
+        let size = image.len() as u32;
+        let mut sink = sink(HashOnly);
+        sink.begin("fw", size).unwrap();
+        sink.write(0, &image).unwrap();
+
+        assert!(matches!(sink.finish(), Err(Error::Verification)));
+        assert!(!sink.is_verified());
+        assert_eq!(sink.begin("fw", size).unwrap(), 0);
+    }
+
+    #[test]
+    fn verifier_rejection_is_reported_to_the_sender() {
+        let data = firmware(200);
+        let manifest = Manifest::new(3, &data);
+        let image = image(&manifest, &data);
+        let mut sim = Simulator::new(SimConfig::default());
+        let sender = FakeSender::new(image.clone());
+        let mut hc14 = link(&mut sim, sender, |peer| FakeSender::new(image).offer(peer));
+        // 拒绝降级(Refuse downgrades)
+        let mut sink = sink(|manifest: &Manifest, _: &[u8; 32]| manifest.version > 3);
+
+        assert!(matches!(
+            Transfer::new(SESSION).receive(&mut hc14, &mut sink, 10_000_000, |_| {}),
+            Err(Error::Verification)
+        ));
+        assert!(!sink.is_verified());
+        let mut peer = hc14.release().0.peer;
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let frame = peer.receive_frame_timeout(&mut buffer, 10_000_000).unwrap();
+        assert_eq!(frame, message(CANCEL, &[CANCEL_REJECTED]));
+    }
+}
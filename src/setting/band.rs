@@ -0,0 +1,364 @@
+//! 地区频段规划：限制可以使用的信道和发射功率
+//!
+//! Regional band plans: restricting the channels and transmit power that may be used.
+//!
+//! 信道表覆盖 415.09–449.86 MHz，其中大部分在多数地区并非免许可频段。[`BandPlan`] 规定信道中心
+//! 频率的范围和最大发射功率，AT配置模式的设置接口会拒绝规划之外的信道
+//! ([`Error::InvalidChannel`])和功率([`Error::InvalidPower`])。驱动默认使用
+//! [`BandPlan::DEFAULT`]，由 `band-cn433`、`band-eu433`、`band-70cm` 特性在编译时选择，同时
+//! 启用多个特性时取它们的交集；也可以在运行时通过
+//! [`Hc14::set_band_plan`](crate::driver::Hc14::set_band_plan) 更换。
+//!
+//! The channel table spans 415.09–449.86 MHz, most of which is not licence-exempt in most regions.
+//! A [`BandPlan`] bounds the centre frequency of channels and the transmit power, and the setting
+//! APIs of AT configuration mode refuse channels ([`Error::InvalidChannel`]) and power levels
+//! ([`Error::InvalidPower`]) outside the plan. Drivers start with [`BandPlan::DEFAULT`], chosen at
+//! compile time by the `band-cn433`, `band-eu433` and `band-70cm` features, with several enabled
+//! their intersection applies; it can also be replaced at runtime through
+//! [`Hc14::set_band_plan`](crate::driver::Hc14::set_band_plan).
+//!
+//! # Example
+//! ```rust
+//! let mut parameters = Parameters::default();
+//! parameters.set_channel(Channel::from(27), &BandPlan::EU433).unwrap();
+//! assert!(parameters.set_power(TransmissionPower::new(20).unwrap(), &BandPlan::EU433).is_err());
+//!
+//! let mut hc14_configure = hc14.into_configuration_mode().unwrap();
+//! hc14_configure.set_band_plan(BandPlan::EU433);
+//! hc14_configure.apply_parameters(&parameters).unwrap();
+//! ```
+use super::{channel::Channel, parameters::Parameters, power::TransmissionPower};
+use crate::Error;
+
+/// 频段规划：信道中心频率的范围(千赫兹，含两端)和最大发射功率
+///
+/// Band plan: the range of channel centre frequencies (kilohertz, both ends included) and the
+/// highest transmit power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BandPlan {
+    min_khz: u32,
+    max_khz: u32,
+    max_power_dbm: u8,
+}
+
+impl BandPlan {
+    /// 不做限制：整个信道表和全部功率
+    ///
+    /// No restriction: the whole channel table and every power level.
+    pub const UNRESTRICTED: Self = Self::new(415_090, 449_860, 20);
+
+    /// 中国 433 MHz 微功率频段，433.00–434.79 MHz，10 mW
+    ///
+    /// China 433 MHz micro-power band, 433.00–434.79 MHz, 10 mW.
+    pub const CN433: Self = Self::new(433_000, 434_790, 10);
+
+    /// 欧洲 433 MHz 短距离设备频段，433.05–434.79 MHz，10 mW
+    ///
+    /// EU 433 MHz short-range device band, 433.05–434.79 MHz, 10 mW.
+    pub const EU433: Self = Self::new(433_050, 434_790, 10);
+
+    /// 业余无线电 70 cm 频段，430–440 MHz，需要执照
+    ///
+    /// Amateur 70 cm band, 430–440 MHz, a licence is required.
+    pub const AMATEUR_70CM: Self = Self::new(430_000, 440_000, 20);
+
+    /// 编译时选择的频段规划：启用的 `band-*` 特性的交集，没有启用时不做限制
+    ///
+    /// Band plan chosen at compile time: the intersection of the enabled `band-*` features, no
+    /// restriction when none is enabled.
+    pub const DEFAULT: Self = {
+        let plan = Self::UNRESTRICTED;
+        let plan = if cfg!(feature = "band-cn433") {
+            plan.intersect(&Self::CN433)
+        } else {
+            plan
+        };
+        let plan = if cfg!(feature = "band-eu433") {
+            plan.intersect(&Self::EU433)
+        } else {
+            plan
+        };
+        if cfg!(feature = "band-70cm") {
+            plan.intersect(&Self::AMATEUR_70CM)
+        } else {
+            plan
+        }
+    };
+
+    /// 构建自定义的频段规划
+    ///
+    /// Build a custom band plan.
+    pub const fn new(min_khz: u32, max_khz: u32, max_power_dbm: u8) -> Self {
+        Self {
+            min_khz,
+            max_khz,
+            max_power_dbm,
+        }
+    }
+
+    /// 同时满足两个规划的频段规划
+    ///
+    /// The band plan satisfying both plans.
+    pub const fn intersect(&self, other: &Self) -> Self {
+        Self {
+            min_khz: if self.min_khz > other.min_khz {
+                self.min_khz
+            } else {
+                other.min_khz
+            },
+            max_khz: if self.max_khz < other.max_khz {
+                self.max_khz
+            } else {
+                other.max_khz
+            },
+            max_power_dbm: if self.max_power_dbm < other.max_power_dbm {
+                self.max_power_dbm
+            } else {
+                other.max_power_dbm
+            },
+        }
+    }
+
+    /// 最低中心频率(千赫兹)
+    ///
+    /// Lowest centre frequency in kilohertz.
+    pub fn min_khz(&self) -> u32 {
+        self.min_khz
+    }
+
+    /// 最高中心频率(千赫兹)
+    ///
+    /// Highest centre frequency in kilohertz.
+    pub fn max_khz(&self) -> u32 {
+        self.max_khz
+    }
+
+    /// 最大发射功率(dBm)
+    ///
+    /// Highest transmit power in dBm.
+    pub fn max_power_dbm(&self) -> u8 {
+        self.max_power_dbm
+    }
+
+    /// 是否允许使用 `channel`
+    ///
+    /// Whether `channel` may be used.
+    pub fn allows_channel(&self, channel: &Channel) -> bool {
+        match channel.get_freq_khz() {
+            Ok(khz) => (self.min_khz..=self.max_khz).contains(&khz),
+            Err(_) => false,
+        }
+    }
+
+    /// 是否允许使用 `power`
+    ///
+    /// Whether `power` may be used.
+    pub fn allows_power(&self, power: &TransmissionPower) -> bool {
+        power.get_power_dbm() <= self.max_power_dbm
+    }
+
+    /// 允许使用的信道
+    ///
+    /// The channels that may be used.
+    pub fn channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        Channel::all().filter(move |channel| self.allows_channel(channel))
+    }
+
+    /// 允许的最大发射功率，规划的上限低于模块的最低功率时返回 `None`
+    ///
+    /// The highest power allowed, `None` when the plan's limit is below the module's lowest level.
+    pub fn max_power(&self) -> Option<TransmissionPower> {
+        TransmissionPower::new(self.max_power_dbm.min(20))
+    }
+
+    /// 检查 `parameters` 的信道和功率
+    ///
+    /// Check the channel and power of `parameters`.
+    pub fn check(&self, parameters: &Parameters) -> Result<(), Error> {
+        if !self.allows_channel(&parameters.channel) {
+            return Err(Error::InvalidChannel);
+        }
+        if !self.allows_power(&parameters.power) {
+            return Err(Error::InvalidPower);
+        }
+        Ok(())
+    }
+}
+
+impl Default for BandPlan {
+    /// 编译时选择的频段规划，见 [`BandPlan::DEFAULT`]
+    ///
+    /// The band plan chosen at compile time, see [`BandPlan::DEFAULT`].
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Parameters {
+    /// 在 `plan` 允许时设置信道
+    ///
+    /// Set the channel when `plan` allows it.
+    pub fn set_channel(&mut self, channel: Channel, plan: &BandPlan) -> Result<(), Error> {
+        if !plan.allows_channel(&channel) {
+            return Err(Error::InvalidChannel);
+        }
+        self.channel = channel;
+        Ok(())
+    }
+
+    /// 在 `plan` 允许时设置发射功率
+    ///
+    /// Set the transmit power when `plan` allows it.
+    pub fn set_power(&mut self, power: TransmissionPower, plan: &BandPlan) -> Result<(), Error> {
+        if !plan.allows_power(&power) {
+            return Err(Error::InvalidPower);
+        }
+        self.power = power;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn power(dbm: u8) -> TransmissionPower {
+        TransmissionPower::new(dbm).unwrap()
+    }
+
+    #[test]
+    fn intersect_keeps_the_narrower_limits() {
+        let plan = BandPlan::CN433.intersect(&BandPlan::EU433);
+        assert_eq!(plan, BandPlan::new(433_050, 434_790, 10));
+        assert_eq!(plan, BandPlan::EU433.intersect(&BandPlan::CN433));
+        assert_eq!(
+            BandPlan::UNRESTRICTED.intersect(&BandPlan::AMATEUR_70CM),
+            BandPlan::AMATEUR_70CM
+        );
+        let plan = BandPlan::AMATEUR_70CM.intersect(&BandPlan::new(400_000, 435_000, 14));
+        assert_eq!(
+            (plan.min_khz(), plan.max_khz(), plan.max_power_dbm()),
+            (430_000, 435_000, 14)
+        );
+    }
+
+    #[test]
+    fn channels_inside_the_plan_are_allowed() {
+        let numbers = |plan: &BandPlan| plan.channels().map(|c| c.get_number()).collect();
+        let cn433: heapless::Vec<u8, 50> = numbers(&BandPlan::CN433);
+        assert_eq!(cn433, [27, 28, 29]);
+        let amateur: heapless::Vec<u8, 50> = numbers(&BandPlan::AMATEUR_70CM);
+        assert_eq!(amateur, (22..=36).collect::<heapless::Vec<u8, 50>>());
+        assert_eq!(BandPlan::UNRESTRICTED.channels().count(), 50);
+
+        // 两端都包含在内(Both ends are included)
+        let plan = BandPlan::new(433_390, 434_000, 20);
+        assert!(plan.allows_channel(&Channel::from(27)));
+        assert!(plan.allows_channel(&Channel::from(28)));
+        assert!(!plan.allows_channel(&Channel::from(26)));
+        assert!(!plan.allows_channel(&Channel::from(29)));
+    }
+
+    #[test]
+    fn power_up_to_the_limit_is_allowed() {
+        assert!(BandPlan::CN433.allows_power(&power(10)));
+        assert!(!BandPlan::CN433.allows_power(&power(11)));
+        assert!(BandPlan::UNRESTRICTED.allows_power(&power(20)));
+        assert_eq!(BandPlan::CN433.max_power(), Some(power(10)));
+        assert_eq!(
+            BandPlan::new(433_000, 434_000, 30).max_power(),
+            Some(power(20))
+        );
+        assert_eq!(BandPlan::new(433_000, 434_000, 5).max_power(), None);
+    }
+
+    #[test]
+    fn check_reports_the_first_violation() {
+        let mut parameters = Parameters {
+            channel: Channel::from(28),
+            power: power(10),
+            ..Parameters::default()
+        };
+        assert!(BandPlan::CN433.check(&parameters).is_ok());
+        parameters.power = power(20);
+        assert!(matches!(
+            BandPlan::CN433.check(&parameters),
+            Err(Error::InvalidPower)
+        ));
+        parameters.channel = Channel::from(1);
+        assert!(matches!(
+            BandPlan::CN433.check(&parameters),
+            Err(Error::InvalidChannel)
+        ));
+        assert!(BandPlan::UNRESTRICTED.check(&parameters).is_ok());
+    }
+
+    #[test]
+    fn parameters_refuse_values_outside_the_plan() {
+        let mut parameters = Parameters {
+            channel: Channel::from(28),
+            power: power(10),
+            ..Parameters::default()
+        };
+        parameters
+            .set_channel(Channel::from(27), &BandPlan::EU433)
+            .unwrap();
+        assert!(matches!(
+            parameters.set_channel(Channel::from(30), &BandPlan::EU433),
+            Err(Error::InvalidChannel)
+        ));
+        assert_eq!(parameters.channel, Channel::from(27));
+
+        parameters.set_power(power(8), &BandPlan::EU433).unwrap();
+        assert!(matches!(
+            parameters.set_power(power(20), &BandPlan::EU433),
+            Err(Error::InvalidPower)
+        ));
+        assert_eq!(parameters.power, power(8));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn configuration_mode_refuses_values_outside_the_plan() {
+        use crate::{
+            driver::Hc14,
+            sim::{SimConfig, Simulator},
+        };
+
+        let mut sim = Simulator::new(SimConfig::default());
+        let node = sim.add_node(Parameters::default());
+        let hc14 = Hc14::new(node.serial, node.key, node.delay).unwrap();
+        let mut configure = hc14.into_configuration_mode().map_err(|e| e.error).unwrap();
+        configure.set_band_plan(BandPlan::CN433);
+        let before = sim.parameters(node.id);
+
+        // 被拒绝的值不会发送给模块(Refused values never reach the module)
+        assert!(matches!(
+            configure.set_channel(Channel::from(1)),
+            Err(Error::InvalidChannel)
+        ));
+        assert!(matches!(
+            configure.set_power(power(20)),
+            Err(Error::InvalidPower)
+        ));
+        let mut buffer = [0u8; 16];
+        assert!(matches!(
+            configure.wirte_set_channel(1, &mut buffer),
+            Err(Error::InvalidChannel)
+        ));
+        assert!(matches!(
+            configure.wirte_set_channel(51, &mut buffer),
+            Err(Error::InvalidChannel)
+        ));
+        assert_eq!(sim.parameters(node.id), before);
+
+        configure.set_channel(Channel::from(29)).unwrap();
+        configure.set_power(power(10)).unwrap();
+        assert_eq!(
+            configure.wirte_set_channel(27, &mut buffer).unwrap(),
+            b"OK+C:27\r\n"
+        );
+        let after = sim.parameters(node.id);
+        assert_eq!((after.channel, after.power), (Channel::from(27), power(10)));
+    }
+}
@@ -28,7 +28,7 @@ pub enum BaudRate {
 }
 
 /// 空中波特率, Baud rate in the air
-#[derive(Debug, Eq, PartialEq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive, ToPrimitive)]
 pub enum AirBaudRate {
     /// S4模式下，500 波特/秒, 5000 bauds per second
     Bps500 = 500,
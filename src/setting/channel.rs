@@ -2,28 +2,114 @@ use crate::{conf::RESPONSE_CHANNEL, driver::normal::format_converter};
 use core::convert::TryFrom;
 use num_derive::{FromPrimitive, ToPrimitive};
 
+/// 信道 1-50 的中心频率(千赫兹)
+///
+/// Centre frequency of channels 1-50 in kilohertz.
+const CHANNEL_KHZ: [u32; 50] = [
+    415_090, 415_700, 416_310, 416_920, 417_530, 418_140, 419_360, 420_580, 421_190, 421_800,
+    422_410, 423_630, 424_240, 424_850, 425_460, 426_070, 426_680, 427_290, 427_900, 429_120,
+    429_730, 430_340, 430_950, 431_560, 432_170, 432_780, 433_390, 434_000, 434_610, 435_220,
+    435_830, 436_440, 437_050, 437_660, 438_270, 438_880, 440_100, 440_710, 441_320, 441_930,
+    442_540, 443_150, 443_760, 444_370, 445_590, 446_200, 446_810, 447_420, 448_640, 449_860,
+];
+
 /// 通信信道
-#[derive(Debug, ToPrimitive, FromPrimitive, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, ToPrimitive, FromPrimitive, PartialEq, Eq)]
 pub struct Channel(u8);
 
 impl Channel {
+    /// 按编号依次遍历全部 50 个信道
+    ///
+    /// Iterate over all 50 channels in order of number.
+    pub fn all() -> impl DoubleEndedIterator<Item = Channel> + ExactSizeIterator {
+        (1..=CHANNEL_KHZ.len() as u8).map(Channel)
+    }
+
+    /// 中心频率正好为 `khz` 千赫兹的信道
+    ///
+    /// The channel whose centre frequency is exactly `khz` kilohertz.
+    pub fn from_freq_khz(khz: u32) -> Option<Channel> {
+        CHANNEL_KHZ
+            .iter()
+            .position(|&f| f == khz)
+            .map(|i| Channel(i as u8 + 1))
+    }
+
+    /// 中心频率最接近 `khz` 千赫兹的信道，距离相同时取编号较小的信道
+    ///
+    /// The channel whose centre frequency is nearest to `khz` kilohertz, the lower number on a tie.
+    pub fn nearest_freq_khz(khz: u32) -> Channel {
+        Channel::all()
+            .min_by_key(|channel| channel.freq_khz().abs_diff(khz))
+            .unwrap_or_default()
+    }
+
+    /// 获取信道编号
+    ///
+    /// Get the channel number.
+    pub fn get_number(&self) -> u8 {
+        self.0
+    }
+
     /// 获取以 MHz 为单位获取信道频率
     ///
     /// Get channel frequency in MHz.
     pub fn get_freq_mhz(&self) -> Result<f32, &'static str> {
-        let channel_mhz: [f32; 50] = [
-            415.09, 415.70, 416.31, 416.92, 417.53, 418.14, 419.36, 420.58, 421.19, 421.80, 422.41,
-            423.63, 424.24, 424.85, 425.46, 426.07, 426.68, 427.29, 427.90, 429.12, 429.73, 430.34,
-            430.95, 431.56, 432.17, 432.78, 433.39, 434.00, 434.61, 435.22, 435.83, 436.44, 437.05,
-            437.66, 438.27, 438.88, 440.10, 440.71, 441.32, 441.93, 442.54, 443.15, 443.76, 444.37,
-            445.59, 446.20, 446.81, 447.42, 448.64, 449.86,
-        ];
+        self.get_freq_khz().map(|khz| khz as f32 / 1000.0)
+    }
+
+    /// 获取以 kHz 为单位的信道频率，不需要浮点运算
+    ///
+    /// Get channel frequency in kHz, without floating point.
+    pub fn get_freq_khz(&self) -> Result<u32, &'static str> {
         if self.0 >= 51 || self.0 == 0 {
             Err("Invalid index, channel range: 1-50")
         } else {
-            Ok(channel_mhz[self.0 as usize - 1])
+            Ok(CHANNEL_KHZ[self.0 as usize - 1])
+        }
+    }
+
+    /// 与 `other` 的中心频率之差(千赫兹)
+    ///
+    /// Distance between the centre frequencies of this channel and `other`, in kilohertz.
+    pub fn spacing_khz(&self, other: &Channel) -> u32 {
+        self.freq_khz().abs_diff(other.freq_khz())
+    }
+
+    /// 编号大一的信道
+    ///
+    /// The channel one number up.
+    pub fn next(&self) -> Option<Channel> {
+        match self.0 {
+            1..=49 => Some(Channel(self.0 + 1)),
+            _ => None,
         }
     }
+
+    /// 编号小一的信道
+    ///
+    /// The channel one number down.
+    pub fn previous(&self) -> Option<Channel> {
+        match self.0 {
+            2..=50 => Some(Channel(self.0 - 1)),
+            _ => None,
+        }
+    }
+
+    /// 中心频率与本信道相差不超过 `khz` 千赫兹的其他信道
+    ///
+    /// The other channels whose centre frequency lies within `khz` kilohertz of this one.
+    pub fn neighbors(&self, khz: u32) -> impl Iterator<Item = Channel> {
+        let this = *self;
+        Channel::all().filter(move |channel| *channel != this && channel.spacing_khz(&this) <= khz)
+    }
+
+    /// 无效信道的频率按 0 计
+    ///
+    /// The frequency of an invalid channel counts as 0.
+    fn freq_khz(&self) -> u32 {
+        self.get_freq_khz().unwrap_or(0)
+    }
 }
 
 impl Default for Channel {
@@ -59,3 +145,74 @@ impl TryFrom<&[u8]> for Channel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(channels: impl Iterator<Item = Channel>) -> heapless::Vec<u8, 50> {
+        channels.map(|channel| channel.get_number()).collect()
+    }
+
+    #[test]
+    fn all_channels_in_order() {
+        assert_eq!(Channel::all().len(), 50);
+        assert_eq!(Channel::all().next(), Some(Channel::from(1)));
+        assert_eq!(Channel::all().next_back(), Some(Channel::from(50)));
+        assert_eq!(Channel::from(50).get_freq_khz(), Ok(449_860));
+        assert!(Channel(0).get_freq_khz().is_err());
+        assert!(Channel(51).get_freq_khz().is_err());
+    }
+
+    #[test]
+    fn from_freq_khz_needs_an_exact_match() {
+        assert_eq!(Channel::from_freq_khz(434_000), Some(Channel::from(28)));
+        assert_eq!(Channel::from_freq_khz(415_090), Some(Channel::from(1)));
+        assert_eq!(Channel::from_freq_khz(434_001), None);
+        assert_eq!(Channel::from_freq_khz(0), None);
+    }
+
+    #[test]
+    fn nearest_freq_khz_clamps_and_prefers_the_lower_channel() {
+        assert_eq!(Channel::nearest_freq_khz(433_920), Channel::from(28));
+        // 信道 1 和 2 的正中间(Halfway between channels 1 and 2)
+        assert_eq!(Channel::nearest_freq_khz(415_395), Channel::from(1));
+        assert_eq!(Channel::nearest_freq_khz(415_396), Channel::from(2));
+        assert_eq!(Channel::nearest_freq_khz(400_000), Channel::from(1));
+        assert_eq!(Channel::nearest_freq_khz(0), Channel::from(1));
+        assert_eq!(Channel::nearest_freq_khz(470_000), Channel::from(50));
+        assert_eq!(Channel::nearest_freq_khz(u32::MAX), Channel::from(50));
+    }
+
+    #[test]
+    fn spacing_is_symmetric() {
+        let (a, b) = (Channel::from(27), Channel::from(28));
+        assert_eq!(a.spacing_khz(&b), 610);
+        assert_eq!(b.spacing_khz(&a), 610);
+        assert_eq!(a.spacing_khz(&a), 0);
+        // 信道表中有间隙(The table has gaps)
+        assert_eq!(Channel::from(6).spacing_khz(&Channel::from(7)), 1_220);
+        assert_eq!(Channel::from(1).spacing_khz(&Channel::from(50)), 34_770);
+    }
+
+    #[test]
+    fn next_and_previous_stop_at_the_ends() {
+        assert_eq!(Channel::from(1).next(), Some(Channel::from(2)));
+        assert_eq!(Channel::from(1).previous(), None);
+        assert_eq!(Channel::from(50).next(), None);
+        assert_eq!(Channel::from(50).previous(), Some(Channel::from(49)));
+    }
+
+    #[test]
+    fn neighbors_within_the_distance() {
+        assert_eq!(numbers(Channel::from(28).neighbors(610)), [27, 29]);
+        assert_eq!(
+            numbers(Channel::from(28).neighbors(1_220)),
+            [26, 27, 29, 30]
+        );
+        assert_eq!(numbers(Channel::from(28).neighbors(609)), []);
+        assert_eq!(numbers(Channel::from(1).neighbors(610)), [2]);
+        assert_eq!(numbers(Channel::from(6).neighbors(610)), [5]);
+        assert_eq!(Channel::from(1).neighbors(u32::MAX).count(), 49);
+    }
+}
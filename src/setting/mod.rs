@@ -1,3 +1,5 @@
+/// 频段规划(Band plans)
+pub mod band;
 /// 波特率数据结构(Baud rate data structure)
 pub mod baudrate;
 /// 无线通信信道数据结构(Wireless communication channel data structure)
@@ -6,6 +8,8 @@ pub mod channel;
 pub mod command;
 /// HC-14 参数(HC-14 Parameters)
 pub mod parameters;
+/// 信道分配(Channel planning)
+pub mod plan;
 /// 传输功率(transmission power)
 pub mod power;
 /// 速率模式数据结构(Speed Data Structures)
@@ -4,7 +4,7 @@ use super::{baudrate::BaudRate, channel::Channel, power::TransmissionPower, spee
 /// 所有 hc14 参数
 ///
 /// All hc14 parameters
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub struct Parameters {
     /// 波特率
     pub baud: BaudRate,
@@ -0,0 +1,216 @@
+//! 同一场地多个网络的信道分配
+//!
+//! Channel allocation for networks sharing one site.
+//!
+//! 同一场地上相互独立的多个 HC-14 网络需要使用间隔足够远的信道，否则会互相干扰。
+//! [`ChannelPlanner`] 在 [`BandPlan`] 允许的信道中为每个网络选一个信道，任意两个信道的中心频率
+//! 至少相隔 `min_separation_khz`，并在此基础上二分查找能达到的最大间隔，让各个网络尽量分散；
+//! 给定间隔时从最低的信道开始贪心选取，可以选出的信道数量最多。结果中的每组 [`Parameters`]
+//! 可以直接交给 [`apply_parameters`](crate::driver::Hc14::apply_parameters)。
+//!
+//! Independent HC-14 networks on one site need channels far enough apart, or they interfere
+//! with each other. [`ChannelPlanner`] picks one channel per network among those the [`BandPlan`]
+//! allows, with the centre frequencies of any two at least `min_separation_khz` apart, and on top
+//! of that binary-searches the largest separation achievable so the networks spread as far as
+//! possible; for a given separation a greedy pass from the lowest channel picks the most channels.
+//! Every [`Parameters`] in the result is ready for
+//! [`apply_parameters`](crate::driver::Hc14::apply_parameters).
+//!
+//! # Example
+//! ```rust
+//! let planner = ChannelPlanner::new(BandPlan::AMATEUR_70CM, 1_500);
+//! let plan = planner.plan::<3>().expect("not enough channels");
+//! hprintln!("channels {} kHz apart", plan.separation_khz);
+//!
+//! let mut hc14_configure = hc14.into_configuration_mode().unwrap();
+//! hc14_configure.apply_parameters(&plan.parameters[0]).unwrap();
+//! ```
+use super::{band::BandPlan, channel::Channel, parameters::Parameters};
+
+/// 分配结果
+///
+/// Allocation result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPlan<const N: usize> {
+    /// 任意两个网络的信道之间的最小间隔(千赫兹)，少于两个网络时为 0
+    /// (Smallest separation between the channels of any two networks, in kilohertz, 0 with fewer
+    /// than two networks)
+    pub separation_khz: u32,
+    /// 每个网络的参数，信道按频率升序排列(Parameters per network, channels in ascending frequency)
+    pub parameters: [Parameters; N],
+}
+
+/// 信道分配器
+///
+/// Channel planner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPlanner {
+    band: BandPlan,
+    min_separation_khz: u32,
+    template: Parameters,
+}
+
+impl ChannelPlanner {
+    /// 在 `band` 中分配信道，信道之间至少相隔 `min_separation_khz` 千赫兹；其余参数取默认值
+    ///
+    /// Allocate channels within `band`, at least `min_separation_khz` kilohertz apart; the other
+    /// parameters keep their defaults.
+    pub fn new(band: BandPlan, min_separation_khz: u32) -> Self {
+        Self {
+            band,
+            min_separation_khz,
+            template: Parameters::default(),
+        }
+    }
+
+    /// 以 `template` 作为各个网络的波特率、速率和功率；功率超出频段规划时降为允许的最大功率
+    ///
+    /// Use `template` for the baud rate, speed and power of every network; a power above the band
+    /// plan is lowered to the highest one allowed.
+    pub fn with_template(mut self, template: Parameters) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// 频段规划
+    ///
+    /// The band plan.
+    pub fn band(&self) -> &BandPlan {
+        &self.band
+    }
+
+    /// 信道之间的最小间隔(千赫兹)
+    ///
+    /// Smallest separation between channels, in kilohertz.
+    pub fn min_separation_khz(&self) -> u32 {
+        self.min_separation_khz
+    }
+
+    /// 以最小间隔最多可以容纳的网络数量
+    ///
+    /// The most networks that fit at the smallest separation.
+    pub fn capacity(&self) -> usize {
+        self.fits(self.min_separation_khz)
+    }
+
+    /// 为 `N` 个网络分配信道；信道或功率不够时返回 `None`
+    ///
+    /// Allocate channels for `N` networks; `None` when there are not enough channels or no power
+    /// level is allowed.
+    pub fn plan<const N: usize>(&self) -> Option<ChannelPlan<N>> {
+        let separation = self.max_separation_khz(N)?;
+        let power = if self.band.allows_power(&self.template.power) {
+            self.template.power
+        } else {
+            self.band.max_power()?
+        };
+
+        let mut parameters = [Parameters {
+            power,
+            ..self.template
+        }; N];
+        for (slot, channel) in parameters.iter_mut().zip(self.pick(separation)) {
+            slot.channel = channel;
+        }
+        Some(ChannelPlan {
+            separation_khz: if N < 2 { 0 } else { separation },
+            parameters,
+        })
+    }
+
+    /// `networks` 个网络能达到的最大间隔(千赫兹)；连最小间隔都达不到时返回 `None`
+    ///
+    /// The largest separation achievable for `networks` networks, in kilohertz; `None` when even the
+    /// smallest separation cannot be met.
+    pub fn max_separation_khz(&self, networks: usize) -> Option<u32> {
+        if self.fits(self.min_separation_khz) < networks {
+            return None;
+        }
+        let mut channels = self.band.channels();
+        let span = match (channels.next(), channels.last()) {
+            (Some(low), Some(high)) => high.spacing_khz(&low),
+            _ => 0,
+        };
+        if networks < 2 || span <= self.min_separation_khz {
+            return Some(self.min_separation_khz);
+        }
+
+        // `low` 总能满足，`high` 总不能满足(`low` always fits, `high` never does)
+        let (mut low, mut high) = (self.min_separation_khz, span + 1);
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if self.fits(middle) >= networks {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        Some(low)
+    }
+
+    /// 以 `separation_khz` 的间隔从最低的信道开始贪心选取的信道数量
+    ///
+    /// Number of channels a greedy pass from the lowest channel picks at `separation_khz`.
+    fn fits(&self, separation_khz: u32) -> usize {
+        self.pick(separation_khz).count()
+    }
+
+    /// 以 `separation_khz` 的间隔从最低的信道开始贪心选取信道
+    ///
+    /// Pick channels greedily from the lowest channel at `separation_khz`.
+    fn pick(&self, separation_khz: u32) -> impl Iterator<Item = Channel> + '_ {
+        let mut last: Option<Channel> = None;
+        self.band.channels().filter(move |channel| {
+            let keep = last.is_none_or(|l| channel.spacing_khz(&l) >= separation_khz);
+            if keep {
+                last = Some(*channel);
+            }
+            keep
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setting::{power::TransmissionPower, speed::Speed};
+
+    #[test]
+    fn networks_spread_as_far_as_possible() {
+        let planner = ChannelPlanner::new(BandPlan::AMATEUR_70CM, 1_500);
+        let plan = planner.plan::<3>().unwrap();
+        assert_eq!(plan.separation_khz, 4_270);
+        let channels = plan.parameters.map(|p| p.channel);
+        assert_eq!(channels, [22, 29, 36].map(Channel::from));
+        assert_eq!(planner.max_separation_khz(3), Some(4_270));
+        // 再大一点就放不下三个网络(Any wider and three networks no longer fit)
+        assert!(ChannelPlanner::new(BandPlan::AMATEUR_70CM, 4_271)
+            .plan::<3>()
+            .is_none());
+    }
+
+    #[test]
+    fn not_enough_channels() {
+        let planner = ChannelPlanner::new(BandPlan::CN433, 1_300);
+        assert_eq!(planner.capacity(), 1);
+        assert!(planner.plan::<2>().is_none());
+        assert_eq!(planner.max_separation_khz(2), None);
+    }
+
+    #[test]
+    fn single_network_and_template() {
+        let template = Parameters {
+            speed: Speed::S8,
+            power: TransmissionPower::new(20).unwrap(),
+            ..Parameters::default()
+        };
+        let planner = ChannelPlanner::new(BandPlan::CN433, 0).with_template(template);
+        let plan = planner.plan::<1>().unwrap();
+        assert_eq!(plan.separation_khz, 0);
+        assert_eq!(plan.parameters[0].channel, Channel::from(27));
+        assert_eq!(plan.parameters[0].speed, Speed::S8);
+        // 功率降为频段规划允许的最大值(The power is lowered to the highest the plan allows)
+        assert_eq!(Some(plan.parameters[0].power), BandPlan::CN433.max_power());
+        assert!(BandPlan::CN433.check(&plan.parameters[0]).is_ok());
+    }
+}
@@ -5,7 +5,7 @@ use num_derive::{FromPrimitive, ToPrimitive};
 /// 无线发射功率，单位: dbm
 ///
 /// Wireless Transmit Power in dbm
-#[derive(Debug, ToPrimitive, FromPrimitive, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, ToPrimitive, FromPrimitive, PartialEq, Eq)]
 pub struct TransmissionPower(u8);
 
 impl TransmissionPower {
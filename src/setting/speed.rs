@@ -1,6 +1,6 @@
 use crate::{conf::RESPONSE_SPEED, driver::normal::format_converter};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// 无线速率等级：1-8，值越大，速率越高
 ///
 /// Wireless rate class: 1-8, the higher the value, the higher the rate
@@ -82,6 +82,59 @@ impl Speed {
             Speed::S7 | Speed::S8 => 250,
         }
     }
+
+    /// 获取当前速率等级首个数据包的延迟(毫秒)，见 [`Speed::get_max_bytes_size`]
+    ///
+    /// Get the delay of the first packet for the current rate class in milliseconds,
+    /// see [`Speed::get_max_bytes_size`].
+    pub fn get_first_packet_delay_ms(&self) -> u32 {
+        match self {
+            Speed::S1 => 5000,
+            Speed::S2 => 2800,
+            Speed::S3 => 2600,
+            Speed::S4 => 1530,
+            Speed::S5 => 1600,
+            Speed::S6 => 1100,
+            Speed::S7 => 1000,
+            Speed::S8 => 700,
+        }
+    }
+
+    /// 获取当前速率等级后续数据包的间隔(毫秒)，即模块发送一个满数据包所需的时间
+    ///
+    /// Get the interval between subsequent packets for the current rate class in milliseconds,
+    /// i.e. the time the module needs to send one full packet.
+    pub fn get_packet_delay_ms(&self) -> u32 {
+        match self {
+            Speed::S1 => 4700,
+            Speed::S2 => 2600,
+            Speed::S3 => 2300,
+            Speed::S4 | Speed::S5 => 1300,
+            Speed::S6 => 800,
+            Speed::S7 => 600,
+            Speed::S8 => 300,
+        }
+    }
+
+    /// 获取发送 `bytes` 字节所需的空中时间(毫秒)：首个数据包的延迟加上其余每个数据包的间隔
+    ///
+    /// Get the air time needed to send `bytes` bytes in milliseconds: the delay of the first packet
+    /// plus the interval of every further packet.
+    pub fn get_airtime_ms(&self, bytes: usize) -> u32 {
+        let packets = bytes.div_ceil(self.get_max_bytes_size()) as u32;
+        match packets {
+            0 => 0,
+            n => self.get_first_packet_delay_ms() + (n - 1) * self.get_packet_delay_ms(),
+        }
+    }
+
+    /// 获取发送 `bytes` 字节所需的空中时间(微秒)，不满的数据包按整包计算，见 [`Speed::get_airtime_ms`]
+    ///
+    /// Get the air time needed to send `bytes` bytes in microseconds, a partial packet counting as a
+    /// whole one, see [`Speed::get_airtime_ms`].
+    pub fn get_airtime_us(&self, bytes: usize) -> u32 {
+        self.get_airtime_ms(bytes).saturating_mul(1000)
+    }
 }
 
 impl Default for Speed {
@@ -0,0 +1,822 @@
+//! 主机端多节点无线信道模拟器(需要 `std` 特性)
+//!
+//! Host-side multi-node radio channel simulator (requires the `std` feature).
+//!
+//! 模拟器创建 N 个虚拟 HC-14，每个节点都有自己的 [`Parameters`]，并提供与真实硬件相同的
+//! 串口、引脚和延迟 trait，因此可以直接在其上构建 [`Hc14`](crate::driver::Hc14) 驱动。
+//!
+//! The simulator creates N virtual HC-14s, each with its own [`Parameters`], exposing the same
+//! serial, pin and delay traits as real hardware, so the [`Hc14`](crate::driver::Hc14) driver runs on it as is.
+//!
+//! - 只有信道、速率等级和空中波特率都相同的节点之间才能传递数据
+//! - 串口写入的字节按 [`Speed::get_max_bytes_size`] 打包，空闲一段时间后发出不满的数据包
+//! - 每个数据包的空中时间取自 [`Speed::get_first_packet_delay_ms`] / [`Speed::get_packet_delay_ms`]
+//! - 同一信道上时间重叠的数据包互相碰撞并全部丢失，发送中的节点收不到数据(半双工)
+//! - 可配置的随机丢包率和节点间的通信范围，模块缓冲区溢出的字节被丢弃
+//! - Key 引脚拉低时节点进入AT配置模式并响应AT指令
+//! - 电源控制引脚拉低时节点断电；Key 引脚为低电平时上电，节点以 9600 bps 进入AT配置模式
+//! - 收到 `AT+SLEEP` 后，节点在 Key 引脚拉高时睡眠，直到再次拉低；可以模拟不支持该指令的固件
+//!
+//! - Data only flows between nodes sharing the channel, rate class and air baud rate
+//! - Bytes written to the serial port are packed per [`Speed::get_max_bytes_size`], a partial packet
+//!   goes out after the line has been idle for a while
+//! - Air time per packet is taken from [`Speed::get_first_packet_delay_ms`] / [`Speed::get_packet_delay_ms`]
+//! - Packets overlapping in time on the same channel collide and are all lost, a transmitting node
+//!   hears nothing (half duplex)
+//! - Configurable random loss and range between nodes, bytes overflowing the module buffer are dropped
+//! - Pulling the key pin low puts a node into AT configuration mode, where it answers AT commands
+//! - Pulling the power-control pin low cuts a node's supply; powered up with the key pin low, the
+//!   node enters AT configuration mode at 9600 bps
+//! - After `AT+SLEEP` a node sleeps once the key pin goes high, until it is pulled low again;
+//!   firmware lacking the command can be simulated
+//!
+//! 时间是虚拟的：延迟、串口写入(每字节一个字符时间)和无数据时的串口读取都会推进时钟。
+//!
+//! Time is virtual: delays, serial writes (one character time per byte) and serial reads that find
+//! no data all advance the clock.
+//!
+//! # Example
+//! ```rust
+//! let mut sim = Simulator::new(SimConfig::default());
+//! let a = sim.add_node(Parameters::default());
+//! let b = sim.add_node(Parameters::default());
+//!
+//! let mut hc14_a = Hc14::new(a.serial, a.key, a.delay).unwrap();
+//! let mut hc14_b = Hc14::new(b.serial, b.key, b.delay).unwrap();
+//!
+//! hc14_a.send_frame(b"ping").unwrap();
+//! let mut buffer = [0u8; 64];
+//! assert_eq!(hc14_b.receive_frame(&mut buffer).unwrap(), b"ping");
+//! ```
+use std::{cell::RefCell, collections::VecDeque, convert::Infallible, rc::Rc};
+
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use num_traits::ToPrimitive;
+
+use crate::setting::{
+    baudrate::BaudRate, channel::Channel, parameters::Parameters, power::TransmissionPower,
+    speed::Speed,
+};
+
+/// 模拟器配置
+///
+/// Simulator configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimConfig {
+    /// 随机丢包率(千分比)
+    ///
+    /// Random packet loss (per mille).
+    pub loss_permille: u16,
+    /// 随机数种子
+    ///
+    /// Random seed.
+    pub seed: u32,
+    /// 串口空闲多少个字符时间后发出不满的数据包
+    ///
+    /// Character times of idle line after which a partial packet is sent.
+    pub idle_gap_chars: u32,
+    /// 模块缓冲区能容纳的数据包数量
+    ///
+    /// Packets the module buffer can hold.
+    pub buffer_packets: usize,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            loss_permille: 0,
+            seed: 0x2545_F491,
+            idle_gap_chars: 4,
+            buffer_packets: 2,
+        }
+    }
+}
+
+/// 模拟器统计
+///
+/// Simulator statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimStats {
+    /// 发出的数据包
+    ///
+    /// Packets transmitted.
+    pub packets_sent: u32,
+    /// 因碰撞丢失的数据包
+    ///
+    /// Packets lost to collisions.
+    pub collisions: u32,
+    /// 成功送达某个节点的次数
+    ///
+    /// Successful deliveries to a node.
+    pub deliveries: u32,
+    /// 因随机丢包或半双工而未送达某个节点的次数
+    ///
+    /// Deliveries to a node missed through random loss or half duplex.
+    pub losses: u32,
+    /// 模块缓冲区溢出而丢弃的字节
+    ///
+    /// Bytes dropped because the module buffer overflowed.
+    pub overflow_bytes: u32,
+}
+
+struct Node {
+    params: Parameters,
+    at_mode: bool,
+    powered: bool,
+    key_low: bool,
+    sleep_armed: bool,
+    /// 固件是否支持 `AT+SLEEP`(Whether the firmware supports `AT+SLEEP`)
+    sleep_supported: bool,
+    asleep: bool,
+    pending: Vec<u8>,
+    last_write_us: u64,
+    queue: VecDeque<Vec<u8>>,
+    tx_start_us: u64,
+    busy_until_us: u64,
+    last_air_end_us: Option<u64>,
+    rx: VecDeque<u8>,
+    command: Vec<u8>,
+    response: VecDeque<u8>,
+}
+
+impl Node {
+    fn char_time_us(&self) -> u64 {
+        10_000_000 / self.params.baud as u64
+    }
+
+    fn awake(&self) -> bool {
+        self.powered && !self.asleep
+    }
+
+    fn buffered(&self) -> usize {
+        self.pending.len() + self.queue.iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// 处理一条AT指令
+    ///
+    /// Handle one AT command.
+    fn respond(&mut self) {
+        let command = std::mem::take(&mut self.command);
+        let command = core::str::from_utf8(&command).unwrap_or("");
+        let params = &mut self.params;
+        let reply = match command {
+            "AT" => "OK\r\n".to_string(),
+            "AT+DEFAULT" => {
+                *params = Parameters::default();
+                "OK+DEFAULT\r\n".to_string()
+            }
+            "AT+VERSION" => "HC-14 simulator\r\n".to_string(),
+            "AT+SLEEP" if self.sleep_supported => {
+                self.sleep_armed = true;
+                "OK+SLEEP\r\n".to_string()
+            }
+            "AT+RX" => [
+                baud_line(params),
+                channel_line(params),
+                speed_line(params),
+                power_line(params),
+            ]
+            .concat(),
+            "AT+B?" => baud_line(params),
+            "AT+C?" => channel_line(params),
+            "AT+S?" => speed_line(params),
+            "AT+P?" => power_line(params),
+            _ => {
+                let value = |prefix: &str| command.strip_prefix(prefix)?.parse::<u32>().ok();
+                if let Some(baud) = value("AT+B").and_then(baud_rate) {
+                    params.baud = baud;
+                    baud_line(params)
+                } else if let Some(ch) = value("AT+C").filter(|ch| (1..=50).contains(ch)) {
+                    params.channel = Channel::from(ch as u8);
+                    channel_line(params)
+                } else if let Some(speed) = value("AT+S").and_then(|s| Speed::new(s as u8)) {
+                    params.speed = speed;
+                    speed_line(params)
+                } else if let Some(power) =
+                    value("AT+P").and_then(|p| TransmissionPower::new(p as u8))
+                {
+                    params.power = power;
+                    power_line(params)
+                } else {
+                    "ERROR\r\n".to_string()
+                }
+            }
+        };
+        self.response.extend(reply.bytes());
+    }
+}
+
+fn baud_rate(value: u32) -> Option<BaudRate> {
+    match value {
+        1200 | 2400 | 4800 | 9600 | 19200 | 38400 | 57600 | 115200 => Some(BaudRate::from(value)),
+        _ => None,
+    }
+}
+
+fn baud_line(params: &Parameters) -> String {
+    format!("OK+B:{}\r\n", params.baud as u32)
+}
+
+fn channel_line(params: &Parameters) -> String {
+    format!("OK+C:{}\r\n", params.channel.to_u8().unwrap_or(0))
+}
+
+fn speed_line(params: &Parameters) -> String {
+    let speed = match params.speed {
+        Speed::S1 => 1,
+        Speed::S2 => 2,
+        Speed::S3 => 3,
+        Speed::S4 => 4,
+        Speed::S5 => 5,
+        Speed::S6 => 6,
+        Speed::S7 => 7,
+        Speed::S8 => 8,
+    };
+    format!("OK+S:{}\r\n", speed)
+}
+
+fn power_line(params: &Parameters) -> String {
+    format!("OK+P:+{}dBm\r\n", params.power.get_power_dbm())
+}
+
+struct Transmission {
+    from: usize,
+    params: Parameters,
+    start_us: u64,
+    end_us: u64,
+    data: Vec<u8>,
+    collided: bool,
+}
+
+struct Medium {
+    config: SimConfig,
+    now_us: u64,
+    rng: u32,
+    nodes: Vec<Node>,
+    out_of_range: Vec<(usize, usize)>,
+    in_air: Vec<Transmission>,
+    stats: SimStats,
+}
+
+impl Medium {
+    fn random_permille(&mut self) -> u16 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng % 1000) as u16
+    }
+
+    fn in_range(&self, a: usize, b: usize) -> bool {
+        !self.out_of_range.contains(&(a.min(b), a.max(b)))
+    }
+
+    fn idle_gap_us(&self, node: &Node) -> u64 {
+        self.config.idle_gap_chars as u64 * node.char_time_us()
+    }
+
+    /// 推进时钟并处理期间发生的所有事件
+    ///
+    /// Advance the clock and handle every event in between.
+    fn advance(&mut self, us: u64) {
+        let target = self.now_us + us;
+        self.process();
+        while let Some(next) = self.next_event().filter(|next| *next <= target) {
+            self.now_us = next;
+            self.process();
+        }
+        self.now_us = target;
+        self.process();
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        let now = self.now_us;
+        let ends = self.in_air.iter().map(|t| t.end_us);
+        let nodes = self.nodes.iter().flat_map(|node| {
+            let gap = self.idle_gap_us(node);
+            let flush = (!node.pending.is_empty() || !node.command.is_empty())
+                .then_some(node.last_write_us + gap);
+            let start = (!node.queue.is_empty()).then_some(node.busy_until_us);
+            flush.into_iter().chain(start)
+        });
+        ends.chain(nodes).filter(|t| *t > now).min()
+    }
+
+    fn process(&mut self) {
+        let now = self.now_us;
+
+        let mut i = 0;
+        while i < self.in_air.len() {
+            if self.in_air[i].end_us <= now {
+                let transmission = self.in_air.remove(i);
+                self.deliver(transmission);
+            } else {
+                i += 1;
+            }
+        }
+
+        for id in 0..self.nodes.len() {
+            let gap = self.idle_gap_us(&self.nodes[id]);
+            let node = &mut self.nodes[id];
+            let idle = now >= node.last_write_us + gap;
+            if !node.awake() {
+                continue;
+            }
+            if node.at_mode {
+                if idle && !node.command.is_empty() && node.response.is_empty() {
+                    node.respond();
+                }
+                continue;
+            }
+
+            let max = node.params.speed.get_max_bytes_size();
+            while node.pending.len() >= max {
+                let rest = node.pending.split_off(max);
+                let packet = std::mem::replace(&mut node.pending, rest);
+                node.queue.push_back(packet);
+            }
+            if idle && !node.pending.is_empty() {
+                let packet = std::mem::take(&mut node.pending);
+                node.queue.push_back(packet);
+            }
+            if now >= node.busy_until_us {
+                if let Some(data) = node.queue.pop_front() {
+                    self.start(id, data);
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, id: usize, data: Vec<u8>) {
+        let now = self.now_us;
+        let node = &mut self.nodes[id];
+        let speed = node.params.speed;
+        let streaming = node
+            .last_air_end_us
+            .is_some_and(|end| now - end < speed.get_packet_delay_ms() as u64 * 1000);
+        let air_ms = if streaming {
+            speed.get_packet_delay_ms()
+        } else {
+            speed.get_first_packet_delay_ms()
+        };
+        let end_us = now + air_ms as u64 * 1000;
+        node.tx_start_us = now;
+        node.busy_until_us = end_us;
+        node.last_air_end_us = Some(end_us);
+
+        let params = node.params;
+        let mut collided = false;
+        for other in self.in_air.iter_mut() {
+            if other.params.channel == params.channel {
+                other.collided = true;
+                collided = true;
+            }
+        }
+        self.stats.packets_sent += 1;
+        self.in_air.push(Transmission {
+            from: id,
+            params,
+            start_us: now,
+            end_us,
+            data,
+            collided,
+        });
+    }
+
+    fn deliver(&mut self, transmission: Transmission) {
+        if transmission.collided {
+            self.stats.collisions += 1;
+            return;
+        }
+        let sent = transmission.params;
+        for id in 0..self.nodes.len() {
+            let node = &self.nodes[id];
+            let params = node.params;
+            if id == transmission.from
+                || !self.in_range(id, transmission.from)
+                || node.at_mode
+                || !node.awake()
+                || params.channel != sent.channel
+                || params.speed != sent.speed
+                || params.get_air_baud() != sent.get_air_baud()
+            {
+                continue;
+            }
+            let transmitting = node.tx_start_us < transmission.end_us
+                && node.busy_until_us > transmission.start_us;
+            let lost = self.random_permille() < self.config.loss_permille;
+            if transmitting || lost {
+                self.stats.losses += 1;
+                continue;
+            }
+            self.nodes[id].rx.extend(&transmission.data);
+            self.stats.deliveries += 1;
+        }
+    }
+}
+
+/// 模拟器：所有虚拟节点共享同一个无线信道和时钟
+///
+/// Simulator: every virtual node shares one radio medium and one clock.
+pub struct Simulator {
+    medium: Rc<RefCell<Medium>>,
+}
+
+/// 一个虚拟 HC-14 的硬件资源
+///
+/// Hardware resources of one virtual HC-14.
+pub struct SimNode {
+    /// 节点编号
+    ///
+    /// Node index.
+    pub id: usize,
+    /// 串口
+    ///
+    /// Serial port.
+    pub serial: SimSerial,
+    /// Key 引脚
+    ///
+    /// Key pin.
+    pub key: SimPin,
+    /// 电源控制引脚
+    ///
+    /// Power-control pin.
+    pub power: SimPowerPin,
+    /// 延迟(推进虚拟时钟)
+    ///
+    /// Delay (advances the virtual clock).
+    pub delay: SimDelay,
+}
+
+impl Simulator {
+    /// 构建模拟器
+    ///
+    /// Build a simulator.
+    pub fn new(config: SimConfig) -> Self {
+        Self {
+            medium: Rc::new(RefCell::new(Medium {
+                config,
+                now_us: 0,
+                rng: config.seed.max(1),
+                nodes: Vec::new(),
+                out_of_range: Vec::new(),
+                in_air: Vec::new(),
+                stats: SimStats::default(),
+            })),
+        }
+    }
+
+    /// 添加一个使用 `params` 的虚拟节点，Key 引脚初始为高电平(正常模式)
+    ///
+    /// Add a virtual node using `params`, the key pin starts high (normal mode).
+    pub fn add_node(&mut self, params: Parameters) -> SimNode {
+        let mut medium = self.medium.borrow_mut();
+        let id = medium.nodes.len();
+        medium.nodes.push(Node {
+            params,
+            at_mode: false,
+            powered: true,
+            key_low: false,
+            sleep_armed: false,
+            sleep_supported: true,
+            asleep: false,
+            pending: Vec::new(),
+            last_write_us: 0,
+            queue: VecDeque::new(),
+            tx_start_us: 0,
+            busy_until_us: 0,
+            last_air_end_us: None,
+            rx: VecDeque::new(),
+            command: Vec::new(),
+            response: VecDeque::new(),
+        });
+        SimNode {
+            id,
+            serial: SimSerial {
+                medium: self.medium.clone(),
+                id,
+            },
+            key: SimPin {
+                medium: self.medium.clone(),
+                id,
+            },
+            power: SimPowerPin {
+                medium: self.medium.clone(),
+                id,
+            },
+            delay: SimDelay {
+                medium: self.medium.clone(),
+            },
+        }
+    }
+
+    /// 节点当前的参数
+    ///
+    /// Current parameters of a node.
+    pub fn parameters(&self, id: usize) -> Parameters {
+        self.medium.borrow().nodes[id].params
+    }
+
+    /// 直接修改节点的参数
+    ///
+    /// Change the parameters of a node directly.
+    pub fn set_parameters(&mut self, id: usize, params: Parameters) {
+        self.medium.borrow_mut().nodes[id].params = params;
+    }
+
+    /// 设置节点的固件是否支持 `AT+SLEEP`(默认支持)，不支持时以 `ERROR` 应答
+    ///
+    /// Set whether the firmware of a node supports `AT+SLEEP` (it does by default), answering
+    /// `ERROR` when it does not.
+    pub fn set_sleep_supported(&mut self, id: usize, supported: bool) {
+        self.medium.borrow_mut().nodes[id].sleep_supported = supported;
+    }
+
+    /// 设置两个节点是否在彼此的通信范围内(默认所有节点都在范围内)
+    ///
+    /// Set whether two nodes are within range of each other (by default every node is).
+    pub fn set_in_range(&mut self, a: usize, b: usize, in_range: bool) {
+        let mut medium = self.medium.borrow_mut();
+        let pair = (a.min(b), a.max(b));
+        medium.out_of_range.retain(|p| *p != pair);
+        if !in_range {
+            medium.out_of_range.push(pair);
+        }
+    }
+
+    /// 当前的虚拟时间(微秒)
+    ///
+    /// Current virtual time in microseconds.
+    pub fn now_us(&self) -> u64 {
+        self.medium.borrow().now_us
+    }
+
+    /// 推进虚拟时间
+    ///
+    /// Advance the virtual time.
+    pub fn advance_us(&mut self, us: u64) {
+        self.medium.borrow_mut().advance(us);
+    }
+
+    /// 到目前为止的统计
+    ///
+    /// Statistics so far.
+    pub fn stats(&self) -> SimStats {
+        self.medium.borrow().stats
+    }
+}
+
+/// 虚拟节点的串口
+///
+/// Serial port of a virtual node.
+pub struct SimSerial {
+    medium: Rc<RefCell<Medium>>,
+    id: usize,
+}
+
+impl Read<u8> for SimSerial {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        let mut medium = self.medium.borrow_mut();
+        let node = &mut medium.nodes[self.id];
+        if node.at_mode && node.response.is_empty() && !node.command.is_empty() {
+            node.respond();
+        }
+        let byte = if node.at_mode {
+            node.response.pop_front()
+        } else {
+            node.rx.pop_front()
+        };
+        match byte {
+            Some(byte) => Ok(byte),
+            None => {
+                let char_time = node.char_time_us();
+                medium.advance(char_time);
+                Err(nb::Error::WouldBlock)
+            }
+        }
+    }
+}
+
+impl Write<u8> for SimSerial {
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        let mut medium = self.medium.borrow_mut();
+        let now = medium.now_us;
+        let capacity = medium.config.buffer_packets;
+        let node = &mut medium.nodes[self.id];
+        node.last_write_us = now;
+        let char_time = node.char_time_us();
+        if !node.awake() {
+            // 断电或睡眠的模块收不到任何数据(A module without power or asleep takes nothing in)
+        } else if node.at_mode {
+            node.command.push(word);
+        } else if node.buffered() < capacity * node.params.speed.get_max_bytes_size() {
+            node.pending.push(word);
+        } else {
+            medium.stats.overflow_bytes += 1;
+        }
+        medium.advance(char_time);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// 虚拟节点的 Key 引脚：低电平进入AT配置模式
+///
+/// Key pin of a virtual node: low enters AT configuration mode.
+pub struct SimPin {
+    medium: Rc<RefCell<Medium>>,
+    id: usize,
+}
+
+impl OutputPin for SimPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut medium = self.medium.borrow_mut();
+        let node = &mut medium.nodes[self.id];
+        node.key_low = true;
+        node.asleep = false;
+        node.at_mode = node.powered;
+        node.command.clear();
+        node.response.clear();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut medium = self.medium.borrow_mut();
+        let node = &mut medium.nodes[self.id];
+        node.key_low = false;
+        node.at_mode = false;
+        node.asleep = std::mem::take(&mut node.sleep_armed);
+        Ok(())
+    }
+}
+
+/// 虚拟节点的电源控制引脚：低电平断电，Key 引脚为低电平时上电会以 9600 bps 进入AT配置模式
+///
+/// Power-control pin of a virtual node: low cuts the supply, powering up with the key pin low
+/// enters AT configuration mode at 9600 bps.
+pub struct SimPowerPin {
+    medium: Rc<RefCell<Medium>>,
+    id: usize,
+}
+
+impl OutputPin for SimPowerPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut medium = self.medium.borrow_mut();
+        let node = &mut medium.nodes[self.id];
+        node.powered = false;
+        node.at_mode = false;
+        node.sleep_armed = false;
+        node.asleep = false;
+        node.pending.clear();
+        node.queue.clear();
+        node.rx.clear();
+        node.command.clear();
+        node.response.clear();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut medium = self.medium.borrow_mut();
+        let node = &mut medium.nodes[self.id];
+        if !node.powered {
+            node.powered = true;
+            if node.key_low {
+                node.at_mode = true;
+                node.params.baud = BaudRate::Bps9600;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 推进虚拟时钟的延迟
+///
+/// Delay that advances the virtual clock.
+pub struct SimDelay {
+    medium: Rc<RefCell<Medium>>,
+}
+
+impl DelayUs<u32> for SimDelay {
+    fn delay_us(&mut self, us: u32) {
+        self.medium.borrow_mut().advance(us as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(serial: &mut SimSerial) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Ok(byte) = serial.read() {
+            out.push(byte);
+        }
+        out
+    }
+
+    fn send(serial: &mut SimSerial, bytes: &[u8]) {
+        for byte in bytes {
+            serial.write(*byte).unwrap();
+        }
+    }
+
+    #[test]
+    fn delivery_needs_matching_parameters() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let mut a = sim.add_node(Parameters::default());
+        let mut b = sim.add_node(Parameters::default());
+        let mut c = sim.add_node(Parameters {
+            channel: Channel::from(2),
+            ..Parameters::default()
+        });
+        let mut d = sim.add_node(Parameters {
+            speed: Speed::S8,
+            ..Parameters::default()
+        });
+
+        send(&mut a.serial, b"hello");
+        sim.advance_us(5_000_000);
+        assert_eq!(drain(&mut b.serial), b"hello");
+        assert!(drain(&mut c.serial).is_empty());
+        assert!(drain(&mut d.serial).is_empty());
+        assert_eq!(sim.stats().deliveries, 1);
+    }
+
+    #[test]
+    fn packets_take_their_air_time() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let mut a = sim.add_node(Parameters::default());
+        let mut b = sim.add_node(Parameters::default());
+
+        // 两个数据包：首包 2.6 s，第二包 2.3 s(Two packets: 2.6 s for the first, 2.3 s for the second)
+        send(&mut a.serial, &[1u8; 100]);
+        sim.advance_us(2_700_000);
+        assert_eq!(drain(&mut b.serial).len(), 80);
+        sim.advance_us(2_300_000);
+        assert_eq!(drain(&mut b.serial).len(), 20);
+        assert_eq!(sim.stats().packets_sent, 2);
+    }
+
+    #[test]
+    fn overlapping_packets_collide() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let mut a = sim.add_node(Parameters::default());
+        let mut b = sim.add_node(Parameters::default());
+        let mut c = sim.add_node(Parameters::default());
+
+        send(&mut a.serial, b"from a");
+        send(&mut b.serial, b"from b");
+        sim.advance_us(5_000_000);
+        assert!(drain(&mut c.serial).is_empty());
+        assert_eq!(sim.stats().collisions, 2);
+    }
+
+    #[test]
+    fn range_and_loss() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let mut a = sim.add_node(Parameters::default());
+        let mut b = sim.add_node(Parameters::default());
+        sim.set_in_range(a.id, b.id, false);
+        send(&mut a.serial, b"far");
+        sim.advance_us(5_000_000);
+        assert!(drain(&mut b.serial).is_empty());
+
+        let mut sim = Simulator::new(SimConfig {
+            loss_permille: 1000,
+            ..SimConfig::default()
+        });
+        let mut a = sim.add_node(Parameters::default());
+        let mut b = sim.add_node(Parameters::default());
+        send(&mut a.serial, b"lost");
+        sim.advance_us(5_000_000);
+        assert!(drain(&mut b.serial).is_empty());
+        assert_eq!(sim.stats().losses, 1);
+    }
+
+    #[test]
+    fn at_commands_in_configuration_mode() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let mut a = sim.add_node(Parameters::default());
+        a.key.set_low().unwrap();
+        send(&mut a.serial, b"AT+C12");
+        assert_eq!(drain(&mut a.serial), b"OK+C:12\r\n");
+        assert_eq!(sim.parameters(a.id).channel, Channel::from(12));
+        send(&mut a.serial, b"AT+X");
+        assert_eq!(drain(&mut a.serial), b"ERROR\r\n");
+    }
+}
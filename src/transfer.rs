@@ -0,0 +1,1107 @@
+//! 基于 HC-14 正常模式的可续传块传输协议
+//!
+//! Resumable block-transfer protocol on top of the HC-14 normal mode.
+//!
+//! 发送端先发出文件名和大小(OFFER)，接收端回复从哪个偏移开始(ACCEPT)，因此中断的传输可以
+//! 从已收到的位置继续。数据块按窗口发送(回退 N 帧)，每块带有偏移和 CRC，并正好占满当前
+//! 速率等级的一个数据包；窗口的最后一块请求确认，接收端以累计确认(ACK)回复下一个期望的偏移。
+//!
+//! The sender first offers the file name and size (OFFER), and the receiver answers with the offset
+//! to start from (ACCEPT), so an interrupted transfer continues where it stopped. Blocks go out in
+//! windows (go-back-N), each with its offset and a CRC and exactly filling one packet of the current
+//! rate class; the last block of a window asks for an acknowledgement, and the receiver answers with
+//! a cumulative ACK carrying the next offset it expects.
+//!
+//! # Example
+//! ```rust
+//! // 发送端(Sender)
+//! let config = b"interval=60\nchannel=5\n";
+//! Transfer::new(1).send(&mut hc14, "node.cfg", &mut &config[..], |p| {
+//!     hprintln!("{}/{}", p.transferred, p.total);
+//! }).unwrap();
+//!
+//! // 接收端(Receiver)
+//! let mut storage = [0u8; 1024];
+//! let mut sink = SliceSink::new(&mut storage);
+//! let offer = Transfer::new(1).receive(&mut hc14, &mut sink, 60_000_000, |_| {}).unwrap();
+//! hprintln!("{}: {:?}", offer.name, sink.data());
+//! ```
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::OutputPin,
+    serial::{Read, Write},
+};
+use heapless::String;
+
+use crate::{
+    driver::{frame::MAX_FRAME_SIZE, modbus::crc16, stats, Hc14, Normal},
+    Error,
+};
+
+const OFFER: u8 = 0xA1;
+const ACCEPT: u8 = 0xA2;
+const DATA: u8 = 0xA3;
+const ACK: u8 = 0xA4;
+const DONE: u8 = 0xA5;
+const DONE_ACK: u8 = 0xA6;
+pub(crate) const CANCEL: u8 = 0xA7;
+
+/// 数据块标志：请求确认
+const FLAG_ACK_REQUEST: u8 = 0x01;
+
+/// 数据块头部长度：类型、会话、标志、偏移和 CRC
+///
+/// Data block header length: kind, session, flags, offset and CRC.
+pub const BLOCK_HEADER_LEN: usize = 10;
+
+/// 文件名的最大长度，超出部分被截断
+///
+/// Longest file name, longer names are truncated.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// 取消原因：接收端存储出错
+///
+/// Cancel reason: the receiver's storage failed.
+pub const CANCEL_STORAGE: u8 = 0x01;
+
+/// 取消原因：接收端在结束时拒绝了数据(例如校验失败)
+///
+/// Cancel reason: the receiver refused the data when finishing (e.g. a failed verification).
+pub const CANCEL_REJECTED: u8 = 0x02;
+
+/// 传输进度
+///
+/// Transfer progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// 已传输的字节数(Bytes transferred)
+    pub transferred: u32,
+    /// 总字节数(Total bytes)
+    pub total: u32,
+}
+
+impl Progress {
+    /// 完成的百分比
+    ///
+    /// Percentage done.
+    pub fn percent(&self) -> u8 {
+        if self.total == 0 {
+            return 100;
+        }
+        (u64::from(self.transferred) * 100 / u64::from(self.total)) as u8
+    }
+}
+
+/// 接收端收到的文件信息
+///
+/// File information received by the receiver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    /// 文件名(File name)
+    pub name: String<MAX_NAME_LEN>,
+    /// 文件大小(File size)
+    pub size: u32,
+}
+
+/// 发送端的数据来源
+///
+/// Data source of the sender.
+pub trait BlockSource {
+    /// 数据的总字节数
+    ///
+    /// Total size of the data in bytes.
+    fn size(&self) -> u32;
+
+    /// 从 `offset` 读取数据填入 `buffer`，返回读取的字节数
+    ///
+    /// Read data at `offset` into `buffer`, returns the number of bytes read.
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// 接收端的数据存储
+///
+/// Data storage of the receiver.
+pub trait BlockSink {
+    /// 开始接收一个文件，返回续传的起始偏移(已保存的字节数)，从头开始时返回 0
+    ///
+    /// Start receiving a file, returns the offset to resume from (bytes already stored), 0 to start
+    /// over.
+    fn begin(&mut self, name: &str, size: u32) -> Result<u32, Error>;
+
+    /// 在 `offset` 处写入数据，偏移总是连续递增
+    ///
+    /// Write data at `offset`, offsets always increase contiguously.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Error>;
+
+    /// 全部数据已收到
+    ///
+    /// All data has been received.
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl BlockSource for &[u8] {
+    fn size(&self) -> u32 {
+        self.len() as u32
+    }
+
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<usize, Error> {
+        let data = self.get(offset as usize..).ok_or(Error::Read)?;
+        let n = data.len().min(buffer.len());
+        buffer[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+/// 接收到内存中的存储，大小相同的传输会从上次中断处续传
+///
+/// Storage receiving into memory, a transfer of the same size resumes where the last one stopped.
+#[derive(Debug)]
+pub struct SliceSink<'a> {
+    buffer: &'a mut [u8],
+    size: u32,
+    received: u32,
+}
+
+impl<'a> SliceSink<'a> {
+    /// 由缓冲区构建
+    ///
+    /// Build from a buffer.
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            size: 0,
+            received: 0,
+        }
+    }
+
+    /// 已收到的数据
+    ///
+    /// Data received so far.
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[..self.received as usize]
+    }
+
+    /// 是否已收到全部数据
+    ///
+    /// Whether all data has been received.
+    pub fn is_complete(&self) -> bool {
+        self.received == self.size
+    }
+}
+
+impl<'a> BlockSink for SliceSink<'a> {
+    fn begin(&mut self, _name: &str, size: u32) -> Result<u32, Error> {
+        if size as usize > self.buffer.len() {
+            return Err(Error::MessageTooLarge);
+        }
+        if size != self.size {
+            self.size = size;
+            self.received = 0;
+        }
+        Ok(self.received)
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        let start = offset as usize;
+        self.buffer
+            .get_mut(start..start + data.len())
+            .ok_or(Error::MessageTooLarge)?
+            .copy_from_slice(data);
+        self.received = offset + data.len() as u32;
+        Ok(())
+    }
+}
+
+/// 一次传输会话：会话号、窗口大小和重试次数
+///
+/// One transfer session: session number, window size and retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer {
+    session: u16,
+    window: u8,
+    retries: u8,
+    turnaround_us: u32,
+}
+
+impl Transfer {
+    /// 构建传输会话，两端必须使用相同的会话号
+    ///
+    /// Build a transfer session, both ends must use the same session number.
+    pub fn new(session: u16) -> Self {
+        Self {
+            session,
+            window: 4,
+            retries: 5,
+            turnaround_us: 300_000,
+        }
+    }
+
+    /// 每个窗口的数据块数(默认 4)
+    ///
+    /// Blocks per window (4 by default).
+    pub fn set_window(&mut self, window: u8) {
+        self.window = window.max(1);
+    }
+
+    /// 没有进展时的重试次数(默认 5)，包括应答超时和没有推进的确认
+    ///
+    /// Retries without progress (5 by default), counting both reply timeouts and acknowledgements
+    /// that do not advance.
+    pub fn set_retries(&mut self, retries: u8) {
+        self.retries = retries;
+    }
+
+    /// 对端处理的时间余量，计入应答超时(默认 300 ms)
+    ///
+    /// Processing margin of the other end added to the reply timeout (300 ms by default).
+    pub fn set_turnaround_us(&mut self, turnaround_us: u32) {
+        self.turnaround_us = turnaround_us;
+    }
+
+    /// 当前速率等级下每块携带的数据字节数
+    ///
+    /// Data bytes carried per block at the current rate class.
+    pub fn block_size<S, P, D, W>(&self, hc14: &Hc14<S, P, D, Normal, W>) -> usize
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        hc14.max_frame_payload() - BLOCK_HEADER_LEN
+    }
+
+    /// 发送 `source` 中的数据，`progress` 在每次确认后调用
+    ///
+    /// Send the data of `source`, `progress` is called after every acknowledgement.
+    pub fn send<S, P, D, W, B>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        name: &str,
+        source: &mut B,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+        B: BlockSource,
+    {
+        let total = source.size();
+        let block_size = self.block_size(hc14);
+        let mut frame = [0u8; MAX_FRAME_SIZE];
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+
+        // 报价(Offer)
+        let mut len = self.header(OFFER, &mut frame);
+        frame[len..len + 4].copy_from_slice(&total.to_be_bytes());
+        len += 4;
+        let mut name_len = name
+            .len()
+            .min(MAX_NAME_LEN)
+            .min(hc14.max_frame_payload() - len);
+        while !name.is_char_boundary(name_len) {
+            name_len -= 1;
+        }
+        frame[len..len + name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+        len += name_len;
+        let mut base = self.exchange(hc14, &frame[..len], &mut buffer, ACCEPT)?;
+        progress(Progress {
+            transferred: base,
+            total,
+        });
+
+        // 数据(Data)
+        let mut retries = 0;
+        while base < total {
+            let mut offset = base;
+            for i in 0..self.window {
+                let mut len = self.header(DATA, &mut frame);
+                let last = i + 1 == self.window;
+                let n = source.read(offset, &mut frame[len + 7..len + 7 + block_size])?;
+                let n = n.min((total - offset) as usize);
+                let final_block = offset + n as u32 >= total;
+                frame[len] = if last || final_block {
+                    FLAG_ACK_REQUEST
+                } else {
+                    0
+                };
+                frame[len + 1..len + 5].copy_from_slice(&offset.to_be_bytes());
+                let crc = crc16(&frame[len + 7..len + 7 + n]);
+                frame[len + 5..len + 7].copy_from_slice(&crc.to_be_bytes());
+                len += 7 + n;
+                hc14.send_frame(&frame[..len])?;
+                offset += n as u32;
+                if final_block || n == 0 {
+                    break;
+                }
+            }
+            match self.wait_reply(hc14, &mut buffer, ACK)? {
+                Some(next) if next > base => {
+                    base = next.min(total);
+                    retries = 0;
+                    progress(Progress {
+                        transferred: base,
+                        total,
+                    });
+                }
+                // 没有进展：从确认的位置重发(No progress: resend from the acknowledged offset)
+                Some(next) => {
+                    stats::add(&mut hc14.stats_mut().retries, 1);
+                    base = next;
+                    retries += 1;
+                    if retries > self.retries {
+                        return Err(Error::Timeout);
+                    }
+                }
+                None => {
+                    stats::add(&mut hc14.stats_mut().retries, 1);
+                    retries += 1;
+                    if retries > self.retries {
+                        return Err(Error::Timeout);
+                    }
+                }
+            }
+        }
+
+        // 结束(Done)
+        let mut len = self.header(DONE, &mut frame);
+        frame[len..len + 4].copy_from_slice(&total.to_be_bytes());
+        len += 4;
+        self.exchange(hc14, &frame[..len], &mut buffer, DONE_ACK)?;
+        Ok(())
+    }
+
+    /// 在 `timeout_us` 微秒内等待传输开始并接收到 `sink` 中，`progress` 在每个数据块后调用。
+    /// 链路中断时返回 [`Error::Timeout`]，`sink` 中保留已收到的数据以便续传
+    ///
+    /// Wait up to `timeout_us` microseconds for a transfer to start and receive it into `sink`,
+    /// `progress` is called after every block. Returns [`Error::Timeout`] when the link breaks off,
+    /// `sink` keeps what was received so the transfer can resume.
+    pub fn receive<S, P, D, W, K>(
+        &mut self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        sink: &mut K,
+        timeout_us: u32,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<Offer, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+        K: BlockSink,
+    {
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let mut frame = [0u8; 16];
+
+        let mut offer = loop {
+            let len = hc14.receive_frame_timeout(&mut buffer, timeout_us)?.len();
+            if let Some(body) = self.body(&buffer[..len], OFFER) {
+                if body.len() >= 4 {
+                    let mut name = String::new();
+                    let text = core::str::from_utf8(&body[4..]).map_err(|_| Error::Utf8)?;
+                    name.push_str(text).map_err(|_| Error::MessageTooLarge)?;
+                    break Offer {
+                        name,
+                        size: u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+                    };
+                }
+            }
+        };
+        let mut expected = match sink.begin(&offer.name, offer.size) {
+            Ok(offset) => offset.min(offer.size),
+            Err(e) => {
+                self.cancel(hc14, &mut frame, CANCEL_STORAGE)?;
+                return Err(e);
+            }
+        };
+        self.reply(hc14, &mut frame, ACCEPT, Some(expected))?;
+        progress(Progress {
+            transferred: expected,
+            total: offer.size,
+        });
+
+        let idle_us = self.idle_timeout_us(hc14);
+        loop {
+            let len = hc14.receive_frame_timeout(&mut buffer, idle_us)?.len();
+            let frame_in = &buffer[..len];
+            if let Some(body) = self.body(frame_in, DATA) {
+                if body.len() < 7 {
+                    continue;
+                }
+                let offset = u32::from_be_bytes([body[1], body[2], body[3], body[4]]);
+                let crc = u16::from_be_bytes([body[5], body[6]]);
+                let data = &body[7..];
+                let intact = crc16(data) == crc;
+                if !intact {
+                    stats::add(&mut hc14.stats_mut().crc_errors, 1);
+                }
+                if offset == expected && intact && !data.is_empty() {
+                    if let Err(e) = sink.write(offset, data) {
+                        self.cancel(hc14, &mut frame, CANCEL_STORAGE)?;
+                        return Err(e);
+                    }
+                    expected += data.len() as u32;
+                    progress(Progress {
+                        transferred: expected,
+                        total: offer.size,
+                    });
+                }
+                if body[0] & FLAG_ACK_REQUEST != 0 {
+                    self.reply(hc14, &mut frame, ACK, Some(expected))?;
+                }
+            } else if self.body(frame_in, OFFER).is_some() {
+                // 发送端没有收到 ACCEPT(The sender missed the ACCEPT)
+                self.reply(hc14, &mut frame, ACCEPT, Some(expected))?;
+            } else if self.body(frame_in, DONE).is_some() {
+                if expected < offer.size {
+                    self.reply(hc14, &mut frame, ACK, Some(expected))?;
+                    continue;
+                }
+                if let Err(e) = sink.finish() {
+                    self.cancel(hc14, &mut frame, CANCEL_REJECTED)?;
+                    return Err(e);
+                }
+                self.reply(hc14, &mut frame, DONE_ACK, None)?;
+                offer.size = expected;
+                return Ok(offer);
+            }
+        }
+    }
+
+    // ---------------------------------------------------------------------------------------------
+
+    /// 写入消息头部(类型和会话号)，返回其长度
+    ///
+    /// Write the message header (kind and session), returns its length.
+    fn header(&self, kind: u8, frame: &mut [u8]) -> usize {
+        frame[0] = kind;
+        frame[1..3].copy_from_slice(&self.session.to_be_bytes());
+        3
+    }
+
+    /// 属于本会话且类型为 `kind` 的消息，返回头部之后的内容
+    ///
+    /// A message of this session with kind `kind`, returns what follows the header.
+    fn body<'b>(&self, frame: &'b [u8], kind: u8) -> Option<&'b [u8]> {
+        if frame.len() < 3 || frame[0] != kind || frame[1..3] != self.session.to_be_bytes() {
+            return None;
+        }
+        Some(&frame[3..])
+    }
+
+    /// 一个数据包往返所需的等待(微秒)
+    ///
+    /// Wait for one packet round trip, in microseconds.
+    fn reply_timeout_us<S, P, D, W>(&self, hc14: &Hc14<S, P, D, Normal, W>) -> u32
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let speed = hc14.speed();
+        ((speed.get_first_packet_delay_ms() + speed.get_packet_delay_ms()) * 1000)
+            .saturating_add(self.turnaround_us)
+    }
+
+    /// 接收端认为链路中断前的静默时间(微秒)，超出 `u32` 时取最大值
+    ///
+    /// Silence after which the receiver considers the link broken, in microseconds, clamped to
+    /// `u32`.
+    fn idle_timeout_us<S, P, D, W>(&self, hc14: &Hc14<S, P, D, Normal, W>) -> u32
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let window = u64::from(self.window) * u64::from(hc14.speed().get_packet_delay_ms()) * 1000;
+        let idle =
+            (u64::from(self.retries) + 1) * (u64::from(self.reply_timeout_us(hc14)) + window);
+        u32::try_from(idle).unwrap_or(u32::MAX)
+    }
+
+    /// 等待类型为 `kind` 的应答，返回其中的偏移(若有)；超时返回 `None`
+    ///
+    /// Wait for a reply of kind `kind`, returns the offset it carries (if any); `None` on timeout.
+    fn wait_reply<S, P, D, W>(
+        &self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        buffer: &mut [u8; MAX_FRAME_SIZE],
+        kind: u8,
+    ) -> Result<Option<u32>, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let timeout_us = self.reply_timeout_us(hc14);
+        let mut elapsed: u32 = 0;
+        loop {
+            let len = match hc14.receive_frame_timed(buffer, timeout_us) {
+                Ok((len, waited)) => {
+                    elapsed = elapsed.saturating_add(waited);
+                    len
+                }
+                Err(Error::Timeout) => {
+                    stats::add(&mut hc14.stats_mut().timeouts, 1);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(body) = self.body(&buffer[..len], CANCEL) {
+                return Err(Error::Rejected(body.first().copied().unwrap_or(0)));
+            }
+            if let Some(body) = self.body(&buffer[..len], kind) {
+                hc14.stats_mut().record_round_trip(self.session, elapsed);
+                return Ok(Some(match body.get(..4) {
+                    Some(b) => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+                    None => 0,
+                }));
+            }
+        }
+    }
+
+    /// 发送消息并等待应答，超时重发
+    ///
+    /// Send a message and wait for the reply, resending on timeout.
+    fn exchange<S, P, D, W>(
+        &self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        message: &[u8],
+        buffer: &mut [u8; MAX_FRAME_SIZE],
+        kind: u8,
+    ) -> Result<u32, Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                stats::add(&mut hc14.stats_mut().retries, 1);
+            }
+            hc14.send_frame(message)?;
+            if let Some(offset) = self.wait_reply(hc14, buffer, kind)? {
+                return Ok(offset);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    fn reply<S, P, D, W>(
+        &self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        frame: &mut [u8; 16],
+        kind: u8,
+        offset: Option<u32>,
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let mut len = self.header(kind, frame);
+        if let Some(offset) = offset {
+            frame[len..len + 4].copy_from_slice(&offset.to_be_bytes());
+            len += 4;
+        }
+        hc14.send_frame(&frame[..len])
+    }
+
+    fn cancel<S, P, D, W>(
+        &self,
+        hc14: &mut Hc14<S, P, D, Normal, W>,
+        frame: &mut [u8; 16],
+        reason: u8,
+    ) -> Result<(), Error>
+    where
+        S: Read<u8> + Write<u8>,
+        P: OutputPin,
+        D: DelayUs<u32>,
+        W: OutputPin,
+    {
+        let len = self.header(CANCEL, frame);
+        frame[len] = reason;
+        hc14.send_frame(&frame[..=len])
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use core::convert::Infallible;
+    use std::{vec, vec::Vec};
+
+    use super::*;
+    use crate::{
+        driver::frame::cobs_decode,
+        setting::{parameters::Parameters, speed::Speed},
+        sim::{SimConfig, SimDelay, SimPin, SimSerial, Simulator},
+    };
+
+    pub(crate) const SESSION: u16 = 7;
+
+    pub(crate) type Radio<S> = Hc14<S, SimPin, SimDelay, Normal>;
+
+    /// 由测试编写的对端(Peer scripted by the test)
+    pub(crate) trait Script {
+        fn on_frame(&mut self, peer: &mut Radio<SimSerial>, frame: &[u8]);
+    }
+
+    /// 被测一端的串口：等待数据时让对端处理它收到的帧
+    /// (Serial port of the end under test: while it waits for data, the peer handles the frames it
+    /// received)
+    pub(crate) struct Peered<T: Script> {
+        serial: SimSerial,
+        pub(crate) peer: Radio<SimSerial>,
+        /// 对端收到的不完整的帧(Incomplete frame received by the peer)
+        pending: Vec<u8>,
+        script: T,
+    }
+
+    impl<T: Script> Read<u8> for Peered<T> {
+        type Error = Infallible;
+
+        fn read(&mut self) -> nb::Result<u8, Infallible> {
+            let read = self.serial.read();
+            if let Err(nb::Error::WouldBlock) = read {
+                let mut buffer = [0u8; MAX_FRAME_SIZE];
+                let received = self.peer.read_available(&mut buffer).unwrap_or(&[]);
+                for &byte in received {
+                    if byte != 0 {
+                        self.pending.push(byte);
+                        continue;
+                    }
+                    let mut raw = [0u8; MAX_FRAME_SIZE];
+                    let n = cobs_decode(&self.pending, &mut raw).unwrap();
+                    self.pending.clear();
+                    self.script.on_frame(&mut self.peer, &raw[1..n]);
+                }
+            }
+            read
+        }
+    }
+
+    impl<T: Script> Write<u8> for Peered<T> {
+        type Error = Infallible;
+
+        fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+            self.serial.write(word)
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Infallible> {
+            self.serial.flush()
+        }
+    }
+
+    /// 被测的一端和由 `script` 驱动的对端；`setup` 在对端开始前使用它
+    /// (The end under test and a peer driven by `script`; `setup` uses the peer before it starts)
+    pub(crate) fn link<T: Script>(
+        sim: &mut Simulator,
+        script: T,
+        setup: impl FnOnce(&mut Radio<SimSerial>),
+    ) -> Radio<Peered<T>> {
+        let [a, b] = [
+            sim.add_node(Parameters::default()),
+            sim.add_node(Parameters::default()),
+        ];
+        let mut peer = Hc14::new(b.serial, b.key, b.delay).unwrap();
+        setup(&mut peer);
+        let serial = Peered {
+            serial: a.serial,
+            peer,
+            pending: Vec::new(),
+            script,
+        };
+        Hc14::new(serial, a.key, a.delay).unwrap()
+    }
+
+    fn script<T: Script>(hc14: Radio<Peered<T>>) -> T {
+        hc14.release().0.script
+    }
+
+    /// 让对端处理被测一端最后发出的帧(Let the peer handle the last frames sent by the end under test)
+    fn settle<T: Script>(hc14: &mut Radio<Peered<T>>) {
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        for _ in 0..5_000 {
+            hc14.read_available(&mut buffer).unwrap();
+        }
+    }
+
+    pub(crate) fn message(kind: u8, body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 3];
+        Transfer::new(SESSION).header(kind, &mut frame);
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    fn offset(body: &[u8]) -> u32 {
+        u32::from_be_bytes([body[0], body[1], body[2], body[3]])
+    }
+
+    fn source() -> Vec<u8> {
+        (0..300u32).map(|i| (i * 7) as u8).collect()
+    }
+
+    /// 按协议应答的接收端(Receiver answering per the protocol)
+    #[derive(Default)]
+    struct FakeReceiver {
+        data: Vec<u8>,
+        /// 丢弃一次该偏移的数据块(Drop the block at this offset once)
+        drop_once: Option<u32>,
+        /// 总是确认该偏移(Always acknowledge this offset)
+        ack: Option<u32>,
+        /// 收到 DONE 时取消(Cancel on DONE)
+        cancel_on_done: Option<u8>,
+        data_frames: usize,
+        done: bool,
+    }
+
+    impl Script for FakeReceiver {
+        fn on_frame(&mut self, peer: &mut Radio<SimSerial>, frame: &[u8]) {
+            let body = &frame[3..];
+            let reply = match frame[0] {
+                OFFER => message(ACCEPT, &(self.data.len() as u32).to_be_bytes()),
+                DATA => {
+                    self.data_frames += 1;
+                    let at = offset(&body[1..]);
+                    let data = &body[7..];
+                    if self.drop_once == Some(at) {
+                        self.drop_once = None;
+                    } else if at as usize == self.data.len() {
+                        assert_eq!(crc16(data).to_be_bytes(), body[5..7]);
+                        self.data.extend_from_slice(data);
+                    }
+                    if body[0] & FLAG_ACK_REQUEST == 0 {
+                        return;
+                    }
+                    let next = self.ack.unwrap_or(self.data.len() as u32);
+                    message(ACK, &next.to_be_bytes())
+                }
+                DONE => match self.cancel_on_done {
+                    Some(reason) => message(CANCEL, &[reason]),
+                    None => {
+                        self.done = true;
+                        message(DONE_ACK, &[])
+                    }
+                },
+                _ => return,
+            };
+            peer.send_frame(&reply).unwrap();
+        }
+    }
+
+    /// 一次发出剩余全部数据块的发送端(Sender putting out every remaining block at once)
+    #[derive(Default)]
+    pub(crate) struct FakeSender {
+        source: Vec<u8>,
+        /// 第一个数据块带错误的 CRC(The first block carries a bad CRC)
+        corrupt_once: bool,
+        /// 在发送任何数据前先发出 DONE(Send DONE before any data)
+        done_early: bool,
+        accepted_at: Option<u32>,
+        acks: Vec<u32>,
+        done_acked: bool,
+    }
+
+    impl FakeSender {
+        pub(crate) fn new(source: Vec<u8>) -> Self {
+            Self {
+                source,
+                ..Self::default()
+            }
+        }
+
+        fn send_from(&mut self, peer: &mut Radio<SimSerial>, from: u32) {
+            let block = peer.max_frame_payload() - BLOCK_HEADER_LEN;
+            let mut at = from as usize;
+            while at < self.source.len() {
+                let chunk = &self.source[at..self.source.len().min(at + block)];
+                let last = at + chunk.len() == self.source.len();
+                let mut crc = crc16(chunk);
+                if self.corrupt_once {
+                    self.corrupt_once = false;
+                    crc ^= 1;
+                }
+                let mut body = vec![if last { FLAG_ACK_REQUEST } else { 0 }];
+                body.extend_from_slice(&(at as u32).to_be_bytes());
+                body.extend_from_slice(&crc.to_be_bytes());
+                body.extend_from_slice(chunk);
+                peer.send_frame(&message(DATA, &body)).unwrap();
+                at += chunk.len();
+            }
+        }
+
+        fn send_done(&mut self, peer: &mut Radio<SimSerial>) {
+            let size = self.source.len() as u32;
+            peer.send_frame(&message(DONE, &size.to_be_bytes()))
+                .unwrap();
+        }
+
+        pub(crate) fn offer(&self, peer: &mut Radio<SimSerial>) {
+            let mut body = (self.source.len() as u32).to_be_bytes().to_vec();
+            body.extend_from_slice(b"node.cfg");
+            peer.send_frame(&message(OFFER, &body)).unwrap();
+        }
+    }
+
+    impl Script for FakeSender {
+        fn on_frame(&mut self, peer: &mut Radio<SimSerial>, frame: &[u8]) {
+            let body = &frame[3..];
+            match frame[0] {
+                ACCEPT => {
+                    let at = offset(body);
+                    self.accepted_at.get_or_insert(at);
+                    if self.done_early {
+                        self.done_early = false;
+                        self.send_done(peer);
+                    } else {
+                        self.send_from(peer, at);
+                    }
+                }
+                ACK => {
+                    let at = offset(body);
+                    self.acks.push(at);
+                    if at as usize >= self.source.len() {
+                        self.send_done(peer);
+                    } else {
+                        self.send_from(peer, at);
+                    }
+                }
+                DONE_ACK => self.done_acked = true,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn sends_a_whole_file() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let mut hc14 = link(&mut sim, FakeReceiver::default(), |_| {});
+        let data = source();
+        let mut seen = Vec::new();
+
+        Transfer::new(SESSION)
+            .send(&mut hc14, "node.cfg", &mut &data[..], |p| seen.push(p))
+            .unwrap();
+        assert_eq!(seen.first().unwrap().transferred, 0);
+        assert_eq!(seen.last().unwrap().percent(), 100);
+        let receiver = script(hc14);
+        assert_eq!(receiver.data, data);
+        assert!(receiver.done);
+        assert_eq!(receiver.data_frames, 5);
+    }
+
+    #[test]
+    fn sender_resumes_where_the_receiver_stopped() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let data = source();
+        let receiver = FakeReceiver {
+            data: data[..134].to_vec(),
+            ..FakeReceiver::default()
+        };
+        let mut hc14 = link(&mut sim, receiver, |_| {});
+        let mut seen = Vec::new();
+
+        Transfer::new(SESSION)
+            .send(&mut hc14, "node.cfg", &mut &data[..], |p| seen.push(p))
+            .unwrap();
+        assert_eq!(seen.first().unwrap().transferred, 134);
+        let receiver = script(hc14);
+        assert_eq!(receiver.data, data);
+        assert_eq!(receiver.data_frames, 3);
+    }
+
+    #[test]
+    fn dropped_block_goes_back_n() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let receiver = FakeReceiver {
+            drop_once: Some(67),
+            ..FakeReceiver::default()
+        };
+        let mut hc14 = link(&mut sim, receiver, |_| {});
+        let data = source();
+        assert_eq!(Transfer::new(SESSION).block_size(&hc14), 67);
+
+        Transfer::new(SESSION)
+            .send(&mut hc14, "node.cfg", &mut &data[..], |_| {})
+            .unwrap();
+        let receiver = script(hc14);
+        assert_eq!(receiver.data, data);
+        // 第一个窗口 4 块，之后从偏移 67 重发 4 块(A window of 4 blocks, then 4 again from offset 67)
+        assert_eq!(receiver.data_frames, 8);
+    }
+
+    #[test]
+    fn acknowledgements_without_progress_give_up() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let receiver = FakeReceiver {
+            ack: Some(0),
+            ..FakeReceiver::default()
+        };
+        let mut hc14 = link(&mut sim, receiver, |_| {});
+        let data = source();
+        let mut transfer = Transfer::new(SESSION);
+        transfer.set_retries(2);
+
+        assert!(matches!(
+            transfer.send(&mut hc14, "node.cfg", &mut &data[..], |_| {}),
+            Err(Error::Timeout)
+        ));
+        assert_eq!(hc14.stats().retries, 3);
+        assert_eq!(script(hc14).data_frames, 12);
+    }
+
+    #[test]
+    fn receiver_cancellation_stops_the_sender() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let receiver = FakeReceiver {
+            cancel_on_done: Some(CANCEL_REJECTED),
+            ..FakeReceiver::default()
+        };
+        let mut hc14 = link(&mut sim, receiver, |_| {});
+        let data = source();
+
+        assert!(matches!(
+            Transfer::new(SESSION).send(&mut hc14, "node.cfg", &mut &data[..], |_| {}),
+            Err(Error::Rejected(CANCEL_REJECTED))
+        ));
+    }
+
+    #[test]
+    fn receives_a_whole_file() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let sender = FakeSender::new(source());
+        let mut hc14 = link(&mut sim, sender, |peer| {
+            FakeSender::new(source()).offer(peer)
+        });
+        let mut storage = [0u8; 512];
+        let mut sink = SliceSink::new(&mut storage);
+
+        let offer = Transfer::new(SESSION)
+            .receive(&mut hc14, &mut sink, 10_000_000, |_| {})
+            .unwrap();
+        assert_eq!((offer.name.as_str(), offer.size), ("node.cfg", 300));
+        assert!(sink.is_complete());
+        assert_eq!(sink.data(), source());
+        let sender = script(hc14);
+        assert_eq!(sender.accepted_at, Some(0));
+        assert_eq!(sender.acks, [300]);
+    }
+
+    #[test]
+    fn receiver_resumes_from_a_partial_sink() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let data = source();
+        let sender = FakeSender {
+            source: data.clone(),
+            ..FakeSender::default()
+        };
+        let mut hc14 = link(&mut sim, sender, |peer| {
+            FakeSender::new(source()).offer(peer)
+        });
+        let mut storage = [0u8; 512];
+        let mut sink = SliceSink::new(&mut storage);
+        sink.begin("node.cfg", 300).unwrap();
+        sink.write(0, &data[..100]).unwrap();
+        let mut seen = Vec::new();
+
+        Transfer::new(SESSION)
+            .receive(&mut hc14, &mut sink, 10_000_000, |p| seen.push(p))
+            .unwrap();
+        assert_eq!(seen.first().unwrap().transferred, 100);
+        assert_eq!(sink.data(), data);
+        assert_eq!(script(hc14).accepted_at, Some(100));
+    }
+
+    #[test]
+    fn blocks_with_a_bad_crc_are_rejected() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let sender = FakeSender {
+            source: source(),
+            corrupt_once: true,
+            ..FakeSender::default()
+        };
+        let mut hc14 = link(&mut sim, sender, |peer| {
+            FakeSender::new(source()).offer(peer)
+        });
+        let mut storage = [0u8; 512];
+        let mut sink = SliceSink::new(&mut storage);
+
+        Transfer::new(SESSION)
+            .receive(&mut hc14, &mut sink, 10_000_000, |_| {})
+            .unwrap();
+        assert_eq!(sink.data(), source());
+        assert_eq!(hc14.stats().crc_errors, 1);
+        // 损坏的块之后的块都被丢弃，从头重发(Everything after the bad block is dropped and resent)
+        assert_eq!(script(hc14).acks, [0, 300]);
+    }
+
+    #[test]
+    fn done_before_the_data_is_complete_is_answered_with_an_ack() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let sender = FakeSender {
+            source: source(),
+            done_early: true,
+            ..FakeSender::default()
+        };
+        let mut hc14 = link(&mut sim, sender, |peer| {
+            FakeSender::new(source()).offer(peer)
+        });
+        let mut storage = [0u8; 512];
+        let mut sink = SliceSink::new(&mut storage);
+
+        Transfer::new(SESSION)
+            .receive(&mut hc14, &mut sink, 10_000_000, |_| {})
+            .unwrap();
+        assert_eq!(sink.data(), source());
+        settle(&mut hc14);
+        let sender = script(hc14);
+        assert_eq!(sender.acks, [0, 300]);
+        assert!(sender.done_acked);
+    }
+
+    #[test]
+    fn storage_failure_cancels_the_transfer() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let sender = FakeSender::new(source());
+        let mut hc14 = link(&mut sim, sender, |peer| {
+            FakeSender::new(source()).offer(peer)
+        });
+        // 300 字节放不进 64 字节的存储(300 bytes do not fit 64 bytes of storage)
+        let mut storage = [0u8; 64];
+        let mut sink = SliceSink::new(&mut storage);
+
+        assert!(matches!(
+            Transfer::new(SESSION).receive(&mut hc14, &mut sink, 10_000_000, |_| {}),
+            Err(Error::MessageTooLarge)
+        ));
+        let mut peer = hc14.release().0.peer;
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        let frame = peer.receive_frame_timeout(&mut buffer, 10_000_000).unwrap();
+        assert_eq!(frame, message(CANCEL, &[CANCEL_STORAGE]));
+    }
+
+    #[test]
+    fn idle_timeout_saturates() {
+        let mut sim = Simulator::new(SimConfig::default());
+        let node = sim.add_node(Parameters::default());
+        let mut hc14 = Hc14::new(node.serial, node.key, node.delay).unwrap();
+        hc14.set_speed(Speed::S1);
+        let mut transfer = Transfer::new(SESSION);
+        transfer.set_retries(255);
+        transfer.set_window(255);
+        transfer.set_turnaround_us(u32::MAX);
+
+        assert_eq!(transfer.idle_timeout_us(&hc14), u32::MAX);
+        assert_eq!(transfer.reply_timeout_us(&hc14), u32::MAX);
+    }
+}